@@ -0,0 +1,240 @@
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+use log::{info, warn};
+use serde::Serialize;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+use tokio::time::{interval, MissedTickBehavior};
+
+use crate::db::schema::{Addr, Interface, Stat};
+use crate::interface::sample::unix_timestamp;
+use super::notify::Notifier;
+use super::rules::{stat_rate, AlertConfiguration, Condition, Rule};
+
+// Conditions without a stream of samples (down, no_address) are checked for their hold time on this tick
+const HOLD_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+pub enum AlertInput {
+    // Deltas of a stats tick
+    Stats(Vec<Stat>),
+    // Link details and addresses of the filtered interfaces of a namespace, after every change
+    Interfaces { netns: String, links: Vec<Interface>, addrs: Vec<Addr> },
+}
+
+// Feeds the alert engine, does nothing when no rule is configured
+#[derive(Debug, Clone, Default)]
+pub struct AlertSender(Option<UnboundedSender<AlertInput>>);
+
+impl AlertSender {
+
+    pub fn is_enabled(&self) -> bool {
+        self.0.is_some()
+    }
+
+    pub fn send(&self, input: AlertInput) {
+        if let Some(sender) = &self.0 {
+            // The engine only stops with the daemon
+            sender.send(input).ok();
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Notification {
+    // firing or resolved
+    pub status: String,
+    pub rule: String,
+    pub condition: String,
+    pub server_id: String,
+    pub netns: String,
+    pub interface: String,
+    // Latest rate of rate conditions
+    pub value: Option<f64>,
+    // When the condition started to hold
+    pub since: u32,
+    pub timestamp: u32,
+}
+
+#[derive(Debug)]
+struct AlertState {
+    since: Instant,
+    since_timestamp: u32,
+    value: Option<f64>,
+    firing: bool,
+}
+
+// Rule index, namespace and interface
+type AlertKey = (usize, String, String);
+
+pub struct AlertEngine {
+    server_id: String,
+    rules: Vec<Rule>,
+    // Conditions currently holding, firing once held for long enough
+    states: HashMap<AlertKey, AlertState>,
+}
+
+impl AlertEngine {
+
+    pub fn new(server_id: &str, rules: Vec<Rule>) -> Self {
+        AlertEngine { server_id: server_id.to_string(), rules, states: HashMap::new() }
+    }
+
+    pub fn handle(&mut self, input: AlertInput, now: Instant, timestamp: u32) -> Vec<Notification> {
+        let mut notifications = Vec::new();
+        match input {
+            AlertInput::Stats(stats) => {
+                for stat in &stats {
+                    for index in 0..self.rules.len() {
+                        let rule = &self.rules[index];
+                        let Condition::Rate { metric, above, threshold } = &rule.condition else {
+                            continue;
+                        };
+                        if !rule.matches(&stat.interface) {
+                            continue;
+                        }
+                        let Some(rate) = stat_rate(stat, metric) else {
+                            continue;
+                        };
+                        let active = if *above { rate > *threshold } else { rate < *threshold };
+                        let key = (index, stat.netns.clone(), stat.interface.clone());
+                        notifications.extend(self.observe(key, active, Some(rate), now, timestamp));
+                    }
+                }
+            }
+            AlertInput::Interfaces { netns, links, addrs } => {
+                let addrs: HashMap<&String, &Addr> = addrs.iter().map(|addr| (&addr.interface, addr)).collect();
+
+                for link in &links {
+                    for index in 0..self.rules.len() {
+                        let rule = &self.rules[index];
+                        if !rule.matches(&link.interface) {
+                            continue;
+                        }
+                        let active = match rule.condition {
+                            Condition::Down => is_down(link),
+                            Condition::NoAddress => addrs.get(&link.interface)
+                                .is_none_or(|addr| addr.ipv6.iter().all(|(ip, _)| ip.is_none())),
+                            Condition::Rate { .. } => continue,
+                        };
+                        let key = (index, netns.clone(), link.interface.clone());
+                        notifications.extend(self.observe(key, active, None, now, timestamp));
+                    }
+                }
+
+                // Interfaces that were deleted or no longer match the filter
+                let present: HashSet<&String> = links.iter().map(|link| &link.interface).collect();
+                let gone: Vec<AlertKey> = self.states.keys()
+                    .filter(|(_, state_netns, interface)| *state_netns == netns && !present.contains(interface))
+                    .cloned()
+                    .collect();
+                for key in gone {
+                    notifications.extend(self.observe(key, false, None, now, timestamp));
+                }
+            }
+        }
+        notifications
+    }
+
+    // Fires the conditions that have now held for long enough
+    pub fn tick(&mut self, now: Instant, timestamp: u32) -> Vec<Notification> {
+        let pending: Vec<AlertKey> = self.states.iter()
+            .filter(|(_, state)| !state.firing)
+            .map(|(key, _)| key.clone())
+            .collect();
+        pending.into_iter()
+            .filter_map(|key| {
+                let value = self.states.get(&key)?.value;
+                self.observe(key, true, value, now, timestamp)
+            })
+            .collect()
+    }
+
+    // A notification when the alert starts firing and one when it resolves, nothing in between
+    fn observe(&mut self, key: AlertKey, active: bool, value: Option<f64>, now: Instant, timestamp: u32) -> Option<Notification> {
+        if !active {
+            let state = self.states.remove(&key)?;
+            return state.firing.then(|| self.notification(&key, "resolved", value.or(state.value), state.since_timestamp, timestamp));
+        }
+
+        let hold = self.rules[key.0].hold;
+        let state = self.states.entry(key.clone()).or_insert(AlertState {
+            since: now,
+            since_timestamp: timestamp,
+            value,
+            firing: false,
+        });
+        state.value = value;
+        if state.firing || now.saturating_duration_since(state.since) < hold {
+            return None;
+        }
+        state.firing = true;
+        let since = state.since_timestamp;
+        Some(self.notification(&key, "firing", value, since, timestamp))
+    }
+
+    fn notification(&self, key: &AlertKey, status: &str, value: Option<f64>, since: u32, timestamp: u32) -> Notification {
+        let rule = &self.rules[key.0];
+        Notification {
+            status: status.to_string(),
+            rule: rule.name.clone(),
+            condition: rule.expression.clone(),
+            server_id: self.server_id.clone(),
+            netns: key.1.clone(),
+            interface: key.2.clone(),
+            value,
+            since,
+            timestamp,
+        }
+    }
+}
+
+pub fn is_down(link: &Interface) -> bool {
+    !link.admin_up || matches!(link.oper_state.as_str(), "down" | "lowerlayerdown" | "notpresent")
+}
+
+// Starts the engine, the sender is disabled without alerting configuration
+pub fn start_alerts(alerting: Option<&AlertConfiguration>, server_id: &str) -> AlertSender {
+    let Some(alerting) = alerting else {
+        return AlertSender::default();
+    };
+
+    let (sender, inputs) = unbounded_channel();
+    let engine = AlertEngine::new(server_id, alerting.rules.clone());
+    let notifier = Notifier::new(alerting);
+    tokio::spawn(evaluate_alerts(engine, notifier, inputs));
+
+    info!("Evaluating {} alert rules.", alerting.rules.len());
+    AlertSender(Some(sender))
+}
+
+async fn evaluate_alerts(mut engine: AlertEngine, notifier: Notifier, mut inputs: UnboundedReceiver<AlertInput>) {
+    // Slow webhooks or commands must not hold back the evaluation, delivery keeps the order
+    let (deliveries, mut pending) = unbounded_channel::<Notification>();
+    tokio::spawn(async move {
+        while let Some(notification) = pending.recv().await {
+            notifier.deliver(&notification).await;
+        }
+    });
+
+    let mut hold_timer = interval(HOLD_CHECK_INTERVAL);
+    hold_timer.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+    loop {
+        let notifications = tokio::select! {
+            input = inputs.recv() => {
+                let Some(input) = input else {
+                    return;
+                };
+                engine.handle(input, Instant::now(), unix_timestamp())
+            },
+            _ = hold_timer.tick() => engine.tick(Instant::now(), unix_timestamp())
+        };
+
+        for notification in notifications {
+            match notification.status.as_str() {
+                "firing" => warn!("Alert {} is firing on {}: {}", notification.rule, notification.interface, notification.condition),
+                _ => info!("Alert {} resolved on {}", notification.rule, notification.interface),
+            }
+            deliveries.send(notification).ok();
+        }
+    }
+}
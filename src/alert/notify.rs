@@ -0,0 +1,104 @@
+use std::process::Stdio;
+use std::time::Duration;
+use bytes::Bytes;
+use http_body_util::Full;
+use hyper::{Method, Request, Uri};
+use hyper_util::client::legacy::{connect::HttpConnector, Client};
+use hyper_util::rt::TokioExecutor;
+use log::error;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use tokio::time::timeout;
+
+use super::engine::Notification;
+use super::rules::AlertConfiguration;
+
+// A hung endpoint or command must not hold back the next notifications forever
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub struct Notifier {
+    client: Client<HttpConnector, Full<Bytes>>,
+    webhooks: Vec<Uri>,
+    commands: Vec<String>,
+}
+
+impl Notifier {
+
+    pub fn new(alerting: &AlertConfiguration) -> Self {
+        Notifier {
+            client: Client::builder(TokioExecutor::new()).build_http(),
+            webhooks: alerting.webhooks.clone(),
+            commands: alerting.commands.clone(),
+        }
+    }
+
+    // Failures are logged, a notification isn't retried
+    pub async fn deliver(&self, notification: &Notification) {
+        let body = match serde_json::to_vec(notification) {
+            Ok(body) => body,
+            Err(e) => {
+                error!("Failed to serialize alert {}: {e}", notification.rule);
+                return;
+            }
+        };
+
+        for webhook in &self.webhooks {
+            match timeout(DELIVERY_TIMEOUT, self.post(webhook, &body)).await {
+                Ok(Ok(())) => (),
+                Ok(Err(e)) => error!("Failed to send alert {} to {webhook}: {e}", notification.rule),
+                Err(_) => error!("Sending alert {} to {webhook} timed out", notification.rule),
+            }
+        }
+
+        for command in &self.commands {
+            match timeout(DELIVERY_TIMEOUT, run_command(command, notification, &body)).await {
+                Ok(Ok(())) => (),
+                Ok(Err(e)) => error!("Alert command {command} failed for {}: {e}", notification.rule),
+                Err(_) => error!("Alert command {command} timed out for {}", notification.rule),
+            }
+        }
+    }
+
+    async fn post(&self, webhook: &Uri, body: &[u8]) -> Result<(), String> {
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri(webhook)
+            .header("content-type", "application/json")
+            .body(Full::new(Bytes::copy_from_slice(body)))
+            .map_err(|e| e.to_string())?;
+
+        let response = self.client.request(request).await.map_err(|e| e.to_string())?;
+        if !response.status().is_success() {
+            return Err(format!("HTTP {}", response.status()));
+        }
+        Ok(())
+    }
+}
+
+// The notification as JSON on stdin, the main fields in the environment for simple scripts
+async fn run_command(command: &str, notification: &Notification, body: &[u8]) -> Result<(), String> {
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .env("ALERT_STATUS", &notification.status)
+        .env("ALERT_RULE", &notification.rule)
+        .env("ALERT_CONDITION", &notification.condition)
+        .env("ALERT_SERVER_ID", &notification.server_id)
+        .env("ALERT_NETNS", &notification.netns)
+        .env("ALERT_INTERFACE", &notification.interface)
+        .stdin(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| e.to_string())?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        // The command may not read stdin at all
+        stdin.write_all(body).await.ok();
+    }
+
+    let status = child.wait().await.map_err(|e| e.to_string())?;
+    if !status.success() {
+        return Err(status.to_string());
+    }
+    Ok(())
+}
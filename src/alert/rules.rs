@@ -0,0 +1,145 @@
+use std::time::Duration;
+use hyper::Uri;
+use regex::Regex;
+
+use crate::config::parse_config::Alerting;
+use crate::db::schema::Stat;
+
+// Counters of the stat table a rate can be computed for
+pub const RATE_METRICS: [&str; 16] = [
+    "rx", "tx", "rx_p", "tx_p", "rx_d", "tx_d", "rx_e", "tx_e", "multicast", "collisions",
+    "rx_crc_e", "rx_frame_e", "rx_fifo_e", "rx_missed_e", "tx_carrier_e", "rx_nohandler",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Condition {
+    // Per second rate of a stat counter above or below the threshold
+    Rate { metric: String, above: bool, threshold: f64 },
+    // Administratively down or without a lower layer
+    Down,
+    // No address left on the interface
+    NoAddress,
+}
+
+#[derive(Debug, Clone)]
+pub struct Rule {
+    pub name: String,
+    // None matches every filtered interface
+    pub interface: Option<Regex>,
+    pub condition: Condition,
+    // As written in the configuration, sent with the notifications
+    pub expression: String,
+    pub hold: Duration,
+}
+
+#[derive(Debug, Clone)]
+pub struct AlertConfiguration {
+    pub rules: Vec<Rule>,
+    pub webhooks: Vec<Uri>,
+    pub commands: Vec<String>,
+}
+
+impl Rule {
+    pub fn matches(&self, interface: &str) -> bool {
+        self.interface.as_ref().is_none_or(|regex| regex.is_match(interface))
+    }
+}
+
+impl AlertConfiguration {
+
+    // None when no rule is configured
+    pub fn new(alerting: Alerting) -> Result<Option<Self>, String> {
+        let mut rules: Vec<Rule> = Vec::new();
+        for rule in alerting.rules.unwrap_or_default() {
+            if rule.name.is_empty() {
+                return Err("alert rules must have a name".to_string());
+            }
+            if rules.iter().any(|known| known.name == rule.name) {
+                return Err(format!("alert rule {} is defined more than once", rule.name));
+            }
+            let interface = rule.interface.as_deref()
+                .map(|pattern| Regex::new(pattern).map_err(|e| format!("alert rule {}: invalid interface pattern: {e}", rule.name)))
+                .transpose()?;
+            let condition = parse_condition(&rule.condition).map_err(|e| format!("alert rule {}: {e}", rule.name))?;
+
+            rules.push(Rule {
+                name: rule.name,
+                interface,
+                condition,
+                expression: rule.condition.trim().to_string(),
+                hold: Duration::from_secs(rule.for_s.unwrap_or(0)),
+            });
+        }
+
+        let mut webhooks = Vec::new();
+        for webhook in alerting.webhooks.unwrap_or_default() {
+            let uri: Uri = webhook.parse().map_err(|e| format!("invalid webhook URL {webhook}: {e}"))?;
+            // Same plain HTTP client as ClickHouse, HTTPS endpoints can be reached through a command
+            if uri.scheme_str() != Some("http") || uri.host().is_none() {
+                return Err(format!("webhook URL {webhook} must be http://host[:port]/path"));
+            }
+            webhooks.push(uri);
+        }
+        let commands = alerting.commands.unwrap_or_default();
+
+        if rules.is_empty() {
+            return Ok(None);
+        }
+        if webhooks.is_empty() && commands.is_empty() {
+            return Err("alert rules are configured without any webhook or command".to_string());
+        }
+        Ok(Some(AlertConfiguration { rules, webhooks, commands }))
+    }
+}
+
+// "<metric> > <value>[/s]", "<metric> < <value>[/s]", "down" or "no_address"
+pub fn parse_condition(condition: &str) -> Result<Condition, String> {
+    let condition = condition.trim();
+    match condition {
+        "down" => return Ok(Condition::Down),
+        "no_address" => return Ok(Condition::NoAddress),
+        _ => ()
+    }
+
+    let (metric, above, threshold) = if let Some((metric, threshold)) = condition.split_once('>') {
+        (metric, true, threshold)
+    } else if let Some((metric, threshold)) = condition.split_once('<') {
+        (metric, false, threshold)
+    } else {
+        return Err(format!("invalid condition {condition}, expected \"<metric> > <value>/s\", \"down\" or \"no_address\""));
+    };
+
+    let metric = metric.trim();
+    if !RATE_METRICS.contains(&metric) {
+        return Err(format!("unknown metric {metric}, expected one of {}", RATE_METRICS.join(", ")));
+    }
+    let threshold = threshold.trim();
+    let threshold: f64 = threshold.strip_suffix("/s").unwrap_or(threshold).trim().parse()
+        .map_err(|_| format!("invalid threshold {threshold}"))?;
+
+    Ok(Condition::Rate { metric: metric.to_string(), above, threshold })
+}
+
+// Per second rate of a counter over the interval of the sample
+pub fn stat_rate(stat: &Stat, metric: &str) -> Option<f64> {
+    let delta = match metric {
+        "rx" => stat.rx,
+        "tx" => stat.tx,
+        "rx_p" => stat.rx_p,
+        "tx_p" => stat.tx_p,
+        "rx_d" => stat.rx_d,
+        "tx_d" => stat.tx_d,
+        "rx_e" => stat.rx_e,
+        "tx_e" => stat.tx_e,
+        "multicast" => stat.multicast,
+        "collisions" => stat.collisions,
+        "rx_crc_e" => stat.rx_crc_e,
+        "rx_frame_e" => stat.rx_frame_e,
+        "rx_fifo_e" => stat.rx_fifo_e,
+        "rx_missed_e" => stat.rx_missed_e,
+        "tx_carrier_e" => stat.tx_carrier_e,
+        "rx_nohandler" => stat.rx_nohandler,
+        _ => return None
+    };
+    (stat.interval_ms > 0).then(|| delta as f64 * 1000.0 / stat.interval_ms as f64)
+}
@@ -1,6 +1,6 @@
 use clap::Parser;
 use crate::config::parse_cli;
-use super::parse_config::Server;
+use super::parse_config::{ Collector, Server };
 
 pub fn get_parameters_from_cli() -> Server {
     let cli = parse_cli::Cli::parse();
@@ -20,3 +20,28 @@ pub fn get_parameters_from_cli() -> Server {
     }
 
 }
+
+pub fn get_collector_parameters_from_cli() -> Collector {
+    let cli = parse_cli::Cli::parse();
+
+    Collector {
+        stats_interval_ms: cli.stats_interval_ms,
+        refresh_interval_ms: cli.refresh_interval_ms,
+        resync_interval_ms: cli.resync_interval_ms,
+        link_interval_ms: cli.link_interval_ms,
+        spool_path: cli.spool_path,
+        spool_max_mb: cli.spool_max_mb,
+        namespaces: if !cli.namespaces.is_empty() { Some(cli.namespaces) } else { None },
+        flow_interval_ms: cli.flow_interval_ms,
+        flow_prefix_v4: cli.flow_prefix_v4,
+        flow_prefix_v6: cli.flow_prefix_v6,
+        tcp_interval_ms: cli.tcp_interval_ms,
+        qdisc_interval_ms: cli.qdisc_interval_ms,
+        wg_interval_ms: cli.wg_interval_ms,
+        proto_interval_ms: cli.proto_interval_ms,
+        burst_interval_ms: cli.burst_interval_ms,
+        store: cli.store,
+        otlp_endpoint: cli.otlp_endpoint,
+        metrics_listen: cli.metrics_listen
+    }
+}
@@ -1,10 +1,18 @@
 use std::process;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::time::Duration;
 use clickhouse::Client;
 use config::{Config, Environment, File};
 use log::{info, error};
 use dotenv::dotenv;
 use crate::config::{logs::configure_logs, parse_cli};
 use crate::db::schema::Server;
+use crate::config::parse_config::{Alerting, Collector};
+use crate::interface::netns::Namespace;
+use crate::alert::rules::AlertConfiguration;
+use crate::db::store::StoreKind;
+use crate::export::otlp::parse_endpoint;
 use clap::Parser;
 use crate::config::{ config_file, cli };
 use super::get_server_info::get_machine_id;
@@ -18,7 +26,42 @@ pub struct DbConnection {
 
 #[derive(Debug, Clone)]
 pub struct ServerConfiguration {
-    config: Server
+    config: Server,
+    collector: CollectorConfiguration,
+    // None when no alert rule is configured
+    alerting: Option<AlertConfiguration>
+}
+
+
+#[derive(Debug, Clone)]
+pub struct CollectorConfiguration {
+    pub stats_interval: Duration,
+    pub refresh_interval: Duration,
+    pub resync_interval: Duration,
+    // Discovery reads the addresses of every server, it runs far less often than the refresh
+    pub link_interval: Duration,
+    pub spool_path: Option<PathBuf>,
+    pub spool_max_bytes: u64,
+    // Collected besides the daemon's own namespace
+    pub namespaces: Vec<Namespace>,
+    // None when conntrack flows aren't collected
+    pub flow_interval: Option<Duration>,
+    // Remote networks of flows and TCP metrics are aggregated to these prefixes
+    pub flow_prefix_v4: u8,
+    pub flow_prefix_v6: u8,
+    // None when TCP metrics aren't collected
+    pub tcp_interval: Option<Duration>,
+    // None when qdisc statistics aren't collected
+    pub qdisc_interval: Option<Duration>,
+    // None when WireGuard peers aren't collected
+    pub wg_interval: Option<Duration>,
+    pub proto_interval: Option<Duration>,
+    // None when only the stats ticks are sampled
+    pub burst_interval: Option<Duration>,
+    // Backend of the server, address and stat tables
+    pub store: StoreKind,
+    // None when the Prometheus endpoint is disabled
+    pub metrics_listen: Option<SocketAddr>
 }
 
 
@@ -102,7 +145,11 @@ impl ServerConfiguration {
     pub fn new(config: &Config) -> Self {
         let config_file_params = config_file::get_parameters_from_config_file(config);
         let cli_params = cli::get_parameters_from_cli();
-        let config_server = config_file_params.and_then(|cfg| cfg.server);
+        let cli_collector = cli::get_collector_parameters_from_cli();
+        let (config_server, config_collector, config_alerting) = match config_file_params {
+            Some(cfg) => (cfg.server, cfg.collector.unwrap_or_default(), cfg.alerting.unwrap_or_default()),
+            None => (None, Collector::default(), Alerting::default())
+        };
 
         // Extract all config values at once
         let (config_server_id, config_hostname, config_interface_filter,
@@ -143,11 +190,122 @@ impl ServerConfiguration {
             country, city, lat, lng, priority, center
         };
 
+        let collector = CollectorConfiguration::new(cli_collector, config_collector).unwrap_or_else(|err| {
+            error!("Configuration error: {}", err);
+            process::exit(1);
+        });
+
+        let alerting = AlertConfiguration::new(config_alerting).unwrap_or_else(|err| {
+            error!("Configuration error: {}", err);
+            process::exit(1);
+        });
+
         info!("Server configuration is valid");
-        ServerConfiguration { config: server }
+        ServerConfiguration { config: server, collector, alerting }
     }
 
     pub fn get_config(&self) -> &Server {
         &self.config
     }
+
+    pub fn get_collector(&self) -> &CollectorConfiguration {
+        &self.collector
+    }
+
+    pub fn get_alerting(&self) -> Option<&AlertConfiguration> {
+        self.alerting.as_ref()
+    }
+}
+
+
+impl CollectorConfiguration {
+
+    pub fn new(cli: Collector, config: Collector) -> Result<Self, String> {
+        // CLI takes precedence over config
+        let stats_interval_ms = cli.stats_interval_ms.or(config.stats_interval_ms).unwrap_or(1000);
+        let refresh_interval_ms = cli.refresh_interval_ms.or(config.refresh_interval_ms).unwrap_or(60_000);
+        let resync_interval_ms = cli.resync_interval_ms.or(config.resync_interval_ms).unwrap_or(60_000);
+        let link_interval_ms = cli.link_interval_ms.or(config.link_interval_ms).unwrap_or(600_000);
+
+        let spool_path = cli.spool_path.or(config.spool_path).map(PathBuf::from);
+        let spool_max_mb = cli.spool_max_mb.or(config.spool_max_mb).unwrap_or(100);
+
+        check_min("stats_interval_ms", stats_interval_ms, 10)?;
+        check_min("refresh_interval_ms", refresh_interval_ms, 1000)?;
+        check_min("resync_interval_ms", resync_interval_ms, 1000)?;
+        check_min("link_interval_ms", link_interval_ms, 1000)?;
+        check_min("spool_max_mb", spool_max_mb, 1)?;
+
+        let flow_interval_ms = cli.flow_interval_ms.or(config.flow_interval_ms);
+        let flow_prefix_v4 = cli.flow_prefix_v4.or(config.flow_prefix_v4).unwrap_or(24);
+        let flow_prefix_v6 = cli.flow_prefix_v6.or(config.flow_prefix_v6).unwrap_or(64);
+
+        check_min("flow_interval_ms", flow_interval_ms, 1000)?;
+        let tcp_interval_ms = cli.tcp_interval_ms.or(config.tcp_interval_ms);
+        check_min("tcp_interval_ms", tcp_interval_ms, 1000)?;
+        let qdisc_interval_ms = cli.qdisc_interval_ms.or(config.qdisc_interval_ms);
+        check_min("qdisc_interval_ms", qdisc_interval_ms, 1000)?;
+        let wg_interval_ms = cli.wg_interval_ms.or(config.wg_interval_ms);
+        check_min("wg_interval_ms", wg_interval_ms, 1000)?;
+        let proto_interval_ms = cli.proto_interval_ms.or(config.proto_interval_ms);
+        check_min("proto_interval_ms", proto_interval_ms, 1000)?;
+        let burst_interval_ms = cli.burst_interval_ms.or(config.burst_interval_ms);
+        check_min("burst_interval_ms", burst_interval_ms, 10)?;
+        if let Some(burst_interval_ms) = burst_interval_ms.filter(|&interval| interval >= stats_interval_ms) {
+            return Err(format!("burst_interval_ms must be below stats_interval_ms ({stats_interval_ms}), got {burst_interval_ms}"));
+        }
+        let otlp_endpoint = cli.otlp_endpoint.or(config.otlp_endpoint).as_deref().map(parse_endpoint).transpose()?;
+        let store = cli.store.or(config.store).as_deref()
+            .map(|store| StoreKind::parse(store, otlp_endpoint.clone()))
+            .transpose()?.unwrap_or_default();
+        if otlp_endpoint.is_some() && !matches!(store, StoreKind::Otlp(_)) {
+            return Err("otlp_endpoint is set but store is not otlp".to_string());
+        }
+        let metrics_listen = cli.metrics_listen.or(config.metrics_listen)
+            .map(|listen| listen.parse::<SocketAddr>().map_err(|e| format!("invalid metrics_listen {listen}: {e}")))
+            .transpose()?;
+        if flow_prefix_v4 > 32 {
+            return Err(format!("flow_prefix_v4 must be at most 32, got {flow_prefix_v4}"));
+        }
+        if flow_prefix_v6 > 128 {
+            return Err(format!("flow_prefix_v6 must be at most 128, got {flow_prefix_v6}"));
+        }
+
+        let mut namespaces: Vec<Namespace> = Vec::new();
+        for entry in cli.namespaces.or(config.namespaces).unwrap_or_default() {
+            let namespace = Namespace::parse(&entry)?;
+            if namespaces.iter().any(|ns| ns.path == namespace.path) {
+                return Err(format!("namespace {entry} is listed more than once"));
+            }
+            namespaces.push(namespace);
+        }
+
+        Ok(CollectorConfiguration {
+            stats_interval: Duration::from_millis(stats_interval_ms),
+            refresh_interval: Duration::from_millis(refresh_interval_ms),
+            resync_interval: Duration::from_millis(resync_interval_ms),
+            link_interval: Duration::from_millis(link_interval_ms),
+            spool_path,
+            spool_max_bytes: spool_max_mb * 1024 * 1024,
+            namespaces,
+            flow_interval: flow_interval_ms.map(Duration::from_millis),
+            flow_prefix_v4,
+            flow_prefix_v6,
+            tcp_interval: tcp_interval_ms.map(Duration::from_millis),
+            qdisc_interval: qdisc_interval_ms.map(Duration::from_millis),
+            wg_interval: wg_interval_ms.map(Duration::from_millis),
+            proto_interval: proto_interval_ms.map(Duration::from_millis),
+            burst_interval: burst_interval_ms.map(Duration::from_millis),
+            store,
+            metrics_listen
+        })
+    }
+}
+
+// Unset optional settings pass, their collector is disabled
+fn check_min(name: &str, value: impl Into<Option<u64>>, min: u64) -> Result<(), String> {
+    if let Some(value) = value.into().filter(|value| *value < min) {
+        return Err(format!("{name} must be at least {min}, got {value}"));
+    }
+    Ok(())
 }
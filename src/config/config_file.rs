@@ -28,10 +28,8 @@ pub fn get_parameters_from_config_file(config: &Config) -> Option<ServerConfig>
     let binding = config.get_string("config_path").ok()?;
     let conf_file = binding.as_str();
 
-    let config_exists: bool;
-
     // Check if the file exists
-    config_exists = fs::metadata(conf_file).is_ok();
+    let config_exists = fs::metadata(conf_file).is_ok();
 
     if config_exists {
         let config_toml = read_file(conf_file)?;
@@ -43,13 +41,15 @@ pub fn get_parameters_from_config_file(config: &Config) -> Option<ServerConfig>
         let cli_hostname = cli.ok().and_then(|s| s.hostname);
 
         // Override hostname or machine_id with cli parameter
-        let hostname = get_hostname(cli_hostname.or_else(|| config_hostname));
-        let machine_id = get_machine_id(cli_machine_id.or_else(|| config_machine_id));
+        let hostname = get_hostname(cli_hostname.or(config_hostname));
+        let machine_id = get_machine_id(cli_machine_id.or(config_machine_id));
 
         if let Some(server) = config_toml.server {
 
             let final_config = ServerConfig {
                 clickhouse: config_toml.clickhouse,
+                collector: config_toml.collector,
+                alerting: config_toml.alerting,
                 server: Some(Server {
                     server_id: Some(machine_id.0),
                     interface_filter: server.interface_filter,
@@ -4,7 +4,7 @@ use log::error;
 
 pub fn get_hostname(hostname: Option<String>) -> Option<String> {
     // Find hostname in /etc/hostname (if not set), if not found, return None
-    if let None = hostname {
+    if hostname.is_none() {
         return fs::read_to_string("/etc/hostname")
             .map(|s| s.trim().to_string())
             .inspect_err(|err| {
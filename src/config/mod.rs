@@ -1,4 +1,5 @@
 pub mod parse_cli;
+#[allow(clippy::module_inception)]
 pub mod config;
 pub mod parse_config;
 pub mod config_file;
@@ -62,5 +62,78 @@ pub struct Cli {
 
     /// Specifies the directory path for saving log files.
     #[arg(long, value_name = "Path")]
-    pub logs_path: Option<String>
+    pub logs_path: Option<String>,
+
+    /// How often interface statistics are collected [1000 default]
+    #[arg(long, value_name = "Milliseconds")]
+    pub stats_interval_ms: Option<u64>,
+
+    /// How often the list of filtered interfaces is refreshed [60000 default]
+    #[arg(long, value_name = "Milliseconds")]
+    pub refresh_interval_ms: Option<u64>,
+
+    /// How often addresses are fully resynchronized with the database [60000 default]
+    #[arg(long, value_name = "Milliseconds")]
+    pub resync_interval_ms: Option<u64>,
+
+    /// How often links to the other servers are discovered from their saved addresses [600000 default]
+    #[arg(long, value_name = "Milliseconds")]
+    pub link_interval_ms: Option<u64>,
+
+    /// Directory where stats are spooled while ClickHouse is unreachable [disabled default]
+    #[arg(long, value_name = "Path")]
+    pub spool_path: Option<String>,
+
+    /// Maximum spool size, oldest batches are dropped first [100 default]
+    #[arg(long, value_name = "Megabytes")]
+    pub spool_max_mb: Option<u64>,
+
+    /// Network namespaces collected besides the daemon's own: names under /var/run/netns,
+    /// paths or pids, --namespaces blue,/proc/1234/ns/net,1234
+    #[arg(long, value_delimiter = ',', value_name = "Network namespaces")]
+    pub namespaces: Vec<String>,
+
+    /// How often per-flow traffic is read from conntrack [disabled default]
+    #[arg(long, value_name = "Milliseconds")]
+    pub flow_interval_ms: Option<u64>,
+
+    /// Prefix length remote IPv4 addresses are aggregated to, for flows and TCP metrics [24 default]
+    #[arg(long, value_name = "Prefix length")]
+    pub flow_prefix_v4: Option<u8>,
+
+    /// Prefix length remote IPv6 addresses are aggregated to, for flows and TCP metrics [64 default]
+    #[arg(long, value_name = "Prefix length")]
+    pub flow_prefix_v6: Option<u8>,
+
+    /// How often TCP connection metrics are read from sock_diag [disabled default]
+    #[arg(long, value_name = "Milliseconds")]
+    pub tcp_interval_ms: Option<u64>,
+
+    /// How often qdisc and class statistics of the filtered interfaces are collected [disabled default]
+    #[arg(long, value_name = "Milliseconds")]
+    pub qdisc_interval_ms: Option<u64>,
+
+    /// How often WireGuard peer statistics are read [disabled default]
+    #[arg(long, value_name = "Milliseconds")]
+    pub wg_interval_ms: Option<u64>,
+
+    /// How often IP, ICMP, TCP and UDP counters are read from /proc/net/snmp and netstat [disabled default]
+    #[arg(long, value_name = "Milliseconds")]
+    pub proto_interval_ms: Option<u64>,
+
+    /// How often byte counters are sampled between stats ticks, for sub-second min/max/p99 rates [disabled default]
+    #[arg(long, value_name = "Milliseconds")]
+    pub burst_interval_ms: Option<u64>,
+
+    /// Where addresses and stats are kept: clickhouse, memory or otlp [clickhouse default]
+    #[arg(long, value_name = "Store")]
+    pub store: Option<String>,
+
+    /// OTLP/HTTP collector the otlp store pushes to, e.g. http://127.0.0.1:4318 [disabled default]
+    #[arg(long, value_name = "URL")]
+    pub otlp_endpoint: Option<String>,
+
+    /// Address the Prometheus /metrics endpoint listens on, e.g. 0.0.0.0:9469 [disabled default]
+    #[arg(long, value_name = "Address:Port")]
+    pub metrics_listen: Option<String>
 }
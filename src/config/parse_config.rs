@@ -4,6 +4,8 @@ use serde::{ Deserialize, Serialize };
 pub struct ServerConfig {
     pub clickhouse: Option<Clickhouse>,
     pub server: Option<Server>,
+    pub collector: Option<Collector>,
+    pub alerting: Option<Alerting>,
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
@@ -29,3 +31,45 @@ pub struct Clickhouse {
     db: Option<String>,
     port: Option<u32>
 }
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct Collector {
+    pub stats_interval_ms: Option<u64>,
+    pub refresh_interval_ms: Option<u64>,
+    pub resync_interval_ms: Option<u64>,
+    pub link_interval_ms: Option<u64>,
+    pub spool_path: Option<String>,
+    pub spool_max_mb: Option<u64>,
+    pub namespaces: Option<Vec<String>>,
+    pub flow_interval_ms: Option<u64>,
+    pub flow_prefix_v4: Option<u8>,
+    pub flow_prefix_v6: Option<u8>,
+    pub tcp_interval_ms: Option<u64>,
+    pub qdisc_interval_ms: Option<u64>,
+    pub wg_interval_ms: Option<u64>,
+    pub proto_interval_ms: Option<u64>,
+    pub burst_interval_ms: Option<u64>,
+    pub store: Option<String>,
+    pub otlp_endpoint: Option<String>,
+    pub metrics_listen: Option<String>
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct Alerting {
+    // URLs notifications are POSTed to as JSON
+    pub webhooks: Option<Vec<String>>,
+    // Run with sh -c, the notification is written to stdin
+    pub commands: Option<Vec<String>>,
+    pub rules: Option<Vec<AlertRule>>
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct AlertRule {
+    pub name: String,
+    // Regex like interface_filter, every filtered interface when missing
+    pub interface: Option<String>,
+    // "rx_e > 100/s", "down" or "no_address"
+    pub condition: String,
+    // How long the condition must hold before the alert fires
+    pub for_s: Option<u64>
+}
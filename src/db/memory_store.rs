@@ -0,0 +1,124 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Mutex, MutexGuard};
+use async_trait::async_trait;
+
+use crate::db::schema::{Addr, Server, Stat, StatReset, SystemStat};
+use super::store::{Store, StoreError};
+
+// Rows kept per table, the oldest are dropped first
+const MEMORY_STORE_ROWS: usize = 100_000;
+
+#[derive(Debug, Default)]
+struct MemoryTables {
+    servers: HashMap<String, Server>,
+    // By server, namespace and interface
+    addrs: HashMap<(String, String, String), Addr>,
+    stats: VecDeque<Stat>,
+    resets: VecDeque<StatReset>,
+    system_stats: VecDeque<SystemStat>,
+}
+
+// Keeps everything in memory, also used as a test double
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    tables: Mutex<MemoryTables>,
+}
+
+fn addr_key(addr: &Addr) -> (String, String, String) {
+    (addr.server_id.clone(), addr.netns.clone(), addr.interface.clone())
+}
+
+fn push_capped<T>(rows: &mut VecDeque<T>, new_rows: Vec<T>) {
+    rows.extend(new_rows);
+    while rows.len() > MEMORY_STORE_ROWS {
+        rows.pop_front();
+    }
+}
+
+impl MemoryStore {
+
+    fn tables(&self) -> MutexGuard<'_, MemoryTables> {
+        // A panic while holding the lock can't leave the tables half written
+        self.tables.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+// What was written, for the tests
+#[cfg(test)]
+impl MemoryStore {
+
+    pub fn servers(&self) -> Vec<Server> {
+        self.tables().servers.values().cloned().collect()
+    }
+
+    pub fn addresses(&self) -> Vec<Addr> {
+        self.tables().addrs.values().cloned().collect()
+    }
+
+    pub fn stats(&self) -> Vec<Stat> {
+        self.tables().stats.iter().cloned().collect()
+    }
+}
+
+#[async_trait]
+impl Store for MemoryStore {
+    async fn server_exists(&self, server: &Server) -> Result<bool, StoreError> {
+        Ok(self.tables().servers.contains_key(&server.server_id))
+    }
+
+    async fn add_server(&self, server: Server) -> Result<(), StoreError> {
+        self.tables().servers.insert(server.server_id.clone(), server);
+        Ok(())
+    }
+
+    async fn update_server(&self, server: Server) -> Result<(), StoreError> {
+        self.add_server(server).await
+    }
+
+    async fn get_addresses(&self, server: &Server, netns: &str) -> Result<Vec<Addr>, StoreError> {
+        Ok(self.tables().addrs.values()
+            .filter(|addr| addr.server_id == server.server_id && addr.netns == netns)
+            .cloned()
+            .collect())
+    }
+
+    async fn add_addresses(&self, addrs: Vec<Addr>) -> Result<(), StoreError> {
+        let mut tables = self.tables();
+        for addr in addrs {
+            tables.addrs.insert(addr_key(&addr), addr);
+        }
+        Ok(())
+    }
+
+    async fn update_addresses(&self, addrs: Vec<Addr>) -> Result<(), StoreError> {
+        self.add_addresses(addrs).await
+    }
+
+    async fn delete_addresses(&self, addrs: Vec<Addr>) -> Result<(), StoreError> {
+        let mut tables = self.tables();
+        for addr in &addrs {
+            tables.addrs.remove(&addr_key(addr));
+        }
+        Ok(())
+    }
+
+    async fn clear_addresses(&self, server_id: &str) -> Result<(), StoreError> {
+        self.tables().addrs.retain(|(id, _, _), _| id != server_id);
+        Ok(())
+    }
+
+    async fn add_stats(&self, stats: Vec<Stat>) -> Result<(), StoreError> {
+        push_capped(&mut self.tables().stats, stats);
+        Ok(())
+    }
+
+    async fn add_stat_resets(&self, resets: Vec<StatReset>) -> Result<(), StoreError> {
+        push_capped(&mut self.tables().resets, resets);
+        Ok(())
+    }
+
+    async fn add_system_stats(&self, stats: Vec<SystemStat>) -> Result<(), StoreError> {
+        push_capped(&mut self.tables().system_stats, stats);
+        Ok(())
+    }
+}
@@ -1,2 +1,5 @@
 pub mod queries;
 pub mod schema;
+pub mod spool;
+pub mod store;
+pub mod memory_store;
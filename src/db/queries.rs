@@ -2,7 +2,7 @@ use clickhouse::error::Error;
 use futures::future::join_all;
 use log::info;
 use clickhouse::Client;
-use crate::schema::{ Server, Addr, Stat };
+use crate::schema::{ Server, Addr, FlowStat, Interface, Link, LinkEvent, Neighbor, ProtoStat, QdiscStat, Route, Stat, StatReset, SystemStat, TcpStat, WgPeerStat };
 
 pub async fn server_exists(client: &Client, server: Server) -> Result<bool, Error> {
     let servers = client.query("SELECT * FROM server WHERE server_id = ?")
@@ -32,13 +32,13 @@ pub async fn update_server(client: &Client, server: Server) -> Result<(), Error>
         lng=?, interface_filter=?, city=?, country=?, priority=?, center=? WHERE server_id=?")
         .bind(&server.hostname)
         .bind(&server.label)
-        .bind(&server.lat)
-        .bind(&server.lng)
+        .bind(server.lat)
+        .bind(server.lng)
         .bind(&server.interface_filter)
         .bind(&server.city)
         .bind(&server.country)
-        .bind(&server.priority)
-        .bind(&server.center)
+        .bind(server.priority)
+        .bind(server.center)
         .bind(&server.server_id)
         .execute().await?;
 
@@ -46,10 +46,30 @@ pub async fn update_server(client: &Client, server: Server) -> Result<(), Error>
     Ok(())
 }
 
-pub async fn get_addr(client: &Client, server: &Server) -> Result<Vec<Addr>, Error> {
+pub async fn get_addr(client: &Client, server: &Server, netns: &str) -> Result<Vec<Addr>, Error> {
 
-    let addrs = client.query("SELECT * FROM addr WHERE server_id = ?")
+    let addrs = client.query("SELECT * FROM addr WHERE server_id = ? AND netns = ?")
         .bind(&server.server_id)
+        .bind(netns)
+        .fetch_all::<Addr>().await?;
+
+    Ok(addrs)
+}
+
+// Addresses of every other server, to recognize peers
+pub async fn get_peer_addr(client: &Client, server: &Server) -> Result<Vec<Addr>, Error> {
+
+    let addrs = client.query("SELECT * FROM addr WHERE server_id != ?")
+        .bind(&server.server_id)
+        .fetch_all::<Addr>().await?;
+
+    Ok(addrs)
+}
+
+// Addresses of every server, to discover the links between them
+pub async fn get_all_addr(client: &Client) -> Result<Vec<Addr>, Error> {
+
+    let addrs = client.query("SELECT * FROM addr")
         .fetch_all::<Addr>().await?;
 
     Ok(addrs)
@@ -77,14 +97,14 @@ pub async fn delete_addr(client: &Client, addrs: Vec<Addr>) -> Result<(), Error>
     let mut query = String::from("DELETE FROM addr WHERE ");
     let conditions: Vec<String> = addrs
         .iter()
-        .map(|_| "(server_id = ? AND interface = ?)".to_string())
+        .map(|_| "(server_id = ? AND netns = ? AND interface = ?)".to_string())
         .collect();
     query.push_str(&conditions.join(" OR "));
 
     // Prepare and bind parameters
     let mut prepared_query = client.query(&query);
     for addr in &addrs {
-        prepared_query = prepared_query.bind(&addr.server_id).bind(&addr.interface);
+        prepared_query = prepared_query.bind(&addr.server_id).bind(&addr.netns).bind(&addr.interface);
     }
 
     prepared_query.execute().await?;
@@ -102,12 +122,13 @@ pub async fn update_addr(client: &Client, addrs: Vec<Addr>) -> Result<(), Error>
     // Collect futures for concurrent execution
     let mut update_futures = Vec::new();
     for addr in &addrs {
-        let query = "ALTER TABLE addr UPDATE ipv6 = ?, ipv6_peer = ? WHERE server_id = ? AND interface = ?";
+        let query = "ALTER TABLE addr UPDATE ipv6 = ?, ipv6_peer = ? WHERE server_id = ? AND netns = ? AND interface = ?";
         let future = client
             .query(query)
             .bind(&addr.ipv6)
             .bind(&addr.ipv6_peer)
             .bind(&addr.server_id)
+            .bind(&addr.netns)
             .bind(&addr.interface)
             .execute();
         update_futures.push(future);
@@ -142,3 +163,266 @@ pub async fn add_stat(client: &Client, stats: Vec<Stat>) -> Result<(), Error> {
     insert_stat.end().await?;
     Ok(())
 }
+
+pub async fn add_stat_reset(client: &Client, resets: Vec<StatReset>) -> Result<(), Error> {
+
+    let mut insert_reset = client.insert("stat_reset")?;
+    for reset in resets {
+        insert_reset.write(&reset).await?;
+    }
+    insert_reset.end().await?;
+    Ok(())
+}
+
+pub async fn add_system_stats(client: &Client, stats: Vec<SystemStat>) -> Result<(), Error> {
+
+    let mut insert_system = client.insert("system_stat")?;
+    for stat in stats {
+        insert_system.write(&stat).await?;
+    }
+    insert_system.end().await?;
+    Ok(())
+}
+
+pub async fn add_proto_stats(client: &Client, stats: Vec<ProtoStat>) -> Result<(), Error> {
+
+    let mut insert_proto = client.insert("proto_stat")?;
+    for stat in stats {
+        insert_proto.write(&stat).await?;
+    }
+    insert_proto.end().await?;
+    Ok(())
+}
+
+pub async fn get_interfaces(client: &Client, server: &Server, netns: &str) -> Result<Vec<Interface>, Error> {
+
+    let interfaces = client.query("SELECT * FROM interface WHERE server_id = ? AND netns = ?")
+        .bind(&server.server_id)
+        .bind(netns)
+        .fetch_all::<Interface>().await?;
+
+    Ok(interfaces)
+}
+
+pub async fn add_interfaces(client: &Client, interfaces: Vec<Interface>) -> Result<(), Error> {
+    info!("Adding interface details to the database");
+
+    let mut insert_interface = client.insert("interface")?;
+    for interface in interfaces {
+        insert_interface.write(&interface).await?;
+    }
+    insert_interface.end().await?;
+    Ok(())
+}
+
+pub async fn delete_interfaces(client: &Client, interfaces: &[Interface]) -> Result<(), Error> {
+    info!("Deleting interface details from the database");
+
+    if interfaces.is_empty() {
+        return Ok(());
+    }
+
+    // Build the WHERE clause dynamically
+    let mut query = String::from("DELETE FROM interface WHERE ");
+    let conditions: Vec<String> = interfaces
+        .iter()
+        .map(|_| "(server_id = ? AND netns = ? AND interface = ?)".to_string())
+        .collect();
+    query.push_str(&conditions.join(" OR "));
+
+    // Prepare and bind parameters
+    let mut prepared_query = client.query(&query);
+    for interface in interfaces {
+        prepared_query = prepared_query.bind(&interface.server_id).bind(&interface.netns).bind(&interface.interface);
+    }
+
+    prepared_query.execute().await?;
+
+    Ok(())
+}
+
+pub async fn add_link_events(client: &Client, events: Vec<LinkEvent>) -> Result<(), Error> {
+
+    let mut insert_event = client.insert("link_event")?;
+    for event in events {
+        insert_event.write(&event).await?;
+    }
+    insert_event.end().await?;
+    Ok(())
+}
+
+pub async fn add_flow_stats(client: &Client, flows: Vec<FlowStat>) -> Result<(), Error> {
+
+    let mut insert_flow = client.insert("flow_stat")?;
+    for flow in flows {
+        insert_flow.write(&flow).await?;
+    }
+    insert_flow.end().await?;
+    Ok(())
+}
+
+pub async fn add_tcp_stats(client: &Client, stats: Vec<TcpStat>) -> Result<(), Error> {
+
+    let mut insert_tcp = client.insert("tcp_stat")?;
+    for stat in stats {
+        insert_tcp.write(&stat).await?;
+    }
+    insert_tcp.end().await?;
+    Ok(())
+}
+
+pub async fn add_qdisc_stats(client: &Client, stats: Vec<QdiscStat>) -> Result<(), Error> {
+
+    let mut insert_qdisc = client.insert("qdisc_stat")?;
+    for stat in stats {
+        insert_qdisc.write(&stat).await?;
+    }
+    insert_qdisc.end().await?;
+    Ok(())
+}
+
+pub async fn add_wg_peer_stats(client: &Client, stats: Vec<WgPeerStat>) -> Result<(), Error> {
+
+    let mut insert_wg = client.insert("wg_peer_stat")?;
+    for stat in stats {
+        insert_wg.write(&stat).await?;
+    }
+    insert_wg.end().await?;
+    Ok(())
+}
+
+pub async fn get_routes(client: &Client, server: &Server, netns: &str) -> Result<Vec<Route>, Error> {
+
+    let routes = client.query("SELECT * FROM route WHERE server_id = ? AND netns = ?")
+        .bind(&server.server_id)
+        .bind(netns)
+        .fetch_all::<Route>().await?;
+
+    Ok(routes)
+}
+
+pub async fn add_routes(client: &Client, routes: Vec<Route>) -> Result<(), Error> {
+
+    let mut insert_route = client.insert("route")?;
+    for route in routes {
+        insert_route.write(&route).await?;
+    }
+    insert_route.end().await?;
+    Ok(())
+}
+
+pub async fn delete_routes(client: &Client, routes: &[Route]) -> Result<(), Error> {
+
+    if routes.is_empty() {
+        return Ok(());
+    }
+
+    // Build the WHERE clause dynamically
+    let mut query = String::from("DELETE FROM route WHERE ");
+    let conditions: Vec<String> = routes
+        .iter()
+        .map(|_| "(server_id = ? AND netns = ? AND table_id = ? AND destination = ? AND prefix = ? AND metric = ? AND gateway = ? AND interface = ?)".to_string())
+        .collect();
+    query.push_str(&conditions.join(" OR "));
+
+    // Prepare and bind parameters
+    let mut prepared_query = client.query(&query);
+    for route in routes {
+        prepared_query = prepared_query.bind(&route.server_id).bind(&route.netns).bind(route.table_id)
+            .bind(route.destination).bind(route.prefix).bind(route.metric).bind(route.gateway).bind(&route.interface);
+    }
+
+    prepared_query.execute().await?;
+
+    Ok(())
+}
+
+pub async fn get_neighbors(client: &Client, server: &Server, netns: &str) -> Result<Vec<Neighbor>, Error> {
+
+    let neighbors = client.query("SELECT * FROM neighbor WHERE server_id = ? AND netns = ?")
+        .bind(&server.server_id)
+        .bind(netns)
+        .fetch_all::<Neighbor>().await?;
+
+    Ok(neighbors)
+}
+
+pub async fn add_neighbors(client: &Client, neighbors: Vec<Neighbor>) -> Result<(), Error> {
+
+    let mut insert_neighbor = client.insert("neighbor")?;
+    for neighbor in neighbors {
+        insert_neighbor.write(&neighbor).await?;
+    }
+    insert_neighbor.end().await?;
+    Ok(())
+}
+
+pub async fn delete_neighbors(client: &Client, neighbors: &[Neighbor]) -> Result<(), Error> {
+
+    if neighbors.is_empty() {
+        return Ok(());
+    }
+
+    // Build the WHERE clause dynamically
+    let mut query = String::from("DELETE FROM neighbor WHERE ");
+    let conditions: Vec<String> = neighbors
+        .iter()
+        .map(|_| "(server_id = ? AND netns = ? AND interface = ? AND ip = ?)".to_string())
+        .collect();
+    query.push_str(&conditions.join(" OR "));
+
+    // Prepare and bind parameters
+    let mut prepared_query = client.query(&query);
+    for neighbor in neighbors {
+        prepared_query = prepared_query.bind(&neighbor.server_id).bind(&neighbor.netns).bind(&neighbor.interface).bind(neighbor.ip);
+    }
+
+    prepared_query.execute().await?;
+
+    Ok(())
+}
+
+pub async fn get_links(client: &Client, server: &Server) -> Result<Vec<Link>, Error> {
+
+    let links = client.query("SELECT * FROM link WHERE server_a = ?")
+        .bind(&server.server_id)
+        .fetch_all::<Link>().await?;
+
+    Ok(links)
+}
+
+pub async fn add_links(client: &Client, links: Vec<Link>) -> Result<(), Error> {
+
+    let mut insert_link = client.insert("link")?;
+    for link in links {
+        insert_link.write(&link).await?;
+    }
+    insert_link.end().await?;
+    Ok(())
+}
+
+pub async fn delete_links(client: &Client, links: &[Link]) -> Result<(), Error> {
+
+    if links.is_empty() {
+        return Ok(());
+    }
+
+    // Build the WHERE clause dynamically
+    let mut query = String::from("DELETE FROM link WHERE ");
+    let conditions: Vec<String> = links
+        .iter()
+        .map(|_| "(server_a = ? AND netns_a = ? AND iface_a = ? AND server_b = ? AND netns_b = ? AND iface_b = ?)".to_string())
+        .collect();
+    query.push_str(&conditions.join(" OR "));
+
+    // Prepare and bind parameters
+    let mut prepared_query = client.query(&query);
+    for link in links {
+        prepared_query = prepared_query.bind(&link.server_a).bind(&link.netns_a).bind(&link.iface_a)
+            .bind(&link.server_b).bind(&link.netns_b).bind(&link.iface_b);
+    }
+
+    prepared_query.execute().await?;
+
+    Ok(())
+}
@@ -1,13 +1,29 @@
 use serde::{Deserialize, Serialize};
 use std::net::Ipv6Addr;
+use std::time::Instant;
 
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
 #[derive(clickhouse::Row)]
 pub struct Stat {
     pub server_id: String,
+    // Network namespace, empty for the daemon's own. Missing from batches spooled by older versions
+    #[serde(default)]
+    pub netns: String,
     pub interface: String,
     pub timestamp: u32,
+    // Unix time in milliseconds, distinguishes sub-second samples
+    pub timestamp_ms: u64,
+    // Used to detect recreated interfaces, not stored
+    #[serde(skip)]
+    pub ifindex: u32,
+    // Monotonic time of the sample, not stored
+    #[serde(skip)]
+    pub instant: Option<Instant>,
+    // Measured time since the previous sample
+    pub interval_ms: u64,
+    // Ticks skipped since the previous sample, 0 when on schedule
+    pub missed_ticks: u32,
     pub rx: u64,
     pub tx: u64,
     pub rx_p: u64,
@@ -15,7 +31,59 @@ pub struct Stat {
     pub rx_d: u64,
     pub tx_d: u64,
     pub rx_e: u64,
-    pub tx_e: u64
+    pub tx_e: u64,
+    pub multicast: u64,
+    pub collisions: u64,
+    pub rx_length_e: u64,
+    pub rx_over_e: u64,
+    pub rx_crc_e: u64,
+    pub rx_frame_e: u64,
+    pub rx_fifo_e: u64,
+    pub rx_missed_e: u64,
+    pub tx_aborted_e: u64,
+    pub tx_carrier_e: u64,
+    pub tx_fifo_e: u64,
+    pub tx_heartbeat_e: u64,
+    pub tx_window_e: u64,
+    pub rx_compressed: u64,
+    pub tx_compressed: u64,
+    pub rx_nohandler: u64,
+    // Per-second rates over interval_ms
+    pub rx_rate: f64,
+    pub tx_rate: f64,
+    pub rx_p_rate: f64,
+    pub tx_p_rate: f64,
+    pub rx_d_rate: f64,
+    pub tx_d_rate: f64,
+    pub rx_e_rate: f64,
+    pub tx_e_rate: f64,
+    // Sub-second rates in bytes/s when burst sampling is on, 0 otherwise.
+    // Missing from batches spooled by older versions
+    #[serde(default)]
+    pub rx_rate_min: f64,
+    #[serde(default)]
+    pub rx_rate_max: f64,
+    #[serde(default)]
+    pub rx_rate_p99: f64,
+    #[serde(default)]
+    pub tx_rate_min: f64,
+    #[serde(default)]
+    pub tx_rate_max: f64,
+    #[serde(default)]
+    pub tx_rate_p99: f64
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(clickhouse::Row)]
+pub struct StatReset {
+    pub server_id: String,
+    pub netns: String,
+    pub interface: String,
+    pub timestamp: u32,
+    // "counter" or "ifindex" for interface stats, "qdisc <handle> parent <parent>"
+    // or "class <handle> parent <parent>" for tc stats, "<protocol> <counter>" for
+    // protocol counters, which have no interface
+    pub reason: String
 }
 
 #[derive(Hash, Eq, PartialEq)]
@@ -23,11 +91,195 @@ pub struct Stat {
 #[derive(clickhouse::Row)]
 pub struct Addr {
     pub server_id: String,
+    pub netns: String,
     pub interface: String,
     pub ipv6: Vec<(Option<Ipv6Addr>, Option<u8>)>,
     pub ipv6_peer: Vec<(Option<Ipv6Addr>, Option<u8>)>
 }
 
+#[derive(PartialEq)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(clickhouse::Row)]
+pub struct Interface {
+    pub server_id: String,
+    pub netns: String,
+    pub interface: String,
+    pub ifindex: u32,
+    pub master_ifindex: Option<u32>,
+    // Link kind from IFLA_LINKINFO (veth, bond, wireguard...), None for physical NICs
+    pub kind: Option<String>,
+    // Lower device of a VLAN, VXLAN or macvlan (IFLA_LINK), when in the same namespace
+    pub parent_ifindex: Option<u32>,
+    // Bonds: balance-rr, active-backup, 802.3ad...
+    pub bond_mode: Option<String>,
+    // Slaves of a bond: active or backup
+    pub slave_state: Option<String>,
+    pub vlan_id: Option<u16>,
+    pub vxlan_vni: Option<u32>,
+    // Remote VTEP or multicast group of a VXLAN, IPv4-mapped like in addr
+    pub vxlan_remote: Option<Ipv6Addr>,
+    pub mac: Option<String>,
+    // IFLA_OPERSTATE as in /sys/class/net/<interface>/operstate
+    pub oper_state: String,
+    pub admin_up: bool,
+    pub carrier: bool,
+    pub carrier_changes: u32,
+    pub mtu: u32,
+    pub txqlen: u32,
+    // Mb/s, None when the driver doesn't report it
+    pub speed: Option<u32>,
+    pub duplex: Option<String>
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(clickhouse::Row)]
+pub struct LinkEvent {
+    pub server_id: String,
+    pub netns: String,
+    pub interface: String,
+    pub timestamp: u32,
+    // created, deleted, renamed, admin_state, oper_state, carrier, carrier_flap, master or slave_state
+    pub event: String,
+    pub old_value: String,
+    pub new_value: String
+}
+
+// Connection between interfaces of two servers, found from their addresses. Every server
+// writes the links seen from its side, server_a is the server that found the link.
+#[derive(PartialEq)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(clickhouse::Row)]
+pub struct Link {
+    pub server_a: String,
+    pub netns_a: String,
+    pub iface_a: String,
+    pub server_b: String,
+    pub netns_b: String,
+    pub iface_b: String,
+    // peer when the peer address of a point-to-point interface is on the other server,
+    // subnet when both interfaces have an address in the same subnet
+    pub kind: String
+}
+
+// One row per next hop, multipath routes have several rows
+#[derive(PartialEq)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(clickhouse::Row)]
+pub struct Route {
+    pub server_id: String,
+    pub netns: String,
+    pub table_id: u32,
+    // IPv4 routes are stored as IPv4-mapped addresses with their IPv4 prefix length
+    pub destination: Ipv6Addr,
+    pub prefix: u8,
+    pub metric: u32,
+    // Unspecified for directly connected routes
+    pub gateway: Ipv6Addr,
+    // Output interface, empty for blackhole, unreachable and prohibit routes
+    pub interface: String,
+    // Who installed the route: kernel, boot, static, dhcp, bgp...
+    pub protocol: String,
+    pub scope: String,
+    pub kind: String
+}
+
+// ARP and NDP entries of the filtered interfaces
+#[derive(PartialEq)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(clickhouse::Row)]
+pub struct Neighbor {
+    pub server_id: String,
+    pub netns: String,
+    pub interface: String,
+    // IPv4 neighbors are stored as IPv4-mapped addresses
+    pub ip: Ipv6Addr,
+    // None while the address is being resolved or when resolution failed
+    pub mac: Option<String>,
+    // Same names as ip neigh: REACHABLE, STALE, DELAY, PROBE, INCOMPLETE, FAILED or PERMANENT
+    pub state: String
+}
+
+// Counters of a qdisc or class over one collection interval, backlog and qlen are instantaneous
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[derive(clickhouse::Row)]
+pub struct QdiscStat {
+    pub server_id: String,
+    pub netns: String,
+    pub interface: String,
+    pub timestamp: u32,
+    pub timestamp_ms: u64,
+    // Monotonic time of the sample, not stored
+    #[serde(skip)]
+    pub instant: Option<Instant>,
+    pub interval_ms: u64,
+    // fq_codel, htb, tbf...
+    pub kind: String,
+    // In tc notation: "1:", "1:10", "root", "ingress"
+    pub handle: String,
+    pub parent: String,
+    // A class of a classful qdisc rather than a qdisc
+    pub is_class: bool,
+    pub bytes: u64,
+    pub packets: u64,
+    pub drops: u64,
+    pub overlimits: u64,
+    pub requeues: u64,
+    // Bytes and packets queued
+    pub backlog: u32,
+    pub qlen: u32
+}
+
+// Traffic to and from a remote network over one collection interval, from conntrack
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(clickhouse::Row)]
+pub struct FlowStat {
+    pub server_id: String,
+    pub netns: String,
+    pub timestamp: u32,
+    pub timestamp_ms: u64,
+    pub interval_ms: u64,
+    // Remote network, IPv4 is IPv4-mapped like in addr
+    pub remote: Ipv6Addr,
+    pub remote_prefix: u8,
+    // Set when the remote address belongs to another server
+    pub peer_server_id: Option<String>,
+    // Connections that carried traffic during the interval
+    pub flows: u32,
+    pub rx: u64,
+    pub tx: u64,
+    pub rx_p: u64,
+    pub tx_p: u64
+}
+
+// Health of the established TCP connections to a remote network, from sock_diag
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(clickhouse::Row)]
+pub struct TcpStat {
+    pub server_id: String,
+    pub netns: String,
+    pub timestamp: u32,
+    pub timestamp_ms: u64,
+    // Remote network, IPv4 is IPv4-mapped like in addr
+    pub remote: Ipv6Addr,
+    pub remote_prefix: u8,
+    // Set when the remote address belongs to another server
+    pub peer_server_id: Option<String>,
+    pub connections: u32,
+    // Smoothed RTT in microseconds
+    pub rtt_avg_us: f64,
+    pub rtt_min_us: u32,
+    pub rtt_max_us: u32,
+    pub rttvar_avg_us: f64,
+    // Congestion window in segments
+    pub cwnd_avg: f64,
+    pub cwnd_min: u32,
+    // Segments currently retransmitted and lost
+    pub retrans: u32,
+    pub lost: u32,
+    // Retransmitted segments over the lifetime of the connections
+    pub total_retrans: u64
+}
+
 #[derive(PartialEq)]
 #[derive(Debug, Clone, Deserialize, Serialize)]
 #[derive(clickhouse::Row)]
@@ -43,3 +295,84 @@ pub struct Server {
     pub priority: Option<u8>,
     pub center: Option<bool>
 }
+
+// Traffic of one WireGuard peer over one collection interval
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[derive(clickhouse::Row)]
+pub struct WgPeerStat {
+    pub server_id: String,
+    pub netns: String,
+    pub interface: String,
+    pub timestamp: u32,
+    pub timestamp_ms: u64,
+    // Monotonic time of the sample, not stored
+    #[serde(skip)]
+    pub instant: Option<Instant>,
+    pub interval_ms: u64,
+    // Base64, as shown by wg
+    pub public_key: String,
+    // IPv4-mapped like in addr, None until the peer is configured or has sent a handshake
+    pub endpoint: Option<Ipv6Addr>,
+    pub endpoint_port: u16,
+    pub allowed_ips: Vec<(Ipv6Addr, u8)>,
+    // Set when the endpoint belongs to another server
+    pub peer_server_id: Option<String>,
+    // Seconds since the latest handshake, None before the first one
+    pub handshake_age_s: Option<u32>,
+    pub rx: u64,
+    pub tx: u64
+}
+
+// Host CPU, load, memory and softnet counters over one stats interval. CPU times are in
+// percent of the interval, summed over every CPU and divided by their number.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[derive(clickhouse::Row)]
+pub struct SystemStat {
+    pub server_id: String,
+    pub timestamp: u32,
+    pub timestamp_ms: u64,
+    pub interval_ms: u64,
+    pub cpu_user: f32,
+    pub cpu_system: f32,
+    pub cpu_iowait: f32,
+    pub cpu_irq: f32,
+    pub cpu_softirq: f32,
+    pub cpu_steal: f32,
+    pub cpu_idle: f32,
+    // Busiest CPU, and the highest softirq share of a single CPU. One saturated
+    // RX queue doesn't show in the average.
+    pub cpu_busy_max: f32,
+    pub cpu_softirq_max: f32,
+    pub load1: f32,
+    pub load5: f32,
+    pub load15: f32,
+    // Bytes
+    pub mem_total: u64,
+    pub mem_available: u64,
+    pub mem_free: u64,
+    pub mem_buffers: u64,
+    pub mem_cached: u64,
+    pub swap_total: u64,
+    pub swap_free: u64,
+    // Packets over the interval, summed over every CPU and by CPU
+    pub softnet_processed: u64,
+    pub softnet_dropped: u64,
+    pub softnet_time_squeeze: u64,
+    pub softnet_dropped_per_cpu: Vec<u64>,
+    pub softnet_time_squeeze_per_cpu: Vec<u64>
+}
+
+// One row per protocol counter that moved during the interval
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[derive(clickhouse::Row)]
+pub struct ProtoStat {
+    pub server_id: String,
+    pub netns: String,
+    pub timestamp: u32,
+    pub timestamp_ms: u64,
+    pub interval_ms: u64,
+    // Ip, Icmp, Tcp, Udp, TcpExt, IpExt, Ip6, Icmp6, Udp6...
+    pub protocol: String,
+    pub counter: String,
+    pub value: u64
+}
@@ -0,0 +1,147 @@
+use std::collections::VecDeque;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use log::{info, warn};
+
+use crate::db::schema::Stat;
+use crate::db::store::{Store, StoreError};
+
+// Batches replayed per stats tick, so a long outage doesn't stall collection while it drains
+pub const REPLAY_BATCHES_PER_TICK: usize = 20;
+
+// On-disk queue of stat batches that failed to be inserted, one file per batch.
+// File names are increasing sequence numbers, so sorting them gives the insertion order.
+// The directory is only listed on startup, the queue and its size are then tracked in memory.
+pub struct Spool {
+    path: PathBuf,
+    max_bytes: u64,
+    next_seq: u64,
+    // Sequence number, file and size of every spooled batch, oldest first
+    pending: VecDeque<(u64, PathBuf, u64)>,
+    pending_bytes: u64
+}
+
+impl Spool {
+
+    pub fn new(path: &Path, max_bytes: u64) -> io::Result<Self> {
+        fs::create_dir_all(path)?;
+
+        let mut pending = VecDeque::new();
+        for (seq, file) in list_batches(path)? {
+            let size = fs::metadata(&file)?.len();
+            pending.push_back((seq, file, size));
+        }
+        // Continue after batches left over from a previous run
+        let next_seq = pending.back()
+            .and_then(|(seq, _, _)| seq.checked_add(1))
+            .unwrap_or(0);
+        let pending_bytes = pending.iter().map(|(_, _, size)| size).sum();
+
+        Ok(Spool { path: path.to_path_buf(), max_bytes, next_seq, pending, pending_bytes })
+    }
+
+    pub async fn push(&mut self, stats: &[Stat]) -> io::Result<()> {
+        let content = serde_json::to_vec(stats)?;
+        let size = content.len() as u64;
+        let file = self.path.join(format!("{:020}.json", self.next_seq));
+
+        let target = file.clone();
+        blocking(move || {
+            // Write to a temporary file first, a crash must not leave a truncated batch
+            let tmp_file = target.with_extension("tmp");
+            fs::write(&tmp_file, content)?;
+            fs::rename(&tmp_file, &target)
+        }).await?;
+        self.pending.push_back((self.next_seq, file, size));
+        self.pending_bytes += size;
+        self.next_seq += 1;
+
+        self.enforce_limit().await
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    pub fn read(file: &Path) -> io::Result<Vec<Stat>> {
+        let content = fs::read(file)?;
+        Ok(serde_json::from_slice(&content)?)
+    }
+
+    // Insert up to `max_batches` spooled batches in order, stop at the first failure
+    pub async fn replay(&mut self, store: &dyn Store, max_batches: usize) -> Result<usize, StoreError> {
+        let mut replayed = 0;
+
+        while replayed < max_batches {
+            let Some((_, file, _)) = self.pending.front().cloned() else {
+                break;
+            };
+
+            let source = file.clone();
+            match blocking(move || Spool::read(&source)).await {
+                Ok(stats) => store.add_stats(stats).await?,
+                Err(e) => warn!("Dropping unreadable spool file {}: {e}", file.display()),
+            }
+            self.remove_oldest().await?;
+            replayed += 1;
+        }
+
+        if replayed > 0 {
+            info!("Replayed {replayed} spooled stat batch(es), {} left", self.pending.len());
+        }
+        Ok(replayed)
+    }
+
+    async fn enforce_limit(&mut self) -> io::Result<()> {
+        // Drop the oldest batches until the spool fits
+        while self.pending_bytes > self.max_bytes {
+            if let Some((_, file, _)) = self.pending.front() {
+                warn!("Spool is over {} bytes, dropping {}", self.max_bytes, file.display());
+            }
+            self.remove_oldest().await?;
+        }
+        Ok(())
+    }
+
+    async fn remove_oldest(&mut self) -> io::Result<()> {
+        let Some((_, file, size)) = self.pending.pop_front() else {
+            return Ok(());
+        };
+        self.pending_bytes -= size;
+        blocking(move || match fs::remove_file(&file) {
+            // Already gone is as good as removed
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+            result => result,
+        }).await
+    }
+}
+
+// What is queued, for the tests
+#[cfg(test)]
+impl Spool {
+
+    // Spooled batches, oldest first
+    pub fn batches(&self) -> Vec<(u64, PathBuf)> {
+        self.pending.iter().map(|(seq, file, _)| (*seq, file.clone())).collect()
+    }
+}
+
+fn list_batches(path: &Path) -> io::Result<Vec<(u64, PathBuf)>> {
+    let mut batches = Vec::new();
+    for entry in fs::read_dir(path)? {
+        let file = entry?.path();
+        if file.extension().is_some_and(|ext| ext == "json") {
+            if let Some(seq) = file.file_stem().and_then(|stem| stem.to_str()).and_then(|stem| stem.parse().ok()) {
+                batches.push((seq, file));
+            }
+        }
+    }
+    batches.sort();
+    Ok(batches)
+}
+
+// File I/O stays off the async workers, a slow disk must not hold back the stats tick
+async fn blocking<T: Send + 'static>(task: impl FnOnce() -> io::Result<T> + Send + 'static) -> io::Result<T> {
+    tokio::task::spawn_blocking(task).await.map_err(io::Error::other)?
+}
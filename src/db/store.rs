@@ -0,0 +1,108 @@
+use async_trait::async_trait;
+use clickhouse::Client;
+use hyper::Uri;
+
+use crate::db::queries;
+use crate::db::schema::{Addr, Server, Stat, StatReset, SystemStat};
+
+pub type StoreError = Box<dyn std::error::Error + Send + Sync>;
+
+// Backend selected by the store option
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum StoreKind {
+    #[default]
+    ClickHouse,
+    // Addresses and stats are kept in the daemon, for trying out the collectors without writing them.
+    // The tables outside the store (details, routes, flows, ...) are still written to ClickHouse.
+    Memory,
+    // Stats pushed to an OpenTelemetry collector at this URL
+    Otlp(Uri),
+}
+
+impl StoreKind {
+    // The endpoint is parsed with the other options, it is only required by otlp
+    pub fn parse(kind: &str, otlp_endpoint: Option<Uri>) -> Result<Self, String> {
+        match kind {
+            "clickhouse" => Ok(StoreKind::ClickHouse),
+            "memory" => Ok(StoreKind::Memory),
+            "otlp" => Ok(StoreKind::Otlp(otlp_endpoint.ok_or("store otlp needs otlp_endpoint")?)),
+            _ => Err(format!("unknown store {kind}, expected clickhouse, memory or otlp")),
+        }
+    }
+}
+
+// Where the server, its addresses and the interface stats are kept
+#[async_trait]
+pub trait Store: Send + Sync {
+    async fn server_exists(&self, server: &Server) -> Result<bool, StoreError>;
+    async fn add_server(&self, server: Server) -> Result<(), StoreError>;
+    async fn update_server(&self, server: Server) -> Result<(), StoreError>;
+
+    async fn get_addresses(&self, server: &Server, netns: &str) -> Result<Vec<Addr>, StoreError>;
+    async fn add_addresses(&self, addrs: Vec<Addr>) -> Result<(), StoreError>;
+    async fn update_addresses(&self, addrs: Vec<Addr>) -> Result<(), StoreError>;
+    async fn delete_addresses(&self, addrs: Vec<Addr>) -> Result<(), StoreError>;
+    // Addresses of the server in every namespace
+    async fn clear_addresses(&self, server_id: &str) -> Result<(), StoreError>;
+
+    async fn add_stats(&self, stats: Vec<Stat>) -> Result<(), StoreError>;
+    async fn add_stat_resets(&self, resets: Vec<StatReset>) -> Result<(), StoreError>;
+    async fn add_system_stats(&self, stats: Vec<SystemStat>) -> Result<(), StoreError>;
+}
+
+pub struct ClickHouseStore {
+    client: Client,
+}
+
+impl ClickHouseStore {
+    pub fn new(client: Client) -> Self {
+        ClickHouseStore { client }
+    }
+}
+
+#[async_trait]
+impl Store for ClickHouseStore {
+    async fn server_exists(&self, server: &Server) -> Result<bool, StoreError> {
+        Ok(queries::server_exists(&self.client, server.clone()).await?)
+    }
+
+    async fn add_server(&self, server: Server) -> Result<(), StoreError> {
+        Ok(queries::add_server(&self.client, server).await?)
+    }
+
+    async fn update_server(&self, server: Server) -> Result<(), StoreError> {
+        Ok(queries::update_server(&self.client, server).await?)
+    }
+
+    async fn get_addresses(&self, server: &Server, netns: &str) -> Result<Vec<Addr>, StoreError> {
+        Ok(queries::get_addr(&self.client, server, netns).await?)
+    }
+
+    async fn add_addresses(&self, addrs: Vec<Addr>) -> Result<(), StoreError> {
+        Ok(queries::add_addr(&self.client, addrs).await?)
+    }
+
+    async fn update_addresses(&self, addrs: Vec<Addr>) -> Result<(), StoreError> {
+        Ok(queries::update_addr(&self.client, addrs).await?)
+    }
+
+    async fn delete_addresses(&self, addrs: Vec<Addr>) -> Result<(), StoreError> {
+        Ok(queries::delete_addr(&self.client, addrs).await?)
+    }
+
+    async fn clear_addresses(&self, server_id: &str) -> Result<(), StoreError> {
+        Ok(queries::delete_data_efficiently(&self.client, &server_id.to_string()).await?)
+    }
+
+    async fn add_stats(&self, stats: Vec<Stat>) -> Result<(), StoreError> {
+        Ok(queries::add_stat(&self.client, stats).await?)
+    }
+
+    async fn add_stat_resets(&self, resets: Vec<StatReset>) -> Result<(), StoreError> {
+        Ok(queries::add_stat_reset(&self.client, resets).await?)
+    }
+
+    async fn add_system_stats(&self, stats: Vec<SystemStat>) -> Result<(), StoreError> {
+        Ok(queries::add_system_stats(&self.client, stats).await?)
+    }
+}
@@ -0,0 +1,33 @@
+use std::sync::{Arc, RwLock};
+
+use crate::db::schema::Stat;
+
+pub mod otlp;
+pub mod prometheus;
+
+// Latest cumulative counters of the collected interfaces, kept for the exporters.
+// Does nothing when no exporter is configured.
+#[derive(Debug, Clone, Default)]
+pub struct LatestStats(Option<Arc<RwLock<Vec<Stat>>>>);
+
+impl LatestStats {
+
+    pub fn enabled() -> Self {
+        LatestStats(Some(Arc::new(RwLock::new(Vec::new()))))
+    }
+
+    // Replaces the previous tick, interfaces that are gone disappear with it
+    pub fn update(&self, stats: &[Stat]) {
+        if let Some(latest) = &self.0 {
+            let mut stats = stats.to_vec();
+            stats.sort_by(|a, b| (&a.netns, &a.interface).cmp(&(&b.netns, &b.interface)));
+            *latest.write().unwrap_or_else(|poisoned| poisoned.into_inner()) = stats;
+        }
+    }
+
+    pub fn snapshot(&self) -> Vec<Stat> {
+        self.0.as_ref()
+            .map(|latest| latest.read().unwrap_or_else(|poisoned| poisoned.into_inner()).clone())
+            .unwrap_or_default()
+    }
+}
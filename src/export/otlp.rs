@@ -0,0 +1,320 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use async_trait::async_trait;
+use bytes::Bytes;
+use http_body_util::Full;
+use hyper::{Method, Request, Uri};
+use hyper_util::client::legacy::{connect::HttpConnector, Client};
+use hyper_util::rt::TokioExecutor;
+use serde_json::{json, Value};
+use tokio::time::timeout;
+
+use crate::db::schema::{Addr, Server, Stat, StatReset, SystemStat};
+use crate::db::store::{Store, StoreError};
+
+// Path of the metrics signal when the endpoint is given without one
+const METRICS_PATH: &str = "/v1/metrics";
+const EXPORT_TIMEOUT: Duration = Duration::from_secs(10);
+// opentelemetry.proto.metrics.v1.AggregationTemporality
+const TEMPORALITY_DELTA: u8 = 1;
+
+// Plain HTTP like the webhooks, a local collector can forward over TLS
+pub fn parse_endpoint(endpoint: &str) -> Result<Uri, String> {
+    let uri: Uri = endpoint.parse().map_err(|e| format!("invalid otlp_endpoint {endpoint}: {e}"))?;
+    if uri.scheme_str() != Some("http") || uri.host().is_none() {
+        return Err(format!("otlp_endpoint {endpoint} must be http://host[:port][/path]"));
+    }
+    if uri.path() != "/" {
+        return Ok(uri);
+    }
+    let authority = uri.authority().map(|authority| authority.as_str()).unwrap_or_default();
+    format!("http://{authority}{METRICS_PATH}").parse()
+        .map_err(|e| format!("invalid otlp_endpoint {endpoint}: {e}"))
+}
+
+// Pushes the stats as OTLP/HTTP JSON metrics. The server and the addresses are kept to
+// label and complete every export, nothing is read back from the collector.
+pub struct OtlpStore {
+    client: Client<HttpConnector, Full<Bytes>>,
+    endpoint: Uri,
+    server: Mutex<Option<Server>>,
+    // By namespace and interface
+    addrs: Mutex<HashMap<(String, String), Addr>>,
+}
+
+fn locked<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+impl OtlpStore {
+
+    pub fn new(endpoint: Uri) -> Self {
+        OtlpStore {
+            client: Client::builder(TokioExecutor::new()).build_http(),
+            endpoint,
+            server: Mutex::new(None),
+            addrs: Mutex::new(HashMap::new()),
+        }
+    }
+
+    async fn export(&self, metrics: Vec<Value>) -> Result<(), StoreError> {
+        let request = export_request(locked(&self.server).as_ref(), metrics);
+        let body = serde_json::to_vec(&request)?;
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri(&self.endpoint)
+            .header("content-type", "application/json")
+            .body(Full::new(Bytes::from(body)))?;
+
+        let response = timeout(EXPORT_TIMEOUT, self.client.request(request)).await
+            .map_err(|_| format!("OTLP export to {} timed out", self.endpoint))??;
+        if !response.status().is_success() {
+            return Err(format!("OTLP export to {} failed: HTTP {}", self.endpoint, response.status()).into());
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Store for OtlpStore {
+    // Always registered again, the server only labels the exports
+    async fn server_exists(&self, _server: &Server) -> Result<bool, StoreError> {
+        Ok(false)
+    }
+
+    async fn add_server(&self, server: Server) -> Result<(), StoreError> {
+        *locked(&self.server) = Some(server);
+        Ok(())
+    }
+
+    async fn update_server(&self, server: Server) -> Result<(), StoreError> {
+        self.add_server(server).await
+    }
+
+    async fn get_addresses(&self, server: &Server, netns: &str) -> Result<Vec<Addr>, StoreError> {
+        Ok(locked(&self.addrs).values()
+            .filter(|addr| addr.server_id == server.server_id && addr.netns == netns)
+            .cloned()
+            .collect())
+    }
+
+    async fn add_addresses(&self, addrs: Vec<Addr>) -> Result<(), StoreError> {
+        let mut known = locked(&self.addrs);
+        for addr in addrs {
+            known.insert((addr.netns.clone(), addr.interface.clone()), addr);
+        }
+        Ok(())
+    }
+
+    async fn update_addresses(&self, addrs: Vec<Addr>) -> Result<(), StoreError> {
+        self.add_addresses(addrs).await
+    }
+
+    async fn delete_addresses(&self, addrs: Vec<Addr>) -> Result<(), StoreError> {
+        let mut known = locked(&self.addrs);
+        for addr in addrs {
+            known.remove(&(addr.netns, addr.interface));
+        }
+        Ok(())
+    }
+
+    async fn clear_addresses(&self, server_id: &str) -> Result<(), StoreError> {
+        locked(&self.addrs).retain(|_, addr| addr.server_id != server_id);
+        Ok(())
+    }
+
+    // The addresses go along with every batch of stats, gauges have to be reported again
+    async fn add_stats(&self, stats: Vec<Stat>) -> Result<(), StoreError> {
+        let time_ms = stats.iter().map(|stat| stat.timestamp_ms).max()
+            .unwrap_or_else(|| SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64);
+        let mut addrs: Vec<Addr> = locked(&self.addrs).values().cloned().collect();
+        addrs.sort_by(|a, b| (&a.netns, &a.interface).cmp(&(&b.netns, &b.interface)));
+
+        let mut metrics = stat_metrics(&stats);
+        metrics.extend(address_metric(&addrs, time_ms));
+        if metrics.is_empty() {
+            return Ok(());
+        }
+        self.export(metrics).await
+    }
+
+    // A reset only drops a delta, the next one starts after it
+    async fn add_stat_resets(&self, _resets: Vec<StatReset>) -> Result<(), StoreError> {
+        Ok(())
+    }
+
+    async fn add_system_stats(&self, stats: Vec<SystemStat>) -> Result<(), StoreError> {
+        if stats.is_empty() {
+            return Ok(());
+        }
+        self.export(system_metrics(&stats)).await
+    }
+}
+
+// ExportMetricsServiceRequest in the protobuf JSON mapping, 64 bit integers are strings
+pub fn export_request(server: Option<&Server>, metrics: Vec<Value>) -> Value {
+    json!({
+        "resourceMetrics": [{
+            "resource": { "attributes": resource_attributes(server) },
+            "scopeMetrics": [{
+                "scope": { "name": env!("CARGO_PKG_NAME"), "version": env!("CARGO_PKG_VERSION") },
+                "metrics": metrics
+            }]
+        }]
+    })
+}
+
+pub fn resource_attributes(server: Option<&Server>) -> Vec<Value> {
+    let mut attributes = vec![string_attribute("service.name", env!("CARGO_PKG_NAME"))];
+    let Some(server) = server else {
+        return attributes;
+    };
+
+    attributes.extend([
+        string_attribute("host.id", &server.server_id),
+        string_attribute("host.name", &server.hostname),
+        string_attribute("network_map.server_id", &server.server_id),
+        string_attribute("network_map.label", &server.label),
+        double_attribute("network_map.lat", server.lat as f64),
+        double_attribute("network_map.lng", server.lng as f64),
+    ]);
+    if let Some(city) = &server.city {
+        attributes.push(string_attribute("network_map.city", city));
+    }
+    if let Some(country) = &server.country {
+        attributes.push(string_attribute("network_map.country", country));
+    }
+    attributes
+}
+
+fn string_attribute(key: &str, value: &str) -> Value {
+    json!({ "key": key, "value": { "stringValue": value } })
+}
+
+fn int_attribute(key: &str, value: i64) -> Value {
+    json!({ "key": key, "value": { "intValue": value.to_string() } })
+}
+
+fn double_attribute(key: &str, value: f64) -> Value {
+    json!({ "key": key, "value": { "doubleValue": value } })
+}
+
+fn nanos(time_ms: u64) -> String {
+    (time_ms as u128 * 1_000_000).to_string()
+}
+
+// Name, unit, description and the receive and transmit values
+type Counter = (&'static str, &'static str, &'static str, fn(&Stat) -> (u64, u64));
+
+// Named after the system.network.* semantic conventions
+const COUNTERS: [Counter; 4] = [
+    ("system.network.io", "By", "Bytes received and transmitted", |stat| (stat.rx, stat.tx)),
+    ("system.network.packets", "{packet}", "Packets received and transmitted", |stat| (stat.rx_p, stat.tx_p)),
+    ("system.network.dropped", "{packet}", "Packets dropped", |stat| (stat.rx_d, stat.tx_d)),
+    ("system.network.errors", "{error}", "Receive and transmit errors", |stat| (stat.rx_e, stat.tx_e)),
+];
+
+// Interface counters as delta sums
+pub fn stat_metrics(stats: &[Stat]) -> Vec<Value> {
+    if stats.is_empty() {
+        return Vec::new();
+    }
+
+    COUNTERS.iter().map(|(name, unit, description, values)| {
+        let data_points: Vec<Value> = stats.iter().flat_map(|stat| {
+            let (rx, tx) = values(stat);
+            [("receive", rx), ("transmit", tx)].map(|(direction, value)| json!({
+                "attributes": [
+                    string_attribute("network.interface.name", &stat.interface),
+                    string_attribute("network.io.direction", direction),
+                    string_attribute("network_map.netns", &stat.netns),
+                ],
+                "startTimeUnixNano": nanos(stat.timestamp_ms.saturating_sub(stat.interval_ms)),
+                "timeUnixNano": nanos(stat.timestamp_ms),
+                "asInt": value.to_string()
+            }))
+        }).collect();
+
+        json!({
+            "name": name,
+            "unit": unit,
+            "description": description,
+            "sum": { "aggregationTemporality": TEMPORALITY_DELTA, "isMonotonic": true, "dataPoints": data_points }
+        })
+    }).collect()
+}
+
+// One point of value 1 per address, the address and its prefix as attributes
+pub fn address_metric(addrs: &[Addr], time_ms: u64) -> Option<Value> {
+    let data_points: Vec<Value> = addrs.iter().flat_map(|addr| {
+        let local = addr.ipv6.iter().map(|address| ("local", address));
+        let peer = addr.ipv6_peer.iter().map(|address| ("peer", address));
+        local.chain(peer).filter_map(move |(scope, (address, prefix))| {
+            let address = address.as_ref()?;
+            // IPv4 is stored mapped into IPv6
+            let address = address.to_ipv4_mapped().map(IpAddr::V4).unwrap_or(IpAddr::V6(*address));
+            Some(json!({
+                "attributes": [
+                    string_attribute("network.interface.name", &addr.interface),
+                    string_attribute("network_map.netns", &addr.netns),
+                    string_attribute("network_map.address", &address.to_string()),
+                    int_attribute("network_map.prefix", prefix.unwrap_or_default() as i64),
+                    string_attribute("network_map.scope", scope),
+                ],
+                "timeUnixNano": nanos(time_ms),
+                "asInt": "1"
+            }))
+        })
+    }).collect();
+
+    (!data_points.is_empty()).then(|| json!({
+        "name": "network_map.interface.address",
+        "unit": "1",
+        "description": "Addresses of the collected interfaces",
+        "gauge": { "dataPoints": data_points }
+    }))
+}
+
+// Host CPU, load and memory as gauges, named after the system.* semantic conventions
+pub fn system_metrics(stats: &[SystemStat]) -> Vec<Value> {
+    let gauge = |name: &str, unit: &str, points: Vec<Value>| json!({
+        "name": name,
+        "unit": unit,
+        "gauge": { "dataPoints": points }
+    });
+    let point = |stat: &SystemStat, attributes: Vec<Value>, value: Value| {
+        let mut point = json!({ "attributes": attributes, "timeUnixNano": nanos(stat.timestamp_ms) });
+        let kind = if value.is_string() { "asInt" } else { "asDouble" };
+        point[kind] = value;
+        point
+    };
+
+    let mut cpu = Vec::new();
+    let mut load = Vec::new();
+    let mut memory = Vec::new();
+    let mut memory_limit = Vec::new();
+    for stat in stats {
+        // Percentages in the table, a ratio in the convention
+        for (mode, value) in [("user", stat.cpu_user), ("system", stat.cpu_system), ("iowait", stat.cpu_iowait),
+            ("interrupt", stat.cpu_irq), ("softirq", stat.cpu_softirq), ("steal", stat.cpu_steal), ("idle", stat.cpu_idle)] {
+            cpu.push(point(stat, vec![string_attribute("cpu.mode", mode)], json!(value as f64 / 100.0)));
+        }
+        for (window, value) in [("1m", stat.load1), ("5m", stat.load5), ("15m", stat.load15)] {
+            load.push(point(stat, vec![string_attribute("network_map.load_window", window)], json!(value as f64)));
+        }
+        let used = stat.mem_total.saturating_sub(stat.mem_available);
+        for (state, value) in [("used", used), ("free", stat.mem_free), ("buffers", stat.mem_buffers), ("cached", stat.mem_cached)] {
+            memory.push(point(stat, vec![string_attribute("system.memory.state", state)], json!(value.to_string())));
+        }
+        memory_limit.push(point(stat, Vec::new(), json!(stat.mem_total.to_string())));
+    }
+
+    vec![
+        gauge("system.cpu.utilization", "1", cpu),
+        gauge("system.cpu.load_average", "{thread}", load),
+        gauge("system.memory.usage", "By", memory),
+        gauge("system.memory.limit", "By", memory_limit),
+    ]
+}
@@ -0,0 +1,122 @@
+use std::convert::Infallible;
+use std::fmt::Write;
+use std::net::SocketAddr;
+use bytes::Bytes;
+use http_body_util::Full;
+use hyper::body::Incoming;
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper::{Method, Request, Response, StatusCode};
+use hyper_util::rt::TokioIo;
+use log::{error, info, warn};
+use tokio::net::TcpListener;
+
+use crate::db::schema::{Server, Stat};
+use super::LatestStats;
+
+const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+// Cumulative interface counter: name, help and field of the stat
+type Counter = (&'static str, &'static str, fn(&Stat) -> u64);
+
+const COUNTERS: [Counter; 24] = [
+    ("receive_bytes_total", "Bytes received", |stat| stat.rx),
+    ("transmit_bytes_total", "Bytes transmitted", |stat| stat.tx),
+    ("receive_packets_total", "Packets received", |stat| stat.rx_p),
+    ("transmit_packets_total", "Packets transmitted", |stat| stat.tx_p),
+    ("receive_dropped_total", "Received packets dropped", |stat| stat.rx_d),
+    ("transmit_dropped_total", "Packets dropped before transmission", |stat| stat.tx_d),
+    ("receive_errors_total", "Receive errors", |stat| stat.rx_e),
+    ("transmit_errors_total", "Transmit errors", |stat| stat.tx_e),
+    ("multicast_total", "Multicast packets received", |stat| stat.multicast),
+    ("collisions_total", "Collisions", |stat| stat.collisions),
+    ("receive_length_errors_total", "Received frames with a wrong length", |stat| stat.rx_length_e),
+    ("receive_over_errors_total", "Receive ring buffer overflows", |stat| stat.rx_over_e),
+    ("receive_crc_errors_total", "Received frames with a bad CRC", |stat| stat.rx_crc_e),
+    ("receive_frame_errors_total", "Received frames misaligned", |stat| stat.rx_frame_e),
+    ("receive_fifo_errors_total", "Receive FIFO overruns", |stat| stat.rx_fifo_e),
+    ("receive_missed_errors_total", "Packets missed by the NIC", |stat| stat.rx_missed_e),
+    ("transmit_aborted_errors_total", "Transmissions aborted", |stat| stat.tx_aborted_e),
+    ("transmit_carrier_errors_total", "Transmit carrier errors", |stat| stat.tx_carrier_e),
+    ("transmit_fifo_errors_total", "Transmit FIFO underruns", |stat| stat.tx_fifo_e),
+    ("transmit_heartbeat_errors_total", "Transmit heartbeat errors", |stat| stat.tx_heartbeat_e),
+    ("transmit_window_errors_total", "Late collisions", |stat| stat.tx_window_e),
+    ("receive_compressed_total", "Compressed packets received", |stat| stat.rx_compressed),
+    ("transmit_compressed_total", "Compressed packets transmitted", |stat| stat.tx_compressed),
+    ("receive_nohandler_total", "Packets dropped for lack of a protocol handler", |stat| stat.rx_nohandler),
+];
+
+pub async fn serve_metrics(listen: SocketAddr, server: Server, latest: LatestStats) {
+    let listener = match TcpListener::bind(listen).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            error!("Failed to listen on {listen}: {e}, Prometheus metrics are not served");
+            return;
+        }
+    };
+
+    info!("Serving Prometheus metrics on http://{listen}/metrics");
+
+    loop {
+        let stream = match listener.accept().await {
+            Ok((stream, _)) => stream,
+            Err(e) => {
+                warn!("Failed to accept metrics connection: {e}");
+                continue;
+            }
+        };
+
+        let (server, latest) = (server.clone(), latest.clone());
+        tokio::spawn(async move {
+            let service = service_fn(move |request| {
+                let response = respond(&request, &server, &latest);
+                async move { Ok::<_, Infallible>(response) }
+            });
+            // A scraper disconnecting mid-response is not worth logging
+            http1::Builder::new().serve_connection(TokioIo::new(stream), service).await.ok();
+        });
+    }
+}
+
+fn respond(request: &Request<Incoming>, server: &Server, latest: &LatestStats) -> Response<Full<Bytes>> {
+    let response = Response::builder();
+    let response = match (request.method(), request.uri().path()) {
+        (&Method::GET, "/metrics") => response
+            .header("content-type", CONTENT_TYPE)
+            .body(Full::new(Bytes::from(render_metrics(server, &latest.snapshot())))),
+        _ => response
+            .status(StatusCode::NOT_FOUND)
+            .body(Full::new(Bytes::from_static(b"Not found, metrics are served on /metrics\n"))),
+    };
+    response.unwrap_or_default()
+}
+
+// Text exposition format, one family per counter with a sample per interface
+pub fn render_metrics(server: &Server, stats: &[Stat]) -> String {
+    let mut output = String::new();
+
+    let optional = |value: Option<String>| value.unwrap_or_default();
+    writeln!(output, "# HELP network_map_server_info Server metadata, always 1").ok();
+    writeln!(output, "# TYPE network_map_server_info gauge").ok();
+    writeln!(output, "network_map_server_info{{server_id=\"{}\",hostname=\"{}\",label=\"{}\",city=\"{}\",country=\"{}\",lat=\"{}\",lng=\"{}\",priority=\"{}\",center=\"{}\"}} 1",
+        escape(&server.server_id), escape(&server.hostname), escape(&server.label),
+        escape(&optional(server.city.clone())), escape(&optional(server.country.clone())),
+        server.lat, server.lng,
+        optional(server.priority.map(|priority| priority.to_string())),
+        optional(server.center.map(|center| center.to_string()))).ok();
+
+    for (name, help, value) in COUNTERS {
+        writeln!(output, "# HELP network_map_{name} {help}").ok();
+        writeln!(output, "# TYPE network_map_{name} counter").ok();
+        for stat in stats {
+            writeln!(output, "network_map_{name}{{server_id=\"{}\",label=\"{}\",netns=\"{}\",interface=\"{}\"}} {}",
+                escape(&server.server_id), escape(&server.label), escape(&stat.netns), escape(&stat.interface), value(stat)).ok();
+        }
+    }
+    output
+}
+
+// Label values escape backslashes, quotes and newlines
+pub fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
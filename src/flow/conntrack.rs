@@ -0,0 +1,153 @@
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::path::Path;
+use netlink_packet_utils::nla::NlasIterator;
+use netlink_sys::{protocols::NETLINK_NETFILTER, Socket, SocketAddr};
+
+use crate::interface::netlink::{dump, dump_request};
+use crate::interface::netns::in_namespace;
+
+// linux/netfilter/nfnetlink.h, nfnetlink_conntrack.h
+const NFNL_SUBSYS_CTNETLINK: u16 = 1;
+const IPCTNL_MSG_CT_NEW: u16 = 0;
+const IPCTNL_MSG_CT_GET: u16 = 1;
+const NFGENMSG_LEN: usize = 4;
+
+const CTA_TUPLE_ORIG: u16 = 1;
+const CTA_COUNTERS_ORIG: u16 = 9;
+const CTA_COUNTERS_REPLY: u16 = 10;
+const CTA_ID: u16 = 12;
+
+const CTA_TUPLE_IP: u16 = 1;
+const CTA_TUPLE_PROTO: u16 = 2;
+const CTA_IP_V4_SRC: u16 = 1;
+const CTA_IP_V4_DST: u16 = 2;
+const CTA_IP_V6_SRC: u16 = 3;
+const CTA_IP_V6_DST: u16 = 4;
+const CTA_PROTO_NUM: u16 = 1;
+
+const CTA_COUNTERS_PACKETS: u16 = 1;
+const CTA_COUNTERS_BYTES: u16 = 2;
+
+// A conntrack entry, counters are only present with net.netfilter.nf_conntrack_acct=1
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Flow {
+    pub id: u32,
+    pub protocol: u8,
+    pub src: Option<IpAddr>,
+    pub dst: Option<IpAddr>,
+    // Sent by the side that opened the connection
+    pub orig_bytes: u64,
+    pub orig_packets: u64,
+    // Sent back by the other side
+    pub reply_bytes: u64,
+    pub reply_packets: u64,
+    pub has_counters: bool,
+}
+
+pub fn open_socket(namespace: Option<&Path>) -> io::Result<Socket> {
+    in_namespace(namespace, || {
+        let mut socket = Socket::new(NETLINK_NETFILTER)?;
+        socket.bind_auto()?;
+        socket.connect(&SocketAddr::new(0, 0))?;
+        Ok(socket)
+    })
+}
+
+// Dumps the whole conntrack table, IPv4 and IPv6
+pub fn dump_flows(socket: &Socket) -> io::Result<Vec<Flow>> {
+    // nfgenmsg: AF_UNSPEC dumps every family, version 0, resource id 0
+    let request = dump_request((NFNL_SUBSYS_CTNETLINK << 8) | IPCTNL_MSG_CT_GET, &[0, 0, 0, 0]);
+
+    let mut flows = Vec::new();
+    dump(socket, &request, |kind, payload| flows.extend(parse_message(kind, payload)))?;
+    Ok(flows)
+}
+
+pub fn parse_message(kind: u16, payload: &[u8]) -> Option<Flow> {
+    if kind != (NFNL_SUBSYS_CTNETLINK << 8) | IPCTNL_MSG_CT_NEW {
+        return None;
+    }
+    parse_flow(payload.get(NFGENMSG_LEN..)?)
+}
+
+pub fn parse_flow(attributes: &[u8]) -> Option<Flow> {
+    let mut flow = Flow::default();
+
+    for nla in NlasIterator::new(attributes) {
+        let nla = nla.ok()?;
+        match nla.kind() {
+            CTA_ID => flow.id = u32::from_be_bytes(nla.value().get(..4)?.try_into().ok()?),
+            CTA_TUPLE_ORIG => parse_tuple(nla.value(), &mut flow)?,
+            CTA_COUNTERS_ORIG => {
+                (flow.orig_packets, flow.orig_bytes) = parse_counters(nla.value())?;
+                flow.has_counters = true;
+            }
+            CTA_COUNTERS_REPLY => {
+                (flow.reply_packets, flow.reply_bytes) = parse_counters(nla.value())?;
+                flow.has_counters = true;
+            }
+            _ => ()
+        }
+    }
+    Some(flow)
+}
+
+fn parse_tuple(tuple: &[u8], flow: &mut Flow) -> Option<()> {
+    for nla in NlasIterator::new(tuple) {
+        let nla = nla.ok()?;
+        match nla.kind() {
+            CTA_TUPLE_IP => {
+                for ip in NlasIterator::new(nla.value()) {
+                    let ip = ip.ok()?;
+                    match ip.kind() {
+                        CTA_IP_V4_SRC => flow.src = Some(parse_ipv4(ip.value())?),
+                        CTA_IP_V4_DST => flow.dst = Some(parse_ipv4(ip.value())?),
+                        CTA_IP_V6_SRC => flow.src = Some(parse_ipv6(ip.value())?),
+                        CTA_IP_V6_DST => flow.dst = Some(parse_ipv6(ip.value())?),
+                        _ => ()
+                    }
+                }
+            }
+            CTA_TUPLE_PROTO => {
+                for proto in NlasIterator::new(nla.value()) {
+                    let proto = proto.ok()?;
+                    if proto.kind() == CTA_PROTO_NUM {
+                        flow.protocol = *proto.value().first()?;
+                    }
+                }
+            }
+            _ => ()
+        }
+    }
+    Some(())
+}
+
+// (packets, bytes)
+fn parse_counters(counters: &[u8]) -> Option<(u64, u64)> {
+    let mut packets = 0;
+    let mut bytes = 0;
+    for nla in NlasIterator::new(counters) {
+        let nla = nla.ok()?;
+        match nla.kind() {
+            CTA_COUNTERS_PACKETS => packets = parse_u64_be(nla.value())?,
+            CTA_COUNTERS_BYTES => bytes = parse_u64_be(nla.value())?,
+            _ => ()
+        }
+    }
+    Some((packets, bytes))
+}
+
+fn parse_u64_be(value: &[u8]) -> Option<u64> {
+    Some(u64::from_be_bytes(value.get(..8)?.try_into().ok()?))
+}
+
+fn parse_ipv4(value: &[u8]) -> Option<IpAddr> {
+    let octets: [u8; 4] = value.get(..4)?.try_into().ok()?;
+    Some(IpAddr::V4(Ipv4Addr::from(octets)))
+}
+
+fn parse_ipv6(value: &[u8]) -> Option<IpAddr> {
+    let octets: [u8; 16] = value.get(..16)?.try_into().ok()?;
+    Some(IpAddr::V6(Ipv6Addr::from(octets)))
+}
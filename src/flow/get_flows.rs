@@ -0,0 +1,201 @@
+use std::collections::{HashMap, HashSet};
+use std::net::{IpAddr, Ipv6Addr};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use clickhouse::Client;
+use log::{error, info, warn};
+use netlink_sys::Socket;
+use tokio::time::{interval, MissedTickBehavior};
+
+use crate::config::config::ServerConfiguration;
+use crate::db::queries::{add_flow_stats, get_peer_addr};
+use crate::db::schema::FlowStat;
+use crate::interface::netns::NamespaceHandle;
+use crate::interface::remote::{get_local_addresses, peer_addresses, remote_network, to_ipv6};
+use crate::interface::sample::timestamp;
+use super::conntrack::{dump_flows, open_socket, Flow};
+
+// Conntrack socket and the previous dump of one namespace
+struct FlowSource {
+    namespace: NamespaceHandle,
+    socket: Arc<Socket>,
+    // Counters by conntrack id, None until the first dump
+    previous: Option<(Instant, HashMap<u32, Flow>)>,
+}
+
+pub async fn save_flows_every_interval(namespaces: &[NamespaceHandle], server_config: &ServerConfiguration, client: &Client) {
+    let collector = server_config.get_collector();
+    let Some(flow_interval) = collector.flow_interval else {
+        return;
+    };
+
+    let mut sources: Vec<FlowSource> = namespaces.iter().filter_map(|namespace| {
+        open_socket(namespace.path.as_deref()).inspect_err(|e| {
+            error!("Failed to open conntrack socket in the {} namespace: {e}", namespace.display_name());
+        }).ok().map(|socket| FlowSource { namespace: namespace.clone(), socket: Arc::new(socket), previous: None })
+    }).collect();
+
+    if sources.is_empty() {
+        error!("No conntrack socket could be opened, flows are not collected");
+        return;
+    }
+
+    let mut flow_timer = interval(flow_interval);
+    flow_timer.set_missed_tick_behavior(MissedTickBehavior::Skip);
+    let mut refresh_timer = interval(collector.refresh_interval);
+    let mut peers: HashMap<Ipv6Addr, String> = HashMap::new();
+    let mut warned_accounting = false;
+
+    info!("Collecting conntrack flows every {} ms.", flow_interval.as_millis());
+
+    loop {
+        tokio::select! {
+            _ = flow_timer.tick() => {
+                let mut flow_stats = Vec::new();
+
+                for source in sources.iter_mut() {
+                    let socket = Arc::clone(&source.socket);
+                    // Large tables take a while to dump, keep it off the async workers
+                    let flows = match tokio::task::spawn_blocking(move || dump_flows(&socket)).await {
+                        Ok(Ok(flows)) => flows,
+                        Ok(Err(e)) => {
+                            error!("Failed to dump conntrack table in the {} namespace: {e}", source.namespace.display_name());
+                            continue;
+                        }
+                        Err(e) => {
+                            error!("Conntrack dump task failed: {e}");
+                            continue;
+                        }
+                    };
+
+                    if !warned_accounting && !flows.is_empty() && flows.iter().all(|flow| !flow.has_counters) {
+                        warn!("Conntrack entries have no counters, enable them with sysctl net.netfilter.nf_conntrack_acct=1");
+                        warned_accounting = true;
+                    }
+
+                    let local = match get_local_addresses(&source.namespace.handle).await {
+                        Ok(local) => local,
+                        Err(e) => {
+                            error!("Failed to get local addresses in the {} namespace: {e}", source.namespace.display_name());
+                            continue;
+                        }
+                    };
+
+                    let instant = Instant::now();
+                    let current: HashMap<u32, Flow> = flows.into_iter()
+                        .filter(|flow| flow.has_counters)
+                        .map(|flow| (flow.id, flow))
+                        .collect();
+
+                    // The first dump is only a baseline, its counters go back to the start of each connection
+                    if let Some((last_instant, previous)) = &source.previous {
+                        let Some(template) = flow_template(server_config, &source.namespace.netns, instant.saturating_duration_since(*last_instant)) else {
+                            continue;
+                        };
+                        let deltas = flow_deltas(previous, &current);
+                        flow_stats.extend(aggregate_flows(&deltas, &local, &peers, collector.flow_prefix_v4, collector.flow_prefix_v6, &template));
+                    }
+                    source.previous = Some((instant, current));
+                }
+
+                if !flow_stats.is_empty() {
+                    add_flow_stats(client, flow_stats).await.inspect_err(|e| {
+                        error!("Failed to save flow stats: {e}");
+                    }).ok();
+                }
+            },
+            _ = refresh_timer.tick() => {
+                match get_peer_addr(client, server_config.get_config()).await {
+                    Ok(addrs) => peers = peer_addresses(&addrs),
+                    Err(e) => error!("Failed to get peer addresses: {e}, continuing with existing peers"),
+                }
+            }
+        }
+    }
+}
+
+// Same timestamp scheme as the interface stats
+fn flow_template(server_config: &ServerConfiguration, netns: &str, interval: Duration) -> Option<FlowStat> {
+    let timestamp = timestamp()?;
+
+    Some(FlowStat {
+        server_id: server_config.get_config().server_id.clone(),
+        netns: netns.to_string(),
+        timestamp: timestamp.as_secs() as u32,
+        timestamp_ms: timestamp.as_millis() as u64,
+        interval_ms: interval.as_millis() as u64,
+        remote: Ipv6Addr::UNSPECIFIED,
+        remote_prefix: 0,
+        peer_server_id: None,
+        flows: 0,
+        rx: 0,
+        tx: 0,
+        rx_p: 0,
+        tx_p: 0
+    })
+}
+
+// Traffic of every connection since the previous dump. Connections that are new, or whose id
+// was reused, count from zero. Connections that ended between two dumps lose their last interval.
+pub fn flow_deltas(previous: &HashMap<u32, Flow>, current: &HashMap<u32, Flow>) -> Vec<Flow> {
+    current.values().filter_map(|flow| {
+        let delta = match previous.get(&flow.id) {
+            Some(old) if flow.orig_bytes >= old.orig_bytes && flow.reply_bytes >= old.reply_bytes
+                && flow.orig_packets >= old.orig_packets && flow.reply_packets >= old.reply_packets => Flow {
+                orig_bytes: flow.orig_bytes - old.orig_bytes,
+                orig_packets: flow.orig_packets - old.orig_packets,
+                reply_bytes: flow.reply_bytes - old.reply_bytes,
+                reply_packets: flow.reply_packets - old.reply_packets,
+                ..flow.clone()
+            },
+            _ => flow.clone()
+        };
+        (delta.orig_packets > 0 || delta.reply_packets > 0).then_some(delta)
+    }).collect()
+}
+
+// Sums traffic by remote network. A connection to a local address was opened by the remote side
+// (its source), anything else by this host or forwarded through it (its destination is remote).
+pub fn aggregate_flows(deltas: &[Flow], local: &HashSet<IpAddr>, peers: &HashMap<Ipv6Addr, String>,
+    prefix_v4: u8, prefix_v6: u8, template: &FlowStat) -> Vec<FlowStat> {
+
+    let mut totals: HashMap<(Ipv6Addr, u8), FlowStat> = HashMap::new();
+
+    for flow in deltas {
+        let (Some(src), Some(dst)) = (flow.src, flow.dst) else {
+            continue;
+        };
+        let inbound = local.contains(&dst);
+        let remote = if inbound { src } else { dst };
+        // Traffic between two local addresses, loopback included
+        if local.contains(&remote) {
+            continue;
+        }
+
+        // Peers are kept by address, other remotes are aggregated to their network
+        let peer = peers.get(&to_ipv6(remote));
+        let (network, prefix) = match peer {
+            Some(_) => remote_network(remote, 32, 128),
+            None => remote_network(remote, prefix_v4, prefix_v6),
+        };
+
+        let total = totals.entry((network, prefix)).or_insert_with(|| FlowStat {
+            remote: network,
+            remote_prefix: prefix,
+            peer_server_id: peer.cloned(),
+            ..template.clone()
+        });
+
+        let (rx, rx_p, tx, tx_p) = if inbound {
+            (flow.orig_bytes, flow.orig_packets, flow.reply_bytes, flow.reply_packets)
+        } else {
+            (flow.reply_bytes, flow.reply_packets, flow.orig_bytes, flow.orig_packets)
+        };
+        total.flows += 1;
+        total.rx += rx;
+        total.rx_p += rx_p;
+        total.tx += tx;
+        total.tx_p += tx_p;
+    }
+    totals.into_values().collect()
+}
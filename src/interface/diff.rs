@@ -0,0 +1,72 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::future::Future;
+use std::hash::Hash;
+use log::{error, info};
+
+// Rows of a table that mirrors kernel state, compared with what the database holds
+#[derive(Debug)]
+pub struct Diff<T> {
+    pub updates: Vec<T>,
+    pub deletes: Vec<T>,
+    pub creates: Vec<T>,
+}
+
+// Rows are matched by key, a row whose key is on both sides is updated when anything else differs
+pub fn diff_by_key<K: Eq + Hash, T: Clone + PartialEq>(fresh: &[T], db: &[T], key: impl Fn(&T) -> K) -> Diff<T> {
+    let mut updates = Vec::new();
+    let mut creates = Vec::new();
+    let mut deletes = Vec::new();
+
+    let fresh_map: HashMap<K, &T> = fresh.iter().map(|row| (key(row), row)).collect();
+    let db_map: HashMap<K, &T> = db.iter().map(|row| (key(row), row)).collect();
+
+    for (key, fresh_row) in &fresh_map {
+        match db_map.get(key) {
+            Some(db_row) if db_row != fresh_row => updates.push((*fresh_row).clone()),
+            Some(_) => (),
+            None => creates.push((*fresh_row).clone()),
+        }
+    }
+
+    for (key, delete) in &db_map {
+        if !fresh_map.contains_key(key) {
+            deletes.push((*delete).clone());
+        }
+    }
+
+    Diff { updates, creates, deletes }
+}
+
+// Changed rows are replaced rather than updated column by column. Rows are deleted by key,
+// so the deletes go first and the changed rows are only inserted again once they are gone.
+pub async fn apply_diff<T: Clone, E: Display, D, A>(diff: Diff<T>, rows: &str,
+    delete: impl FnOnce(Vec<T>) -> D, add: impl FnOnce(Vec<T>) -> A)
+where
+    D: Future<Output = Result<(), E>>,
+    A: Future<Output = Result<(), E>>,
+{
+    let deletes: Vec<T> = diff.deletes.into_iter().chain(diff.updates.iter().cloned()).collect();
+    let mut creates = diff.creates;
+
+    let deleted = if deletes.is_empty() {
+        true
+    } else {
+        info!("Deleting {} {rows}", deletes.len());
+        delete(deletes).await.inspect_err(|e| {
+            error!("Failed to delete {rows}: {e}, keeping the changed rows for the next sync");
+        }).is_ok()
+    };
+
+    // The old rows are still there, inserting the changed ones would duplicate them
+    if deleted {
+        creates.extend(diff.updates);
+    }
+
+    if !creates.is_empty() {
+        info!("Saving {} {rows}", creates.len());
+        add(creates).await.inspect_err(|e| {
+            error!("Failed to save {rows}: {e}");
+        }).ok();
+    }
+}
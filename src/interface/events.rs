@@ -0,0 +1,45 @@
+use netlink_packet_core::{NetlinkMessage, NetlinkPayload};
+use netlink_packet_route::RouteNetlinkMessage;
+use netlink_packet_route::link::LinkMessage;
+
+// rtnetlink multicast groups (linux/rtnetlink.h)
+const RTNLGRP_LINK: u32 = 1;
+const RTNLGRP_IPV4_IFADDR: u32 = 5;
+const RTNLGRP_IPV4_ROUTE: u32 = 7;
+const RTNLGRP_IPV6_IFADDR: u32 = 9;
+const RTNLGRP_IPV6_ROUTE: u32 = 11;
+
+#[derive(Debug)]
+pub enum InterfaceEvent {
+    // RTM_NEWLINK / RTM_DELLINK
+    Link { link: LinkMessage, removed: bool },
+    // RTM_NEWADDR / RTM_DELADDR, only the interface index is needed
+    Address { index: u32 },
+    // RTM_NEWROUTE / RTM_DELROUTE, the routes are dumped again
+    Route,
+}
+
+const fn nl_mgrp(group: u32) -> u32 {
+    if group == 0 { 0 } else { 1 << (group - 1) }
+}
+
+// Multicast groups mask to bind the rtnetlink socket to
+pub const fn interface_event_groups() -> u32 {
+    nl_mgrp(RTNLGRP_LINK) | nl_mgrp(RTNLGRP_IPV4_IFADDR) | nl_mgrp(RTNLGRP_IPV6_IFADDR)
+        | nl_mgrp(RTNLGRP_IPV4_ROUTE) | nl_mgrp(RTNLGRP_IPV6_ROUTE)
+}
+
+pub fn parse_event(message: NetlinkMessage<RouteNetlinkMessage>) -> Option<InterfaceEvent> {
+    if let NetlinkPayload::InnerMessage(inner) = message.payload {
+        return match inner {
+            RouteNetlinkMessage::NewLink(link) => Some(InterfaceEvent::Link { link, removed: false }),
+            RouteNetlinkMessage::DelLink(link) => Some(InterfaceEvent::Link { link, removed: true }),
+            RouteNetlinkMessage::NewAddress(addr) | RouteNetlinkMessage::DelAddress(addr) => {
+                Some(InterfaceEvent::Address { index: addr.header.index })
+            }
+            RouteNetlinkMessage::NewRoute(_) | RouteNetlinkMessage::DelRoute(_) => Some(InterfaceEvent::Route),
+            _ => None
+        };
+    }
+    None
+}
@@ -1,17 +1,35 @@
 use std::collections::HashMap;
 use std::net::Ipv6Addr;
 use std::process;
-use std::time::Duration;
 use futures::StreamExt;
 use clickhouse::Client;
+use futures::channel::mpsc::UnboundedReceiver;
+use netlink_packet_core::NetlinkMessage;
+use netlink_packet_route::RouteNetlinkMessage;
+use netlink_sys::SocketAddr;
+use regex::Regex;
 use rtnetlink::Handle;
 use log::{error, info, warn};
-use tokio::time::interval;
+use tokio::time::{interval, Duration, MissedTickBehavior};
 
-use crate::db::queries::{add_addr, delete_addr, delete_data_efficiently, get_addr, update_addr};
-use crate::{config::config::ServerConfiguration, db::schema::Addr};
+use crate::db::store::Store;
+use crate::{config::config::ServerConfiguration, db::schema::{Addr, Interface, Route}};
 use crate::interface::info;
-use super::info::get_filtered_interfaces_names;
+use crate::alert::engine::{AlertInput, AlertSender};
+use super::events::{parse_event, InterfaceEvent};
+use super::netns::NamespaceHandle;
+use super::get_neighbor::resync_neighbors;
+use super::get_route::{resync_routes, sync_routes, RouteKey};
+use super::get_link::{get_link_info, link_events, resync_links, save_link_events, sync_link};
+use super::sample::unix_timestamp;
+use super::info::{compile_rules, get_all_interfaces, get_filtered_interfaces_names, get_interface_by_index,
+    get_interface_name_from_attribute, get_loopback_from_header, is_interface_matching};
+
+// Route changes come in bursts (an interface going down takes its routes along), they are
+// dumped at most once per delay
+const ROUTE_SYNC_DELAY: Duration = Duration::from_secs(1);
+
+pub type InterfaceEvents = UnboundedReceiver<(NetlinkMessage<RouteNetlinkMessage>, SocketAddr)>;
 
 #[derive(Debug)]
 pub struct Updates {
@@ -20,9 +38,9 @@ pub struct Updates {
     pub creates: Vec<Addr>,
 }
 
-pub async fn get_interface_addresses(handle: &Handle, rules: &[Option<String>], config: &ServerConfiguration, verbose: bool) -> Result<Vec<Addr>, rtnetlink::Error> {
+pub async fn get_interface_addresses(handle: &Handle, netns: &str, rules: &[Option<String>], config: &ServerConfiguration, verbose: bool) -> Result<Vec<Addr>, rtnetlink::Error> {
     // Filter interface names based on the rules
-    let matching_interface_names = get_filtered_interfaces_names(&handle, rules).await?;
+    let matching_interface_names = get_filtered_interfaces_names(handle, rules).await?;
 
     // Process each filtered interface
     let max_concurrent = 10;
@@ -30,7 +48,7 @@ pub async fn get_interface_addresses(handle: &Handle, rules: &[Option<String>],
         let results = futures::stream::iter(matching_interface_names)
             .map(|name| {
                 async move {
-                    get_addresses(handle, name, config, verbose).await.inspect_err(|e| {
+                    get_addresses(handle, netns, name, config, verbose).await.inspect_err(|e| {
                         error!("An error occurred while getting address for {e}");
                     })
                 }
@@ -51,15 +69,15 @@ pub async fn get_interface_addresses(handle: &Handle, rules: &[Option<String>],
             // If all failed
             results.into_iter()
                 .find_map(|res| res.err())
-                .map(|e| Err(e))
+                .map(Err)
                 .unwrap_or(Err(rtnetlink::Error::RequestFailed))
         }
     };
     addrs
 }
 
-pub async fn get_addresses(handle: &Handle, name: String, config: &ServerConfiguration, verbose: bool) -> Result<Addr, rtnetlink::Error> {
-    let interface_addr = info::get_interface_address(&handle, &name).await?;
+pub async fn get_addresses(handle: &Handle, netns: &str, name: String, config: &ServerConfiguration, verbose: bool) -> Result<Addr, rtnetlink::Error> {
+    let interface_addr = info::get_interface_address(handle, &name).await?;
     let mut addresses: Vec<(Option<Ipv6Addr>, Option<u8>)> = Vec::new();
     let mut peers: Vec<(Option<Ipv6Addr>, Option<u8>)> = Vec::new();
 
@@ -104,76 +122,232 @@ pub async fn get_addresses(handle: &Handle, name: String, config: &ServerConfigu
 
     Ok(Addr {
         server_id: config.get_config().server_id.to_string(),
+        netns: netns.to_string(),
         interface: name,
         ipv6: addresses,
         ipv6_peer: peers
     })
 }
 
-pub async fn add_addr_to_database(handle: &Handle, client: &Client, server: &ServerConfiguration) {
+pub async fn add_addr_to_database(namespaces: &[NamespaceHandle], store: &dyn Store, server: &ServerConfiguration) {
 
     info!("Adding interfaces' IPv6/IPv4-mapped addresses...");
 
-    // Delete data efficiently
-    delete_data_efficiently(client, &server.get_config().server_id).await.inspect_err(|e| {
+    // Delete data efficiently, the partition holds the addresses of every namespace
+    store.clear_addresses(&server.get_config().server_id).await.inspect_err(|e| {
         error!("An error occured while deleting data: {e}. Exiting...");
         process::exit(1);
     }).ok();
 
-    // Get interface addresses
-    let addresses = get_interface_addresses(&handle, &server.get_config().interface_filter, &server, true).await;
+    for namespace in namespaces {
+        // Get interface addresses
+        let addresses = get_interface_addresses(&namespace.handle, &namespace.netns, &server.get_config().interface_filter, server, true).await;
 
-    if let Ok(addrs) = addresses {
-        add_addr(client, addrs).await.inspect_err(|e| {
-            error!("An error occured while deleting data: {e}.");
-        }).ok();
+        if let Ok(addrs) = addresses {
+            store.add_addresses(addrs).await.inspect_err(|e| {
+                error!("An error occured while deleting data: {e}.");
+            }).ok();
+        }
     }
 }
 
-pub async fn check_for_interface_updates(handle: &Handle, client: &Client, server: &ServerConfiguration) {
-    let mut interval = interval(Duration::from_secs(5));
-    info!("Checking for interface updates [5 seconds].");
+// Interface state as last written to the database
+#[derive(Default)]
+struct KnownInterfaces {
+    // Addresses by interface name
+    addrs: HashMap<String, Addr>,
+    // Link details by interface name
+    links: HashMap<String, Interface>,
+    // Interface names by index, to detect renames
+    names: HashMap<u32, String>,
+    routes: HashMap<RouteKey, Route>,
+    // Route events were received since the last dump
+    routes_changed: bool,
+}
 
-    loop {
-        interval.tick().await;
-        info!("Checking for interfaces updates...");
-
-        let addresses = match get_interface_addresses(&handle, &server.get_config().interface_filter, &server, false).await {
-            Ok(addrs) => addrs,
-            Err(e) => {
-                error!("Failed to get interface addresses: {e}, skipping update cycle");
-                continue; // Skip this iteration and try again next time
-            }
-        };
+pub async fn check_for_interface_updates(namespace: &NamespaceHandle, client: &Client, store: &dyn Store,
+    server: &ServerConfiguration, alerts: &AlertSender, mut events: InterfaceEvents) {
+    let (handle, netns) = (&namespace.handle, namespace.netns.as_str());
+    let resync_interval = server.get_collector().resync_interval;
+    let mut resync_timer = interval(resync_interval);
+    let mut route_timer = interval(ROUTE_SYNC_DELAY);
+    route_timer.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    let compiled_rules = compile_rules(&server.get_config().interface_filter);
+    let mut known = KnownInterfaces::default();
 
-        let db_addrs = match get_addr(&client, &server.get_config()).await {
-            Ok(addrs) => addrs,
-            Err(e) => {
-                error!("Failed to get addresses from database: {e}, skipping update cycle");
-                continue; // Skip this iteration and try again next time
+    info!("Listening for interface events in the {} namespace, full resync every {} ms.",
+        namespace.display_name(), resync_interval.as_millis());
+
+    loop {
+        tokio::select! {
+            _ = resync_timer.tick() => {
+                info!("Resynchronizing interfaces...");
+                if let Some(fresh) = resync_interfaces(handle, netns, store, server).await {
+                    known.addrs = fresh;
+                }
+                match get_all_interfaces(handle).await {
+                    Ok(links) => {
+                        if let Some(fresh) = resync_links(client, server, namespace, &links, &compiled_rules).await {
+                            known.links = fresh;
+                        }
+                        known.names = links.into_iter()
+                            .filter_map(|link| get_interface_name_from_attribute(link.attributes).map(|name| (link.header.index, name)))
+                            .collect();
+                    },
+                    Err(e) => error!("Failed to get interfaces: {e}, skipping update cycle"),
+                }
+                if let Some(fresh) = resync_routes(handle, netns, client, server, &compiled_rules).await {
+                    known.routes = fresh;
+                    known.routes_changed = false;
+                }
+                resync_neighbors(handle, netns, client, server, &compiled_rules).await;
+                send_to_alerts(alerts, netns, &known);
+            },
+            _ = route_timer.tick(), if known.routes_changed => {
+                known.routes_changed = false;
+                sync_routes(handle, netns, client, server, &compiled_rules, &mut known.routes).await;
+            },
+            message = events.next() => {
+                let Some((message, _)) = message else {
+                    error!("Interface event stream closed");
+                    return;
+                };
+                match parse_event(message) {
+                    Some(InterfaceEvent::Route) => known.routes_changed = true,
+                    Some(event) => {
+                        handle_interface_event(namespace, client, store, server, &compiled_rules, &mut known, event).await;
+                        send_to_alerts(alerts, netns, &known);
+                    },
+                    None => (),
+                }
             }
-        };
+        }
+    }
+}
 
-        let diff = compare(&addresses, &db_addrs);
+// Down and no_address rules are evaluated on the known state of the namespace
+fn send_to_alerts(alerts: &AlertSender, netns: &str, known: &KnownInterfaces) {
+    if alerts.is_enabled() {
+        alerts.send(AlertInput::Interfaces {
+            netns: netns.to_string(),
+            links: known.links.values().cloned().collect(),
+            addrs: known.addrs.values().cloned().collect(),
+        });
+    }
+}
 
-        if !diff.creates.is_empty() {
-            info!("Creating new interfaces (Update)");
-            add_addr(client, diff.creates).await.ok();
+async fn resync_interfaces(handle: &Handle, netns: &str, store: &dyn Store, server: &ServerConfiguration) -> Option<HashMap<String, Addr>> {
+    let addresses = match get_interface_addresses(handle, netns, &server.get_config().interface_filter, server, false).await {
+        Ok(addrs) => addrs,
+        Err(e) => {
+            error!("Failed to get interface addresses: {e}, skipping update cycle");
+            return None; // Skip this iteration and try again next time
         }
+    };
 
-        if !diff.updates.is_empty() {
-            info!("Updating interfaces (Update)");
-            update_addr(client, diff.updates).await.ok();
+    let db_addrs = match store.get_addresses(server.get_config(), netns).await {
+        Ok(addrs) => addrs,
+        Err(e) => {
+            error!("Failed to get addresses from database: {e}, skipping update cycle");
+            return None; // Skip this iteration and try again next time
         }
+    };
+
+    apply_updates(store, compare(&addresses, &db_addrs)).await;
+
+    Some(addresses.into_iter().map(|addr| (addr.interface.clone(), addr)).collect())
+}
+
+async fn handle_interface_event(namespace: &NamespaceHandle, client: &Client, store: &dyn Store, server: &ServerConfiguration, rules: &[Option<Regex>],
+    known: &mut KnownInterfaces, event: InterfaceEvent) {
+    let (handle, netns) = (&namespace.handle, namespace.netns.as_str());
 
-        if !diff.deletes.is_empty() {
-            info!("Deleting interfaces (Update)");
-            delete_addr(client, diff.deletes).await.ok();
+    let (link, removed, is_link_event) = match event {
+        InterfaceEvent::Link { link, removed } => (link, removed, true),
+        InterfaceEvent::Address { index } => match get_interface_by_index(handle, index).await {
+            Ok(link) => (link, false, false),
+            // The interface is already gone, RTM_DELLINK will handle it
+            Err(_) => return,
+        },
+        InterfaceEvent::Route => return,
+    };
+
+    let index = link.header.index;
+    let is_loopback = get_loopback_from_header(link.header.clone());
+    let Some(name) = get_interface_name_from_attribute(link.attributes.clone()) else {
+        return;
+    };
+
+    let mut events = Vec::new();
+
+    // The interface was renamed, drop it under the previous name
+    if let Some(old_name) = known.names.get(&index).filter(|old_name| **old_name != name).cloned() {
+        info!("Interface {old_name} was renamed to {name}");
+        sync_interface(store, &mut known.addrs, old_name.clone(), None).await;
+
+        // Carry the link details over to the new name, so the rename is
+        // recorded as such rather than as a deletion and a creation
+        if let Some(previous) = known.links.get(&old_name).cloned() {
+            sync_link(client, &mut known.links, old_name, None).await;
+            let renamed = Interface { interface: name.clone(), ..previous.clone() };
+            events.extend(link_events(Some(&previous), Some(&renamed), unix_timestamp()));
+            known.links.insert(name.clone(), renamed);
         }
     }
+
+    if removed {
+        known.names.remove(&index);
+    } else {
+        known.names.insert(index, name.clone());
+    }
+    let matching = !removed && is_interface_matching(&name, is_loopback, rules);
+
+    // Address events don't change the link details
+    if is_link_event {
+        let fresh_link = if matching { get_link_info(&link, &server.get_config().server_id, netns, namespace.path.as_deref()) } else { None };
+        events.extend(sync_link(client, &mut known.links, name.clone(), fresh_link).await);
+    }
+    save_link_events(client, events).await;
+
+    let fresh = if matching {
+        get_addresses(handle, netns, name.clone(), server, false).await.ok()
+    } else {
+        None
+    };
+
+    sync_interface(store, &mut known.addrs, name, fresh).await;
+}
+
+async fn sync_interface(store: &dyn Store, known: &mut HashMap<String, Addr>, name: String, fresh: Option<Addr>) {
+    let cached: Vec<Addr> = known.get(&name).cloned().into_iter().collect();
+    let current: Vec<Addr> = fresh.clone().into_iter().collect();
+
+    apply_updates(store, compare(&current, &cached)).await;
+
+    match fresh {
+        Some(addr) => known.insert(name, addr),
+        None => known.remove(&name),
+    };
+}
+
+async fn apply_updates(store: &dyn Store, diff: Updates) {
+    if !diff.creates.is_empty() {
+        info!("Creating new interfaces (Update)");
+        store.add_addresses(diff.creates).await.ok();
+    }
+
+    if !diff.updates.is_empty() {
+        info!("Updating interfaces (Update)");
+        store.update_addresses(diff.updates).await.ok();
+    }
+
+    if !diff.deletes.is_empty() {
+        info!("Deleting interfaces (Update)");
+        store.delete_addresses(diff.deletes).await.ok();
+    }
 }
 
-pub fn compare(fresh: &Vec<Addr>, db: &Vec<Addr>) -> Updates {
+pub fn compare(fresh: &[Addr], db: &[Addr]) -> Updates {
     let mut updates = Vec::new();
     let mut creates = Vec::new();
     let mut deletes = Vec::new();
@@ -0,0 +1,365 @@
+use std::collections::HashMap;
+use std::io;
+use std::net::{Ipv4Addr, Ipv6Addr, UdpSocket};
+use std::os::fd::AsRawFd;
+use std::path::Path;
+use clickhouse::Client;
+use log::{error, info};
+use regex::Regex;
+use netlink_packet_route::link::{BondPortState, InfoBond, InfoBondPort, InfoData, InfoPortData, InfoVlan, InfoVxlan,
+    LinkAttribute, LinkFlag, LinkInfo, LinkMessage, State};
+use syscalls::{syscall, Sysno};
+
+use crate::db::queries::{add_interfaces, add_link_events, delete_interfaces, get_interfaces};
+use crate::{config::config::ServerConfiguration, db::schema::{Interface, LinkEvent}};
+use super::diff::{apply_diff, diff_by_key, Diff};
+use super::netns::{in_namespace, NamespaceHandle};
+use super::sample::unix_timestamp;
+use super::info::{get_interface_name_from_attribute, get_loopback_from_header, is_interface_matching};
+
+// linux/sockios.h and linux/ethtool.h
+const SIOCETHTOOL: usize = 0x8946;
+const ETHTOOL_GLINKSETTINGS: u32 = 0x4c;
+const SPEED_UNKNOWN: u32 = u32::MAX;
+const DUPLEX_HALF: u8 = 0;
+const DUPLEX_FULL: u8 = 1;
+// struct ethtool_link_settings up to the link mode masks, in 32-bit words
+const LINK_SETTINGS_WORDS: usize = 12;
+// Byte offsets in struct ethtool_link_settings
+const DUPLEX_OFFSET: usize = 8;
+const NWORDS_OFFSET: usize = 15;
+
+// struct ifreq with the ifr_data member of the union
+#[repr(C)]
+struct IfReq {
+    name: [u8; 16],
+    data: *mut u32,
+    padding: [u8; 16],
+}
+
+pub fn parse_link(link: &LinkMessage, server_id: &str, netns: &str) -> Option<Interface> {
+    let name = get_interface_name_from_attribute(link.attributes.clone())?;
+
+    let mut interface = Interface {
+        server_id: server_id.to_string(),
+        netns: netns.to_string(),
+        interface: name,
+        ifindex: link.header.index,
+        master_ifindex: None,
+        kind: None,
+        parent_ifindex: None,
+        bond_mode: None,
+        slave_state: None,
+        vlan_id: None,
+        vxlan_vni: None,
+        vxlan_remote: None,
+        mac: None,
+        oper_state: oper_state_name(&State::Unknown),
+        admin_up: link.header.flags.contains(&LinkFlag::Up),
+        carrier: false,
+        carrier_changes: 0,
+        mtu: 0,
+        txqlen: 0,
+        speed: None,
+        duplex: None
+    };
+
+    for attribute in link.attributes.iter() {
+        match attribute {
+            LinkAttribute::OperState(state) => interface.oper_state = oper_state_name(state),
+            LinkAttribute::Carrier(carrier) => interface.carrier = *carrier != 0,
+            LinkAttribute::CarrierChanges(changes) => interface.carrier_changes = *changes,
+            LinkAttribute::Mtu(mtu) => interface.mtu = *mtu,
+            LinkAttribute::TxQueueLen(txqlen) => interface.txqlen = *txqlen,
+            LinkAttribute::Controller(master) => interface.master_ifindex = Some(*master),
+            LinkAttribute::Address(mac) if !mac.is_empty() => interface.mac = Some(format_mac(mac)),
+            LinkAttribute::Link(parent) if *parent != link.header.index => interface.parent_ifindex = Some(*parent),
+            LinkAttribute::LinkInfo(infos) => parse_link_info(infos, &mut interface),
+            _ => ()
+        }
+    }
+
+    // The lower device of a tunnel can be in another namespace, its index means nothing here.
+    // The two ends of a veth point at each other, neither is the parent.
+    if interface.kind.as_deref() == Some("veth") || link.attributes.iter().any(|attribute| matches!(attribute, LinkAttribute::NetnsId(_))) {
+        interface.parent_ifindex = None;
+    }
+    Some(interface)
+}
+
+// Kind of the link and its place in the bond, bridge or VLAN hierarchy
+fn parse_link_info(infos: &[LinkInfo], interface: &mut Interface) {
+    for info in infos {
+        match info {
+            LinkInfo::Kind(kind) => interface.kind = Some(kind.to_string()),
+            LinkInfo::Data(InfoData::Bond(bond)) => {
+                for attribute in bond {
+                    if let InfoBond::Mode(mode) = attribute {
+                        interface.bond_mode = Some(bond_mode_name(*mode));
+                    }
+                }
+            }
+            LinkInfo::Data(InfoData::Vlan(vlan)) => {
+                for attribute in vlan {
+                    if let InfoVlan::Id(id) = attribute {
+                        interface.vlan_id = Some(*id);
+                    }
+                }
+            }
+            LinkInfo::Data(InfoData::Vxlan(vxlan)) => {
+                for attribute in vxlan {
+                    match attribute {
+                        InfoVxlan::Id(vni) => interface.vxlan_vni = Some(*vni),
+                        InfoVxlan::Group(group) => {
+                            if let Ok(octets) = <[u8; 4]>::try_from(group.as_slice()) {
+                                interface.vxlan_remote = Some(Ipv4Addr::from(octets).to_ipv6_mapped());
+                            }
+                        }
+                        InfoVxlan::Group6(group) => {
+                            if let Ok(octets) = <[u8; 16]>::try_from(group.as_slice()) {
+                                interface.vxlan_remote = Some(Ipv6Addr::from(octets));
+                            }
+                        }
+                        _ => ()
+                    }
+                }
+            }
+            LinkInfo::PortData(InfoPortData::BondPort(port)) => {
+                for attribute in port {
+                    if let InfoBondPort::BondPortState(state) = attribute {
+                        interface.slave_state = Some(match state {
+                            BondPortState::Active => "active".to_string(),
+                            BondPortState::Backup => "backup".to_string(),
+                            other => format!("{other:?}").to_lowercase(),
+                        });
+                    }
+                }
+            }
+            _ => ()
+        }
+    }
+}
+
+// Same names as /sys/class/net/<bond>/bonding/mode
+pub fn bond_mode_name(mode: u8) -> String {
+    match mode {
+        0 => "balance-rr".to_string(),
+        1 => "active-backup".to_string(),
+        2 => "balance-xor".to_string(),
+        3 => "broadcast".to_string(),
+        4 => "802.3ad".to_string(),
+        5 => "balance-tlb".to_string(),
+        6 => "balance-alb".to_string(),
+        other => other.to_string(),
+    }
+}
+
+// `path` is the namespace of the link, None for the daemon's own
+pub fn get_link_info(link: &LinkMessage, server_id: &str, netns: &str, path: Option<&Path>) -> Option<Interface> {
+    let mut interface = parse_link(link, server_id, netns)?;
+    (interface.speed, interface.duplex) = get_ethtool_settings(path, &interface.interface);
+    Some(interface)
+}
+
+// Same names as /sys/class/net/<interface>/operstate
+pub fn oper_state_name(state: &State) -> String {
+    match state {
+        State::Unknown => "unknown".to_string(),
+        State::NotPresent => "notpresent".to_string(),
+        State::Down => "down".to_string(),
+        State::LowerLayerDown => "lowerlayerdown".to_string(),
+        State::Testing => "testing".to_string(),
+        State::Dormant => "dormant".to_string(),
+        State::Up => "up".to_string(),
+        other => format!("{other:?}").to_lowercase(),
+    }
+}
+
+pub fn format_mac(mac: &[u8]) -> String {
+    mac.iter().map(|byte| format!("{byte:02x}")).collect::<Vec<String>>().join(":")
+}
+
+// Speed and duplex from the ETHTOOL_GLINKSETTINGS ioctl. Virtual and down links
+// report no speed or duplex, and many drivers don't support it at all.
+pub fn get_ethtool_settings(path: Option<&Path>, name: &str) -> (Option<u32>, Option<String>) {
+    // The ioctl looks the link up in the namespace the socket was created in
+    let settings = in_namespace(path, || UdpSocket::bind("0.0.0.0:0")).and_then(|socket| {
+        // The first request only asks the kernel how many link mode words it needs
+        let probe = ethtool_link_settings(&socket, name, 0)?;
+        let nwords = (settings_byte(&probe, NWORDS_OFFSET) as i8).checked_neg().filter(|nwords| *nwords > 0)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "no link mode words in ethtool reply"))?;
+        ethtool_link_settings(&socket, name, nwords)
+    });
+
+    match settings {
+        Ok(settings) => parse_link_settings(&settings),
+        Err(_) => (None, None),
+    }
+}
+
+fn ethtool_link_settings(socket: &UdpSocket, name: &str, nwords: i8) -> io::Result<Vec<u32>> {
+    // The name has to leave room for the terminating nul
+    if name.len() >= 16 {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "interface name too long"));
+    }
+
+    // Followed by the supported, advertised and link partner masks
+    let mut settings = vec![0u32; LINK_SETTINGS_WORDS + 3 * nwords.max(0) as usize];
+    settings[0] = ETHTOOL_GLINKSETTINGS;
+    let mut word = settings[NWORDS_OFFSET / 4].to_ne_bytes();
+    word[NWORDS_OFFSET % 4] = nwords as u8;
+    settings[NWORDS_OFFSET / 4] = u32::from_ne_bytes(word);
+
+    let mut request = IfReq { name: [0; 16], data: settings.as_mut_ptr(), padding: [0; 16] };
+    request.name[..name.len()].copy_from_slice(name.as_bytes());
+
+    // SAFETY: the kernel writes at most nwords masks into settings, which outlives the call
+    unsafe { syscall!(Sysno::ioctl, socket.as_raw_fd(), SIOCETHTOOL, &mut request as *mut IfReq) }
+        .map_err(io::Error::from)?;
+    Ok(settings)
+}
+
+fn settings_byte(settings: &[u32], offset: usize) -> u8 {
+    settings[offset / 4].to_ne_bytes()[offset % 4]
+}
+
+// Same values as /sys/class/net/<interface>/{speed,duplex}
+pub fn parse_link_settings(settings: &[u32]) -> (Option<u32>, Option<String>) {
+    let speed = Some(settings[1]).filter(|speed| *speed != 0 && *speed != SPEED_UNKNOWN);
+    let duplex = match settings_byte(settings, DUPLEX_OFFSET) {
+        DUPLEX_HALF => Some("half".to_string()),
+        DUPLEX_FULL => Some("full".to_string()),
+        _ => None,
+    };
+    (speed, duplex)
+}
+
+pub fn get_filtered_links(links: &[LinkMessage], rules: &[Option<Regex>], server_id: &str, netns: &str, path: Option<&Path>) -> Vec<Interface> {
+    links.iter()
+        .filter(|link| {
+            let is_loopback = get_loopback_from_header(link.header.clone());
+            get_interface_name_from_attribute(link.attributes.clone())
+                .is_some_and(|name| is_interface_matching(&name, is_loopback, rules))
+        })
+        .filter_map(|link| get_link_info(link, server_id, netns, path))
+        .collect()
+}
+
+pub async fn resync_links(client: &Client, server: &ServerConfiguration, namespace: &NamespaceHandle, links: &[LinkMessage], rules: &[Option<Regex>]) -> Option<HashMap<String, Interface>> {
+    let netns = namespace.netns.as_str();
+    let interfaces = get_filtered_links(links, rules, &server.get_config().server_id, netns, namespace.path.as_deref());
+
+    let db_interfaces = match get_interfaces(client, server.get_config(), netns).await {
+        Ok(interfaces) => interfaces,
+        Err(e) => {
+            error!("Failed to get interface details from database: {e}, skipping update cycle");
+            return None;
+        }
+    };
+
+    // Catches transitions missed while the daemon was down or events were lost
+    save_link_events(client, collect_link_events(&db_interfaces, &interfaces, unix_timestamp())).await;
+    apply_link_updates(client, compare_links(&interfaces, &db_interfaces)).await;
+
+    Some(interfaces.into_iter().map(|interface| (interface.interface.clone(), interface)).collect())
+}
+
+// Returns the state transitions, the caller decides whether to save them
+pub async fn sync_link(client: &Client, known: &mut HashMap<String, Interface>, name: String, fresh: Option<Interface>) -> Vec<LinkEvent> {
+    let cached: Vec<Interface> = known.get(&name).cloned().into_iter().collect();
+    let current: Vec<Interface> = fresh.clone().into_iter().collect();
+
+    let events = collect_link_events(&cached, &current, unix_timestamp());
+    apply_link_updates(client, compare_links(&current, &cached)).await;
+
+    match fresh {
+        Some(interface) => known.insert(name, interface),
+        None => known.remove(&name),
+    };
+    events
+}
+
+pub async fn save_link_events(client: &Client, events: Vec<LinkEvent>) {
+    if events.is_empty() {
+        return;
+    }
+
+    for event in &events {
+        info!("{}: {} {} -> {}", event.interface, event.event, event.old_value, event.new_value);
+    }
+    add_link_events(client, events).await.inspect_err(|e| {
+        error!("Failed to save link events: {e}");
+    }).ok();
+}
+
+// Match interfaces by name and list the transitions between the two states
+pub fn collect_link_events(old: &[Interface], new: &[Interface], timestamp: u32) -> Vec<LinkEvent> {
+    let old_map: HashMap<&String, &Interface> = old.iter().map(|link| (&link.interface, link)).collect();
+    let new_map: HashMap<&String, &Interface> = new.iter().map(|link| (&link.interface, link)).collect();
+
+    let mut events: Vec<LinkEvent> = new.iter()
+        .flat_map(|link| link_events(old_map.get(&link.interface).copied(), Some(link), timestamp))
+        .collect();
+    events.extend(old.iter()
+        .filter(|link| !new_map.contains_key(&link.interface))
+        .flat_map(|link| link_events(Some(link), None, timestamp)));
+    events
+}
+
+pub fn link_events(old: Option<&Interface>, new: Option<&Interface>, timestamp: u32) -> Vec<LinkEvent> {
+    let event = |link: &Interface, event: &str, old_value: String, new_value: String| LinkEvent {
+        server_id: link.server_id.clone(),
+        netns: link.netns.clone(),
+        interface: link.interface.clone(),
+        timestamp,
+        event: event.to_string(),
+        old_value,
+        new_value
+    };
+    let up_down = |up: bool| if up { "up".to_string() } else { "down".to_string() };
+
+    match (old, new) {
+        (None, Some(new)) => vec![event(new, "created", String::new(), new.oper_state.clone())],
+        (Some(old), None) => vec![event(old, "deleted", old.oper_state.clone(), String::new())],
+        (Some(old), Some(new)) => {
+            let mut events = Vec::new();
+            if old.interface != new.interface {
+                events.push(event(new, "renamed", old.interface.clone(), new.interface.clone()));
+            }
+            if old.admin_up != new.admin_up {
+                events.push(event(new, "admin_state", up_down(old.admin_up), up_down(new.admin_up)));
+            }
+            if old.oper_state != new.oper_state {
+                events.push(event(new, "oper_state", old.oper_state.clone(), new.oper_state.clone()));
+            }
+            // Enslaved to or released from a bond or bridge
+            if old.master_ifindex != new.master_ifindex {
+                let ifindex = |master: Option<u32>| master.map(|index| index.to_string()).unwrap_or_default();
+                events.push(event(new, "master", ifindex(old.master_ifindex), ifindex(new.master_ifindex)));
+            }
+            // Failover between the slaves of an active-backup bond
+            if old.slave_state.is_some() && new.slave_state.is_some() && old.slave_state != new.slave_state {
+                events.push(event(new, "slave_state", old.slave_state.clone().unwrap_or_default(),
+                    new.slave_state.clone().unwrap_or_default()));
+            }
+            if old.carrier != new.carrier {
+                events.push(event(new, "carrier", up_down(old.carrier), up_down(new.carrier)));
+            } else if old.carrier_changes != new.carrier_changes {
+                // The carrier went down and back up between two observations
+                events.push(event(new, "carrier_flap", old.carrier_changes.to_string(), new.carrier_changes.to_string()));
+            }
+            events
+        }
+        (None, None) => Vec::new()
+    }
+}
+
+async fn apply_link_updates(client: &Client, diff: Diff<Interface>) {
+    apply_diff(diff, "interface detail(s)",
+        |deletes| async move { delete_interfaces(client, &deletes).await },
+        |creates| add_interfaces(client, creates)).await;
+}
+
+pub fn compare_links(fresh: &[Interface], db: &[Interface]) -> Diff<Interface> {
+    diff_by_key(fresh, db, |link| link.interface.clone())
+}
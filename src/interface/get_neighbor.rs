@@ -0,0 +1,104 @@
+use std::collections::HashMap;
+use clickhouse::Client;
+use futures::TryStreamExt;
+use log::error;
+use netlink_packet_route::neighbour::{NeighbourAddress, NeighbourAttribute, NeighbourMessage, NeighbourState};
+use regex::Regex;
+use rtnetlink::{Handle, IpVersion};
+
+use crate::config::config::ServerConfiguration;
+use crate::db::queries::{add_neighbors, delete_neighbors, get_neighbors};
+use crate::db::schema::Neighbor;
+use super::get_link::format_mac;
+use super::diff::{apply_diff, diff_by_key, Diff};
+use super::info::get_filtered_interfaces_by_index;
+
+// ARP and NDP entries, dumped per family as an unspecified family also returns the bridge FDB
+pub async fn get_all_neighbors(handle: &Handle, netns: &str, rules: &[Option<Regex>], server_id: &str) -> Result<Vec<Neighbor>, rtnetlink::Error> {
+    let names = get_filtered_interfaces_by_index(handle, rules).await?;
+
+    let mut neighbors = Vec::new();
+    for version in [IpVersion::V4, IpVersion::V6] {
+        let messages: Vec<NeighbourMessage> = handle.neighbours().get().set_family(version).execute().try_collect().await?;
+        neighbors.extend(messages.iter().filter_map(|message| parse_neighbor(message, server_id, netns, &names)));
+    }
+    Ok(neighbors)
+}
+
+// `names` only holds the filtered interfaces
+pub fn parse_neighbor(message: &NeighbourMessage, server_id: &str, netns: &str, names: &HashMap<u32, String>) -> Option<Neighbor> {
+    let header = &message.header;
+    // Multicast, broadcast and point-to-point entries never need resolution
+    if matches!(header.state, NeighbourState::Noarp | NeighbourState::None) {
+        return None;
+    }
+    let interface = names.get(&header.ifindex)?;
+
+    let mut ip = None;
+    let mut mac = None;
+    for attribute in &message.attributes {
+        match attribute {
+            NeighbourAttribute::Destination(NeighbourAddress::Inet(v4)) => ip = Some(v4.to_ipv6_mapped()),
+            NeighbourAttribute::Destination(NeighbourAddress::Inet6(v6)) => ip = Some(*v6),
+            NeighbourAttribute::LinkLocalAddress(address) if !address.is_empty() => mac = Some(format_mac(address)),
+            _ => ()
+        }
+    }
+
+    Some(Neighbor {
+        server_id: server_id.to_string(),
+        netns: netns.to_string(),
+        interface: interface.clone(),
+        ip: ip?,
+        mac,
+        state: neighbor_state_name(&header.state)
+    })
+}
+
+pub fn neighbor_state_name(state: &NeighbourState) -> String {
+    match state {
+        NeighbourState::Incomplete => "INCOMPLETE".to_string(),
+        NeighbourState::Reachable => "REACHABLE".to_string(),
+        NeighbourState::Stale => "STALE".to_string(),
+        NeighbourState::Delay => "DELAY".to_string(),
+        NeighbourState::Probe => "PROBE".to_string(),
+        NeighbourState::Failed => "FAILED".to_string(),
+        NeighbourState::Noarp => "NOARP".to_string(),
+        NeighbourState::Permanent => "PERMANENT".to_string(),
+        NeighbourState::None => "NONE".to_string(),
+        other => format!("{other:?}").to_uppercase(),
+    }
+}
+
+// Entries move between REACHABLE and STALE all the time, they are only compared on resync
+// rather than on every neighbor event
+pub async fn resync_neighbors(handle: &Handle, netns: &str, client: &Client, server: &ServerConfiguration, rules: &[Option<Regex>]) {
+    let neighbors = match get_all_neighbors(handle, netns, rules, &server.get_config().server_id).await {
+        Ok(neighbors) => neighbors,
+        Err(e) => {
+            error!("Failed to get neighbors: {e}, skipping update cycle");
+            return;
+        }
+    };
+
+    let db_neighbors = match get_neighbors(client, server.get_config(), netns).await {
+        Ok(neighbors) => neighbors,
+        Err(e) => {
+            error!("Failed to get neighbors from database: {e}, skipping update cycle");
+            return;
+        }
+    };
+
+    apply_neighbor_updates(client, compare_neighbors(&neighbors, &db_neighbors)).await;
+}
+
+async fn apply_neighbor_updates(client: &Client, diff: Diff<Neighbor>) {
+    apply_diff(diff, "neighbor(s)",
+        |deletes| async move { delete_neighbors(client, &deletes).await },
+        |creates| add_neighbors(client, creates)).await;
+}
+
+// The same address can be resolved on several interfaces
+pub fn compare_neighbors(fresh: &[Neighbor], db: &[Neighbor]) -> Diff<Neighbor> {
+    diff_by_key(fresh, db, |neighbor| (neighbor.interface.clone(), neighbor.ip))
+}
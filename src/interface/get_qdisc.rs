@@ -0,0 +1,208 @@
+use std::collections::HashSet;
+use std::time::{Duration, Instant};
+use clickhouse::Client;
+use futures::TryStreamExt;
+use log::{error, info, warn};
+use netlink_packet_route::tc::{TcAttribute, TcHandle, TcMessage, TcStats2};
+use rtnetlink::Handle;
+use regex::Regex;
+use tokio::time::{interval, MissedTickBehavior};
+
+use crate::config::config::ServerConfiguration;
+use crate::db::queries::{add_qdisc_stats, add_stat_reset};
+use crate::db::schema::{QdiscStat, StatReset};
+use super::get_stats::{counter_delta, counter_delta32};
+use super::info::{compile_rules, get_filtered_interfaces_by_index};
+use super::netns::NamespaceHandle;
+use super::sample::{interval_ms, timestamp, Baselines};
+
+// Previous sample by namespace, interface, handle, parent and whether it is a class.
+// Default qdiscs have no handle, the children of mq only differ by their parent.
+type QdiscKey = (String, String, String, String, bool);
+
+pub async fn save_qdisc_stats_every_interval(namespaces: &[NamespaceHandle], server_config: &ServerConfiguration, client: &Client) {
+    let Some(qdisc_interval) = server_config.get_collector().qdisc_interval else {
+        return;
+    };
+
+    let mut qdisc_timer = interval(qdisc_interval);
+    qdisc_timer.set_missed_tick_behavior(MissedTickBehavior::Skip);
+    let rules = compile_rules(&server_config.get_config().interface_filter);
+    let mut last_stats = Baselines::default();
+
+    info!("Collecting qdisc statistics every {} ms.", qdisc_interval.as_millis());
+
+    loop {
+        qdisc_timer.tick().await;
+
+        let mut samples = Vec::new();
+        for namespace in namespaces {
+            match get_qdisc_stats(&namespace.handle, &namespace.netns, &rules, server_config).await {
+                Ok(stats) => samples.extend(stats),
+                Err(e) => error!("Failed to get qdisc statistics in the {} namespace: {e}", namespace.display_name()),
+            }
+        }
+
+        let (stats, resets) = save_qdisc_stat(&mut last_stats, samples);
+        if !stats.is_empty() {
+            add_qdisc_stats(client, stats).await.inspect_err(|e| {
+                error!("Failed to save qdisc stats: {e}");
+            }).ok();
+        }
+        if !resets.is_empty() {
+            add_stat_reset(client, resets).await.inspect_err(|e| {
+                error!("Failed to save qdisc counter resets: {e}");
+            }).ok();
+        }
+    }
+}
+
+// Qdiscs and classes of the filtered interfaces
+async fn get_qdisc_stats(handle: &Handle, netns: &str, rules: &[Option<Regex>], config: &ServerConfiguration) -> Result<Vec<QdiscStat>, rtnetlink::Error> {
+    let names = get_filtered_interfaces_by_index(handle, rules).await?;
+
+    let Some(timestamp) = timestamp() else {
+        return Ok(Vec::new());
+    };
+    let server_id = config.get_config().server_id.as_str();
+    let instant = Instant::now();
+
+    // One dump covers the qdiscs of every interface, classes are dumped per interface
+    let qdiscs: Vec<TcMessage> = handle.qdisc().get().execute().try_collect().await?;
+    let mut messages: Vec<(TcMessage, bool)> = qdiscs.into_iter().map(|qdisc| (qdisc, false)).collect();
+    for index in names.keys() {
+        let classes: Vec<TcMessage> = handle.traffic_class(*index as i32).get().execute().try_collect().await?;
+        messages.extend(classes.into_iter().map(|class| (class, true)));
+    }
+
+    Ok(messages.iter()
+        .filter_map(|(message, is_class)| {
+            let interface = names.get(&(message.header.index as u32))?;
+            get_tc_stats(message, server_id, netns, interface, *is_class, timestamp)
+        })
+        .map(|stat| QdiscStat { instant: Some(instant), ..stat })
+        .collect())
+}
+
+pub fn get_tc_stats(message: &TcMessage, server_id: &str, netns: &str, interface: &str, is_class: bool, timestamp: Duration) -> Option<QdiscStat> {
+    let mut stat = QdiscStat {
+        server_id: server_id.to_string(),
+        netns: netns.to_string(),
+        interface: interface.to_string(),
+        timestamp: timestamp.as_secs() as u32,
+        timestamp_ms: timestamp.as_millis() as u64,
+        handle: format_tc_handle(message.header.handle),
+        parent: format_tc_handle(message.header.parent),
+        is_class,
+        ..Default::default()
+    };
+    let mut has_stats = false;
+    let mut has_stats2 = false;
+
+    for attribute in &message.attributes {
+        match attribute {
+            TcAttribute::Kind(kind) => stat.kind = kind.clone(),
+            TcAttribute::Stats2(stats) => {
+                has_stats2 = true;
+                for stats in stats {
+                    match stats {
+                        TcStats2::Basic(basic) => {
+                            stat.bytes = basic.bytes;
+                            stat.packets = basic.packets as u64;
+                        }
+                        TcStats2::Queue(queue) => {
+                            stat.drops = queue.drops as u64;
+                            stat.overlimits = queue.overlimits as u64;
+                            stat.requeues = queue.requeues as u64;
+                            stat.backlog = queue.backlog;
+                            stat.qlen = queue.qlen;
+                        }
+                        _ => ()
+                    }
+                }
+            }
+            // Older kernels only send TCA_STATS, which has no requeues
+            TcAttribute::Stats(stats) if !has_stats2 => {
+                has_stats = true;
+                stat.bytes = stats.bytes;
+                stat.packets = stats.packets as u64;
+                stat.drops = stats.drops as u64;
+                stat.overlimits = stats.overlimits as u64;
+                stat.backlog = stats.backlog;
+                stat.qlen = stats.qlen;
+            }
+            _ => ()
+        }
+    }
+    (has_stats || has_stats2).then_some(stat)
+}
+
+// Same notation as tc: major and minor in hex, the minor is left out when 0
+pub fn format_tc_handle(handle: TcHandle) -> String {
+    match handle {
+        TcHandle::ROOT => "root".to_string(),
+        TcHandle::INGRESS => "ingress".to_string(),
+        TcHandle::UNSPEC => "none".to_string(),
+        TcHandle { major, minor: 0 } => format!("{major:x}:"),
+        TcHandle { major, minor } => format!("{major:x}:{minor:x}"),
+    }
+}
+
+// Same as save_stat: the first sample is a baseline, replaced qdiscs and counter resets skip a sample
+// and are recorded in stat_reset
+pub fn save_qdisc_stat(last_stats: &mut Baselines<QdiscKey, QdiscStat>, stats: Vec<QdiscStat>) -> (Vec<QdiscStat>, Vec<StatReset>) {
+    let mut final_stats = Vec::new();
+    let mut resets = Vec::new();
+    let mut seen = HashSet::new();
+
+    for curr_stat in stats {
+        let key: QdiscKey = (curr_stat.netns.clone(), curr_stat.interface.clone(), curr_stat.handle.clone(),
+            curr_stat.parent.clone(), curr_stat.is_class);
+        seen.insert(key.clone());
+
+        let mut reset = None;
+        let stat = last_stats.delta(key, curr_stat, |curr_stat, old_data| {
+            let stat = qdisc_delta(curr_stat, old_data);
+            if stat.is_none() {
+                reset = Some(qdisc_reset(curr_stat));
+            }
+            stat
+        });
+        final_stats.extend(stat);
+        resets.extend(reset);
+    }
+
+    // Qdiscs that were deleted since
+    last_stats.retain(|key| seen.contains(key));
+    (final_stats, resets)
+}
+
+fn qdisc_reset(stat: &QdiscStat) -> StatReset {
+    let kind = if stat.is_class { "class" } else { "qdisc" };
+    warn!("Counters of {} {} on {} were reset, skipping sample", stat.kind, stat.handle, stat.interface);
+    StatReset {
+        server_id: stat.server_id.clone(),
+        netns: stat.netns.clone(),
+        interface: stat.interface.clone(),
+        timestamp: stat.timestamp,
+        reason: format!("{kind} {} parent {}", stat.handle, stat.parent)
+    }
+}
+
+pub fn qdisc_delta(curr_stat: &QdiscStat, old_data: &QdiscStat) -> Option<QdiscStat> {
+    // A different qdisc was attached under the same handle
+    if curr_stat.kind != old_data.kind {
+        return None;
+    }
+
+    Some(QdiscStat {
+        interval_ms: interval_ms(curr_stat, old_data),
+        bytes: counter_delta(curr_stat.bytes, old_data.bytes)?,
+        // The kernel keeps these in 32 bits
+        packets: counter_delta32(curr_stat.packets, old_data.packets)?,
+        drops: counter_delta32(curr_stat.drops, old_data.drops)?,
+        overlimits: counter_delta32(curr_stat.overlimits, old_data.overlimits)?,
+        requeues: counter_delta32(curr_stat.requeues, old_data.requeues)?,
+        ..curr_stat.clone()
+    })
+}
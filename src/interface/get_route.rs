@@ -0,0 +1,177 @@
+use std::collections::HashMap;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use clickhouse::Client;
+use futures::TryStreamExt;
+use log::error;
+use netlink_packet_route::AddressFamily;
+use netlink_packet_route::route::{RouteAddress, RouteAttribute, RouteMessage, RouteType, RouteVia};
+use regex::Regex;
+use rtnetlink::{Handle, IpVersion};
+
+use crate::config::config::ServerConfiguration;
+use crate::db::queries::{add_routes, delete_routes, get_routes};
+use crate::db::schema::Route;
+use super::diff::{apply_diff, diff_by_key, Diff};
+use super::info::get_filtered_interfaces_by_index;
+
+// linux/rtnetlink.h, the local table holds the addresses of the host, they are already in addr
+const RT_TABLE_LOCAL: u32 = 255;
+
+// A route is identified by its table, destination and metric, a next hop by its gateway and interface
+pub type RouteKey = (u32, Ipv6Addr, u8, u32, Ipv6Addr, String);
+
+pub fn route_key(route: &Route) -> RouteKey {
+    (route.table_id, route.destination, route.prefix, route.metric, route.gateway, route.interface.clone())
+}
+
+// IPv4 and IPv6 routes of every table but local, through the filtered interfaces
+pub async fn get_all_routes(handle: &Handle, netns: &str, rules: &[Option<Regex>], server_id: &str) -> Result<Vec<Route>, rtnetlink::Error> {
+    let names = get_filtered_interfaces_by_index(handle, rules).await?;
+
+    let mut routes = Vec::new();
+    for version in [IpVersion::V4, IpVersion::V6] {
+        let messages: Vec<RouteMessage> = handle.route().get(version).execute().try_collect().await?;
+        routes.extend(messages.iter().flat_map(|message| parse_route(message, server_id, netns, &names)));
+    }
+    Ok(routes)
+}
+
+// `names` only holds the filtered interfaces, next hops through other interfaces are left out
+pub fn parse_route(message: &RouteMessage, server_id: &str, netns: &str, names: &HashMap<u32, String>) -> Vec<Route> {
+    let header = &message.header;
+    if matches!(header.kind, RouteType::Local | RouteType::Broadcast | RouteType::Anycast | RouteType::Multicast) {
+        return Vec::new();
+    }
+
+    let mut route = Route {
+        server_id: server_id.to_string(),
+        netns: netns.to_string(),
+        table_id: header.table as u32,
+        destination: match header.address_family {
+            AddressFamily::Inet => Ipv4Addr::UNSPECIFIED.to_ipv6_mapped(),
+            _ => Ipv6Addr::UNSPECIFIED,
+        },
+        prefix: header.destination_prefix_length,
+        metric: 0,
+        gateway: Ipv6Addr::UNSPECIFIED,
+        interface: String::new(),
+        protocol: format!("{:?}", header.protocol).to_lowercase(),
+        scope: format!("{:?}", header.scope).to_lowercase(),
+        kind: format!("{:?}", header.kind).to_lowercase()
+    };
+
+    // Interface index and gateway of every next hop
+    let mut hops: Vec<(u32, Ipv6Addr)> = Vec::new();
+    let mut hop = (0, Ipv6Addr::UNSPECIFIED);
+
+    for attribute in &message.attributes {
+        match attribute {
+            // Tables above 255 only fit in the attribute
+            RouteAttribute::Table(table) => route.table_id = *table,
+            RouteAttribute::Priority(metric) => route.metric = *metric,
+            RouteAttribute::Destination(destination) => {
+                if let Some(destination) = route_address(destination) {
+                    route.destination = destination;
+                }
+            }
+            RouteAttribute::Oif(index) => hop.0 = *index,
+            RouteAttribute::Gateway(_) | RouteAttribute::Via(_) => {
+                if let Some(gateway) = route_gateway(attribute) {
+                    hop.1 = gateway;
+                }
+            }
+            RouteAttribute::MultiPath(next_hops) => {
+                hops.extend(next_hops.iter().map(|next_hop| {
+                    let gateway = next_hop.attributes.iter().find_map(route_gateway).unwrap_or(Ipv6Addr::UNSPECIFIED);
+                    (next_hop.interface_index, gateway)
+                }));
+            }
+            _ => ()
+        }
+    }
+
+    if route.table_id == RT_TABLE_LOCAL {
+        return Vec::new();
+    }
+    if hops.is_empty() {
+        hops.push(hop);
+    }
+
+    hops.into_iter()
+        .filter_map(|(index, gateway)| {
+            // Blackhole, unreachable and prohibit routes have no interface
+            let interface = match index {
+                0 => String::new(),
+                index => names.get(&index)?.clone(),
+            };
+            Some(Route { gateway, interface, ..route.clone() })
+        })
+        .collect()
+}
+
+fn route_address(address: &RouteAddress) -> Option<Ipv6Addr> {
+    match address {
+        RouteAddress::Inet(v4) => Some(v4.to_ipv6_mapped()),
+        RouteAddress::Inet6(v6) => Some(*v6),
+        _ => None
+    }
+}
+
+// IPv4 routes can have an IPv6 next hop (RFC 5549), the kernel sends it as RTA_VIA
+fn route_gateway(attribute: &RouteAttribute) -> Option<Ipv6Addr> {
+    match attribute {
+        RouteAttribute::Gateway(gateway) => route_address(gateway),
+        RouteAttribute::Via(RouteVia::Inet(v4)) => Some(v4.to_ipv6_mapped()),
+        RouteAttribute::Via(RouteVia::Inet6(v6)) => Some(*v6),
+        _ => None
+    }
+}
+
+// Compares the routes with the database, returns them to be used as the cache
+pub async fn resync_routes(handle: &Handle, netns: &str, client: &Client, server: &ServerConfiguration, rules: &[Option<Regex>]) -> Option<HashMap<RouteKey, Route>> {
+    let routes = match get_all_routes(handle, netns, rules, &server.get_config().server_id).await {
+        Ok(routes) => routes,
+        Err(e) => {
+            error!("Failed to get routes: {e}, skipping update cycle");
+            return None;
+        }
+    };
+
+    let db_routes = match get_routes(client, server.get_config(), netns).await {
+        Ok(routes) => routes,
+        Err(e) => {
+            error!("Failed to get routes from database: {e}, skipping update cycle");
+            return None;
+        }
+    };
+
+    apply_route_updates(client, compare_routes(&routes, &db_routes)).await;
+
+    Some(routes.into_iter().map(|route| (route_key(&route), route)).collect())
+}
+
+// Compares the routes with the cache, after route events
+pub async fn sync_routes(handle: &Handle, netns: &str, client: &Client, server: &ServerConfiguration, rules: &[Option<Regex>], known: &mut HashMap<RouteKey, Route>) {
+    let routes = match get_all_routes(handle, netns, rules, &server.get_config().server_id).await {
+        Ok(routes) => routes,
+        Err(e) => {
+            error!("Failed to get routes: {e}");
+            return;
+        }
+    };
+
+    let cached: Vec<Route> = known.values().cloned().collect();
+    apply_route_updates(client, compare_routes(&routes, &cached)).await;
+
+    *known = routes.into_iter().map(|route| (route_key(&route), route)).collect();
+}
+
+async fn apply_route_updates(client: &Client, diff: Diff<Route>) {
+    apply_diff(diff, "route(s)",
+        |deletes| async move { delete_routes(client, &deletes).await },
+        |creates| add_routes(client, creates)).await;
+}
+
+pub fn compare_routes(fresh: &[Route], db: &[Route]) -> Diff<Route> {
+    diff_by_key(fresh, db, route_key)
+}
@@ -1,28 +1,51 @@
-use log::{error, info};
-use clickhouse::Client;
+use log::{error, info, warn};
 use rtnetlink::{Error, Handle};
-use tokio::time::{interval, Duration};
+use tokio::time::{interval, MissedTickBehavior};
 use crate::config::config::ServerConfiguration;
-use crate::queries::add_stat;
-use std::{collections::HashMap, time::{SystemTime, UNIX_EPOCH}};
-use crate::db::schema::Stat;
-use super::info::{get_filtered_interfaces_names, get_interface_stats};
-use futures::stream::StreamExt;
+use std::{collections::{HashMap, HashSet}, time::{Duration, Instant}};
+use crate::db::schema::{Stat, StatReset};
+use crate::db::spool::{Spool, REPLAY_BATCHES_PER_TICK};
+use crate::db::store::Store;
+use crate::system::get_system_stats::HostStats;
+use crate::alert::engine::{AlertInput, AlertSender};
+use crate::export::LatestStats;
+use super::info::{get_all_interfaces, get_filtered_interfaces_names, get_interface_stats};
+use super::sample::{interval_ms, timestamp};
+use super::netns::NamespaceHandle;
+use futures::future::join_all;
+use netlink_packet_route::link::LinkMessage;
 use std::sync::Arc;
 
-pub async fn get_stats(handle: &Handle, name: &String, config: &ServerConfiguration) -> Option<Stat> {
-    let stats = get_interface_stats(handle, name).await.inspect_err(|err| error!("Failed to get stats: {}", err));
+// Previous sample by namespace and interface name
+pub type LastStats = Arc<tokio::sync::Mutex<HashMap<(String, String), Option<Stat>>>>;
 
-    let start = SystemTime::now();
-    let since_the_epoch = start.duration_since(UNIX_EPOCH).inspect_err(|err| error!("Failed to get timestamp: {}", err));
+// Sub-second byte rates of an interface since the last stats tick
+#[derive(Debug, Default)]
+pub struct BurstWindow {
+    // Instant, ifindex and byte counters of the previous sub-sample
+    previous: Option<(Instant, u32, u64, u64)>,
+    pub rx_rates: Vec<f64>,
+    pub tx_rates: Vec<f64>,
+}
+
+// Burst windows by namespace and interface name
+pub type BurstWindows = HashMap<(String, String), BurstWindow>;
 
-    if let (Ok(stat), Ok(timestamp)) = (stats, since_the_epoch) {
-        let server_id = &config.get_config().server_id.as_str();
+#[derive(Debug)]
+pub struct Samples {
+    pub stats: Vec<Stat>,
+    pub resets: Vec<StatReset>,
+}
 
+pub fn get_stats(link: &LinkMessage, server_id: &str, netns: &str, timestamp: Duration) -> Option<Stat> {
+    if let Some(stat) = get_interface_stats(link) {
         return Some(Stat {
             server_id: server_id.to_string(),
+            netns: netns.to_string(),
             interface: stat.int_name,
             timestamp: timestamp.as_secs() as u32,
+            timestamp_ms: timestamp.as_millis() as u64,
+            ifindex: stat.index,
             rx: stat.rx_bytes,
             tx: stat.tx_bytes,
             rx_p: stat.rx_packets,
@@ -30,109 +53,377 @@ pub async fn get_stats(handle: &Handle, name: &String, config: &ServerConfigurat
             rx_d: stat.rx_dropped,
             tx_d: stat.tx_dropped,
             rx_e: stat.rx_error,
-            tx_e: stat.tx_error
+            tx_e: stat.tx_error,
+            multicast: stat.multicast,
+            collisions: stat.collisions,
+            rx_length_e: stat.rx_length_errors,
+            rx_over_e: stat.rx_over_errors,
+            rx_crc_e: stat.rx_crc_errors,
+            rx_frame_e: stat.rx_frame_errors,
+            rx_fifo_e: stat.rx_fifo_errors,
+            rx_missed_e: stat.rx_missed_errors,
+            tx_aborted_e: stat.tx_aborted_errors,
+            tx_carrier_e: stat.tx_carrier_errors,
+            tx_fifo_e: stat.tx_fifo_errors,
+            tx_heartbeat_e: stat.tx_heartbeat_errors,
+            tx_window_e: stat.tx_window_errors,
+            rx_compressed: stat.rx_compressed,
+            tx_compressed: stat.tx_compressed,
+            rx_nohandler: stat.rx_nohandler,
+            ..Default::default()
         });
     }
     None
 }
 
-pub async fn save_stats_every_second(handle: &Handle, server_config: &ServerConfiguration, client: &Client) -> Result<(), Error> {
-    let stats_interval = Duration::from_secs(1);
-    let refresh_interval = Duration::from_secs(60);
+pub async fn save_stats_every_second(namespaces: &[NamespaceHandle], server_config: &ServerConfiguration, store: &dyn Store,
+    alerts: &AlertSender, latest_stats: &LatestStats) -> Result<(), Error> {
+    let stats_interval = server_config.get_collector().stats_interval;
+    let refresh_interval = server_config.get_collector().refresh_interval;
 
     let mut stats_timer = interval(stats_interval);
+    // Late ticks are skipped rather than bursted, the gap shows up in missed_ticks
+    stats_timer.set_missed_tick_behavior(MissedTickBehavior::Skip);
+    // Sub-second samples between the stats ticks, only kept as rates
+    let burst_interval = server_config.get_collector().burst_interval;
+    let mut burst_timer = interval(burst_interval.unwrap_or(stats_interval));
+    burst_timer.set_missed_tick_behavior(MissedTickBehavior::Skip);
+    let mut burst_windows = BurstWindows::new();
     let mut refresh_timer = interval(refresh_interval);
-    // Cache the interface names initially
-    let cached_interface_names = match get_filtered_interfaces_names(handle, &server_config.get_config().interface_filter).await {
-        Ok(names) => names,
-        Err(e) => {
-            error!("Failed to get initial interface names: {e}");
-            return Err(e);
+    let rules = &server_config.get_config().interface_filter;
+    // Cache the interface names initially, one set per namespace
+    let mut cached_interface_names: Vec<HashSet<String>> = Vec::with_capacity(namespaces.len());
+    for namespace in namespaces {
+        match get_filtered_interfaces_names(&namespace.handle, rules).await {
+            Ok(names) => cached_interface_names.push(names.into_iter().collect()),
+            Err(e) if namespace.netns.is_empty() => {
+                error!("Failed to get initial interface names: {e}");
+                return Err(e);
+            }
+            Err(e) => {
+                // Retried on the next refresh
+                error!("Failed to get initial interface names in the {} namespace: {e}", namespace.display_name());
+                cached_interface_names.push(HashSet::new());
+            }
         }
-    };
-    let mut cached_interface_names = cached_interface_names;
+    }
     // For concurrent updates
-    let last_stats = Arc::new(tokio::sync::Mutex::new(HashMap::<String, Option<Stat>>::new()));
+    let last_stats: LastStats = Arc::new(tokio::sync::Mutex::new(HashMap::new()));
+
+    let mut spool = server_config.get_collector().spool_path.as_ref().and_then(|path| {
+        Spool::new(path, server_config.get_collector().spool_max_bytes).inspect(|_| {
+            info!("Spooling stats to {} while the database is unreachable.", path.display());
+        }).inspect_err(|e| {
+            error!("Failed to open spool directory {}: {e}, spooling is disabled", path.display());
+        }).ok()
+    });
 
-    info!("Collecting and saving statistics every {} second(s).", stats_interval.as_secs());
-    info!("Refreshing interface list every {} second(s).", refresh_interval.as_secs());
+    // CPU, load, memory and softnet counters of the host, on the same tick
+    let mut host_stats = HostStats::new().await;
+
+    info!("Collecting and saving statistics every {} ms.", stats_interval.as_millis());
+    if let Some(burst_interval) = burst_interval {
+        info!("Sampling sub-second rates every {} ms.", burst_interval.as_millis());
+    }
+    info!("Refreshing interface list every {} ms.", refresh_interval.as_millis());
 
     loop {
         tokio::select! {
             _ = stats_timer.tick() => {
                 // Use the cached interface names for stats collection
-                if !cached_interface_names.is_empty() {
-                    let stats_result = filter_interfaces(handle, cached_interface_names.clone(), server_config).await;
-                    let maybe_stat = save_stat(Arc::clone(&last_stats), stats_result).await;
-                    if let Some(stat) = maybe_stat {
-                        add_stat(client, stat).await.inspect_err(|e| {
-                            error!("Failed to save stats: {e}");
+                let dumps = namespaces.iter().zip(&cached_interface_names)
+                    .filter(|(_, names)| !names.is_empty())
+                    .map(|(namespace, names)| filter_interfaces(&namespace.handle, &namespace.netns, names, server_config));
+                let stats_result: Vec<Stat> = join_all(dumps).await.into_iter().flatten().collect();
+
+                if !stats_result.is_empty() {
+                    latest_stats.update(&stats_result);
+                    // The tick closes the window of the sub-samples taken since the previous one
+                    if let Some(burst_interval) = burst_interval {
+                        add_burst_samples(&mut burst_windows, &stats_result, burst_interval);
+                    }
+                    let maybe_samples = save_stat(Arc::clone(&last_stats), stats_result, stats_interval).await;
+                    if let Some(mut samples) = maybe_samples {
+                        if burst_interval.is_some() {
+                            apply_burst_windows(&mut burst_windows, &mut samples.stats);
+                        }
+                        if alerts.is_enabled() {
+                            alerts.send(AlertInput::Stats(samples.stats.clone()));
+                        }
+                        store_stats(store, spool.as_mut(), samples.stats).await;
+
+                        if !samples.resets.is_empty() {
+                            store.add_stat_resets(samples.resets).await.inspect_err(|e| {
+                                error!("Failed to save counter resets: {e}");
+                            }).ok();
+                        }
+                    }
+                }
+
+                if let Some(host) = host_stats.as_mut() {
+                    if let Some(host_stat) = host.sample(&server_config.get_config().server_id).await {
+                        store.add_system_stats(vec![host_stat]).await.inspect_err(|e| {
+                            error!("Failed to save host metrics: {e}");
                         }).ok();
                     }
                 }
             },
+            _ = burst_timer.tick(), if burst_interval.is_some() => {
+                let dumps = namespaces.iter().zip(&cached_interface_names)
+                    .filter(|(_, names)| !names.is_empty())
+                    .map(|(namespace, names)| filter_interfaces(&namespace.handle, &namespace.netns, names, server_config));
+                let sub_samples: Vec<Stat> = join_all(dumps).await.into_iter().flatten().collect();
+                add_burst_samples(&mut burst_windows, &sub_samples, burst_interval.unwrap_or(stats_interval));
+            },
             _ = refresh_timer.tick() => {
                 // Refresh the cached interface names periodically
-                match get_filtered_interfaces_names(handle, &server_config.get_config().interface_filter).await {
-                    Ok(new_interfaces) => {
-                        if !new_interfaces.is_empty() {
-                            cached_interface_names = new_interfaces;
+                for (namespace, cached) in namespaces.iter().zip(cached_interface_names.iter_mut()) {
+                    match get_filtered_interfaces_names(&namespace.handle, rules).await {
+                        Ok(new_interfaces) => {
+                            if !new_interfaces.is_empty() {
+                                *cached = new_interfaces.into_iter().collect();
+                            }
+                        },
+                        Err(e) => {
+                            error!("Failed to refresh interface names in the {} namespace: {e}, continuing with existing names",
+                                namespace.display_name());
                         }
-                    },
-                    Err(e) => {
-                        error!("Failed to refresh interface names: {e}, continuing with existing names");
                     }
                 }
+                // Drop the windows of interfaces that are gone
+                burst_windows.retain(|(netns, interface), _| namespaces.iter().zip(&cached_interface_names)
+                    .any(|(namespace, names)| namespace.netns == *netns && names.contains(interface)));
             }
         }
     }
 }
 
-pub async fn filter_interfaces(handle: &Handle, filtered_interface_names: Vec<String>, config: &ServerConfiguration) -> Vec<Stat> {
-    let max_concurrent = 10;
-    let stats: Vec<Stat> = futures::stream::iter(filtered_interface_names)
-        .map(|name| {
-            async move {
-                get_stats(&handle, &name, &config).await
-            }
-        })
-        .buffer_unordered(max_concurrent)
-        .filter_map(|opt| async move { opt })
-        .collect()
-        .await;
+async fn store_stats(store: &dyn Store, spool: Option<&mut Spool>, stats: Vec<Stat>) {
+    if stats.is_empty() {
+        return;
+    }
+
+    let Some(spool) = spool else {
+        store.add_stats(stats).await.inspect_err(|e| {
+            error!("Failed to save stats: {e}");
+        }).ok();
+        return;
+    };
+
+    // Older batches are still spooled, queue behind them to keep the order
+    let pending = !spool.is_empty();
+    if !pending {
+        match store.add_stats(stats.clone()).await {
+            Ok(()) => return,
+            Err(e) => error!("Failed to save stats: {e}, spooling them to disk"),
+        }
+    }
 
-    stats
+    spool.push(&stats).await.inspect_err(|e| {
+        error!("Failed to spool stats: {e}");
+    }).ok();
+
+    if pending {
+        spool.replay(store, REPLAY_BATCHES_PER_TICK).await.inspect_err(|e| {
+            warn!("Failed to replay spooled stats: {e}");
+        }).ok();
+    }
 }
 
-pub async fn save_stat(last_stats: Arc<tokio::sync::Mutex<HashMap<String, Option<Stat>>>>, stats: Vec<Stat>) -> Option<Vec<Stat>> {
+pub async fn filter_interfaces(handle: &Handle, netns: &str, filtered_interface_names: &HashSet<String>, config: &ServerConfiguration) -> Vec<Stat> {
+    // A single dump per tick, every interface shares the same timestamp
+    let links = match get_all_interfaces(handle).await {
+        Ok(links) => links,
+        Err(err) => {
+            error!("Failed to get stats: {}", err);
+            return Vec::new();
+        }
+    };
+
+    let Some(timestamp) = timestamp() else {
+        return Vec::new();
+    };
+
+    let server_id = config.get_config().server_id.as_str();
+
+    let instant = Instant::now();
+
+    links.iter()
+        .filter_map(|link| get_stats(link, server_id, netns, timestamp))
+        .filter(|stat| filtered_interface_names.contains(&stat.interface))
+        .map(|stat| Stat { instant: Some(instant), ..stat })
+        .collect()
+}
+
+pub async fn save_stat(last_stats: LastStats, stats: Vec<Stat>, expected_interval: Duration) -> Option<Samples> {
     let mut final_stats: Vec<Stat> = Vec::new();
+    let mut resets: Vec<StatReset> = Vec::new();
 
     for curr_stat in stats {
         let server_id = curr_stat.server_id.as_str();
         let interface = curr_stat.interface.as_str();
+        // The same interface name can exist in several namespaces
+        let key = (curr_stat.netns.clone(), curr_stat.interface.clone());
 
         {
             // Lock the last_stats map to read/update the previous data.
             let mut last_data = last_stats.lock().await;
-            if let Some(Some(old_data)) = last_data.get(interface) {
-
-                final_stats.push( Stat {
-                    server_id: server_id.into(),
-                    interface: interface.into(),
-                    timestamp: curr_stat.timestamp,
-                    tx_p: (curr_stat.tx_p - old_data.tx_p),
-                    rx_p: (curr_stat.rx_p - old_data.rx_p),
-                    tx: (curr_stat.tx - old_data.tx),
-                    rx: (curr_stat.rx - old_data.rx),
-                    tx_d: (curr_stat.tx_d - old_data.tx_d),
-                    rx_d: (curr_stat.rx_d - old_data.rx_d),
-                    tx_e: (curr_stat.tx_e - old_data.tx_e),
-                    rx_e: (curr_stat.rx_e - old_data.rx_e),
-                });
+            if let Some(Some(old_data)) = last_data.get(&key) {
+
+                // The interface was recreated with the same name, or a counter was reset:
+                // skip the sample and use the current one as the new baseline.
+                let reason = if curr_stat.ifindex != old_data.ifindex {
+                    Some("ifindex")
+                } else if let Some(stat) = stat_delta(&curr_stat, old_data, expected_interval) {
+                    final_stats.push(stat);
+                    None
+                } else {
+                    Some("counter")
+                };
+
+                if let Some(reason) = reason {
+                    warn!("Counters of {interface} were reset ({reason}), skipping sample");
+                    resets.push(StatReset {
+                        server_id: server_id.into(),
+                        netns: curr_stat.netns.clone(),
+                        interface: interface.into(),
+                        timestamp: curr_stat.timestamp,
+                        reason: reason.into()
+                    });
+                }
             }
             // Update the last_stats for this interface.
-            last_data.insert(interface.into(), Some(curr_stat));
+            last_data.insert(key, Some(curr_stat));
+        }
+    }
+    Some(Samples { stats: final_stats, resets })
+}
+
+pub fn stat_delta(curr_stat: &Stat, old_data: &Stat, expected_interval: Duration) -> Option<Stat> {
+    let interval_ms = interval_ms(curr_stat, old_data);
+    let expected_ms = (expected_interval.as_millis() as u64).max(1);
+    let missed_ticks = ((interval_ms + expected_ms / 2) / expected_ms).saturating_sub(1) as u32;
+
+    let mut stat = Stat {
+        server_id: curr_stat.server_id.clone(),
+        netns: curr_stat.netns.clone(),
+        interface: curr_stat.interface.clone(),
+        timestamp: curr_stat.timestamp,
+        timestamp_ms: curr_stat.timestamp_ms,
+        ifindex: curr_stat.ifindex,
+        instant: curr_stat.instant,
+        interval_ms,
+        missed_ticks,
+        tx_p: counter_delta(curr_stat.tx_p, old_data.tx_p)?,
+        rx_p: counter_delta(curr_stat.rx_p, old_data.rx_p)?,
+        tx: counter_delta(curr_stat.tx, old_data.tx)?,
+        rx: counter_delta(curr_stat.rx, old_data.rx)?,
+        tx_d: counter_delta(curr_stat.tx_d, old_data.tx_d)?,
+        rx_d: counter_delta(curr_stat.rx_d, old_data.rx_d)?,
+        tx_e: counter_delta(curr_stat.tx_e, old_data.tx_e)?,
+        rx_e: counter_delta(curr_stat.rx_e, old_data.rx_e)?,
+        multicast: counter_delta(curr_stat.multicast, old_data.multicast)?,
+        collisions: counter_delta(curr_stat.collisions, old_data.collisions)?,
+        rx_length_e: counter_delta(curr_stat.rx_length_e, old_data.rx_length_e)?,
+        rx_over_e: counter_delta(curr_stat.rx_over_e, old_data.rx_over_e)?,
+        rx_crc_e: counter_delta(curr_stat.rx_crc_e, old_data.rx_crc_e)?,
+        rx_frame_e: counter_delta(curr_stat.rx_frame_e, old_data.rx_frame_e)?,
+        rx_fifo_e: counter_delta(curr_stat.rx_fifo_e, old_data.rx_fifo_e)?,
+        rx_missed_e: counter_delta(curr_stat.rx_missed_e, old_data.rx_missed_e)?,
+        tx_aborted_e: counter_delta(curr_stat.tx_aborted_e, old_data.tx_aborted_e)?,
+        tx_carrier_e: counter_delta(curr_stat.tx_carrier_e, old_data.tx_carrier_e)?,
+        tx_fifo_e: counter_delta(curr_stat.tx_fifo_e, old_data.tx_fifo_e)?,
+        tx_heartbeat_e: counter_delta(curr_stat.tx_heartbeat_e, old_data.tx_heartbeat_e)?,
+        tx_window_e: counter_delta(curr_stat.tx_window_e, old_data.tx_window_e)?,
+        rx_compressed: counter_delta(curr_stat.rx_compressed, old_data.rx_compressed)?,
+        tx_compressed: counter_delta(curr_stat.tx_compressed, old_data.tx_compressed)?,
+        rx_nohandler: counter_delta(curr_stat.rx_nohandler, old_data.rx_nohandler)?,
+        ..Default::default()
+    };
+
+    stat.rx_rate = rate(stat.rx, interval_ms);
+    stat.tx_rate = rate(stat.tx, interval_ms);
+    stat.rx_p_rate = rate(stat.rx_p, interval_ms);
+    stat.tx_p_rate = rate(stat.tx_p, interval_ms);
+    stat.rx_d_rate = rate(stat.rx_d, interval_ms);
+    stat.tx_d_rate = rate(stat.tx_d, interval_ms);
+    stat.rx_e_rate = rate(stat.rx_e, interval_ms);
+    stat.tx_e_rate = rate(stat.tx_e, interval_ms);
+    Some(stat)
+}
+
+// Adds the byte rates since the previous sub-sample of every interface. A recreated interface
+// or a counter reset only loses that rate.
+pub fn add_burst_samples(windows: &mut BurstWindows, stats: &[Stat], burst_interval: Duration) {
+    for stat in stats {
+        let Some(instant) = stat.instant else {
+            continue;
+        };
+        let window = windows.entry((stat.netns.clone(), stat.interface.clone())).or_default();
+
+        if let Some((previous, ifindex, rx, tx)) = window.previous {
+            let elapsed = instant.saturating_duration_since(previous);
+            // The stats tick and a burst tick can land together, a rate over a few
+            // microseconds would be noise. Keep the older baseline instead.
+            if elapsed < burst_interval / 2 {
+                continue;
+            }
+            if ifindex == stat.ifindex {
+                if let (Some(rx_delta), Some(tx_delta)) = (counter_delta(stat.rx, rx), counter_delta(stat.tx, tx)) {
+                    window.rx_rates.push(rx_delta as f64 / elapsed.as_secs_f64());
+                    window.tx_rates.push(tx_delta as f64 / elapsed.as_secs_f64());
+                }
+            }
         }
+        window.previous = Some((instant, stat.ifindex, stat.rx, stat.tx));
+    }
+}
+
+// Fills the sub-second columns of the stats and starts new windows
+pub fn apply_burst_windows(windows: &mut BurstWindows, stats: &mut [Stat]) {
+    for stat in stats {
+        let Some(window) = windows.get_mut(&(stat.netns.clone(), stat.interface.clone())) else {
+            continue;
+        };
+        (stat.rx_rate_min, stat.rx_rate_max, stat.rx_rate_p99) = rate_summary(&mut window.rx_rates);
+        (stat.tx_rate_min, stat.tx_rate_max, stat.tx_rate_p99) = rate_summary(&mut window.tx_rates);
+        window.rx_rates.clear();
+        window.tx_rates.clear();
+    }
+}
+
+// Minimum, maximum and 99th percentile (nearest rank)
+pub fn rate_summary(rates: &mut [f64]) -> (f64, f64, f64) {
+    if rates.is_empty() {
+        return (0.0, 0.0, 0.0);
+    }
+    rates.sort_by(f64::total_cmp);
+    let rank = ((rates.len() as f64 * 0.99).ceil() as usize).clamp(1, rates.len());
+    (rates[0], rates[rates.len() - 1], rates[rank - 1])
+}
+
+pub fn rate(delta: u64, interval_ms: u64) -> f64 {
+    if interval_ms == 0 {
+        return 0.0;
     }
-    Some(final_stats)
+    delta as f64 * 1000.0 / interval_ms as f64
+}
+
+// Stats64 counters don't wrap in practice, any decrease is a reset
+pub fn counter_delta(curr: u64, old: u64) -> Option<u64> {
+    curr.checked_sub(old)
+}
+
+// For sources known to be 32-bit (tc packets and drops, softnet), which wrap at u32::MAX.
+// A drop from the top quarter of the range into the bottom quarter is a wraparound, anything else a reset.
+pub fn counter_delta32(curr: u64, old: u64) -> Option<u64> {
+    if curr >= old {
+        return Some(curr - old);
+    }
+
+    let max = u32::MAX as u64;
+    if old <= max && old > max - max / 4 && curr < max / 4 {
+        return Some(max - old + curr + 1);
+    }
+    None
 }
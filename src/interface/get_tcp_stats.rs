@@ -0,0 +1,164 @@
+use std::collections::{HashMap, HashSet};
+use std::net::{IpAddr, Ipv6Addr};
+use std::sync::Arc;
+use clickhouse::Client;
+use log::{error, info};
+use netlink_sys::Socket;
+use tokio::time::{interval, MissedTickBehavior};
+
+use crate::config::config::ServerConfiguration;
+use crate::db::queries::{add_tcp_stats, get_peer_addr};
+use crate::db::schema::TcpStat;
+use super::netns::NamespaceHandle;
+use super::sample::timestamp;
+use super::remote::{get_local_addresses, peer_addresses, remote_network};
+use super::sock_diag::{dump_tcp_connections, open_socket, TcpConnection};
+
+pub async fn save_tcp_stats_every_interval(namespaces: &[NamespaceHandle], server_config: &ServerConfiguration, client: &Client) {
+    let collector = server_config.get_collector();
+    let Some(tcp_interval) = collector.tcp_interval else {
+        return;
+    };
+
+    let sources: Vec<(&NamespaceHandle, Arc<Socket>)> = namespaces.iter().filter_map(|namespace| {
+        open_socket(namespace.path.as_deref()).inspect_err(|e| {
+            error!("Failed to open sock_diag socket in the {} namespace: {e}", namespace.display_name());
+        }).ok().map(|socket| (namespace, Arc::new(socket)))
+    }).collect();
+
+    if sources.is_empty() {
+        error!("No sock_diag socket could be opened, TCP metrics are not collected");
+        return;
+    }
+
+    let mut tcp_timer = interval(tcp_interval);
+    tcp_timer.set_missed_tick_behavior(MissedTickBehavior::Skip);
+    let mut refresh_timer = interval(collector.refresh_interval);
+    let mut peers: HashMap<Ipv6Addr, String> = HashMap::new();
+
+    info!("Collecting TCP connection metrics every {} ms.", tcp_interval.as_millis());
+
+    loop {
+        tokio::select! {
+            _ = tcp_timer.tick() => {
+                let mut tcp_stats = Vec::new();
+
+                for (namespace, socket) in &sources {
+                    let socket = Arc::clone(socket);
+                    // Busy servers have many sockets, keep the dump off the async workers
+                    let connections = match tokio::task::spawn_blocking(move || dump_tcp_connections(&socket)).await {
+                        Ok(Ok(connections)) => connections,
+                        Ok(Err(e)) => {
+                            error!("Failed to dump TCP sockets in the {} namespace: {e}", namespace.display_name());
+                            continue;
+                        }
+                        Err(e) => {
+                            error!("sock_diag dump task failed: {e}");
+                            continue;
+                        }
+                    };
+
+                    let local = match get_local_addresses(&namespace.handle).await {
+                        Ok(local) => local,
+                        Err(e) => {
+                            error!("Failed to get local addresses in the {} namespace: {e}", namespace.display_name());
+                            continue;
+                        }
+                    };
+
+                    let Some(template) = tcp_template(server_config, &namespace.netns) else {
+                        continue;
+                    };
+                    tcp_stats.extend(summarize_connections(&connections, &local, &peers,
+                        collector.flow_prefix_v4, collector.flow_prefix_v6, &template));
+                }
+
+                if !tcp_stats.is_empty() {
+                    add_tcp_stats(client, tcp_stats).await.inspect_err(|e| {
+                        error!("Failed to save TCP stats: {e}");
+                    }).ok();
+                }
+            },
+            _ = refresh_timer.tick() => {
+                match get_peer_addr(client, server_config.get_config()).await {
+                    Ok(addrs) => peers = peer_addresses(&addrs),
+                    Err(e) => error!("Failed to get peer addresses: {e}, continuing with existing peers"),
+                }
+            }
+        }
+    }
+}
+
+// Same timestamp scheme as the interface stats
+fn tcp_template(server_config: &ServerConfiguration, netns: &str) -> Option<TcpStat> {
+    let timestamp = timestamp()?;
+
+    Some(TcpStat {
+        server_id: server_config.get_config().server_id.clone(),
+        netns: netns.to_string(),
+        timestamp: timestamp.as_secs() as u32,
+        timestamp_ms: timestamp.as_millis() as u64,
+        remote: Ipv6Addr::UNSPECIFIED,
+        remote_prefix: 0,
+        peer_server_id: None,
+        connections: 0,
+        rtt_avg_us: 0.0,
+        rtt_min_us: 0,
+        rtt_max_us: 0,
+        rttvar_avg_us: 0.0,
+        cwnd_avg: 0.0,
+        cwnd_min: 0,
+        retrans: 0,
+        lost: 0,
+        total_retrans: 0
+    })
+}
+
+// Summarizes connections by remote network, connections between local addresses are skipped
+pub fn summarize_connections(connections: &[TcpConnection], local: &HashSet<IpAddr>, peers: &HashMap<Ipv6Addr, String>,
+    prefix_v4: u8, prefix_v6: u8, template: &TcpStat) -> Vec<TcpStat> {
+
+    let mut summaries: HashMap<(Ipv6Addr, u8), TcpStat> = HashMap::new();
+
+    for connection in connections {
+        let Some(remote) = connection.dst.filter(|dst| !local.contains(dst) && !dst.is_loopback()) else {
+            continue;
+        };
+
+        // Peers are kept by address, other remotes are aggregated to their network
+        let peer = peers.get(&remote_network(remote, 32, 128).0);
+        let (network, prefix) = match peer {
+            Some(_) => remote_network(remote, 32, 128),
+            None => remote_network(remote, prefix_v4, prefix_v6),
+        };
+
+        let summary = summaries.entry((network, prefix)).or_insert_with(|| TcpStat {
+            remote: network,
+            remote_prefix: prefix,
+            peer_server_id: peer.cloned(),
+            rtt_min_us: u32::MAX,
+            cwnd_min: u32::MAX,
+            ..template.clone()
+        });
+
+        summary.connections += 1;
+        // Sums until all connections are counted
+        summary.rtt_avg_us += connection.rtt_us as f64;
+        summary.rttvar_avg_us += connection.rttvar_us as f64;
+        summary.cwnd_avg += connection.cwnd as f64;
+        summary.rtt_min_us = summary.rtt_min_us.min(connection.rtt_us);
+        summary.rtt_max_us = summary.rtt_max_us.max(connection.rtt_us);
+        summary.cwnd_min = summary.cwnd_min.min(connection.cwnd);
+        summary.retrans += connection.retrans;
+        summary.lost += connection.lost;
+        summary.total_retrans += connection.total_retrans as u64;
+    }
+
+    summaries.into_values().map(|mut summary| {
+        let connections = summary.connections as f64;
+        summary.rtt_avg_us /= connections;
+        summary.rttvar_avg_us /= connections;
+        summary.cwnd_avg /= connections;
+        summary
+    }).collect()
+}
@@ -0,0 +1,217 @@
+use std::collections::{HashMap, HashSet};
+use std::net::Ipv6Addr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use clickhouse::Client;
+use log::{error, info, warn};
+use netlink_packet_route::link::{InfoKind, LinkAttribute, LinkInfo, LinkMessage};
+use netlink_sys::Socket;
+use regex::Regex;
+use tokio::time::{interval, MissedTickBehavior};
+
+use crate::config::config::ServerConfiguration;
+use crate::db::queries::{add_wg_peer_stats, get_peer_addr};
+use crate::db::schema::WgPeerStat;
+use super::info::{compile_rules, get_all_interfaces, get_interface_name_from_attribute, is_interface_matching};
+use super::netns::NamespaceHandle;
+use super::remote::{peer_addresses, to_ipv6};
+use super::sample::{interval_ms, timestamp, Baselines};
+use super::wireguard::{dump_peers, encode_key, open_socket, resolve_family, WgPeer};
+
+// Previous sample by namespace, interface and public key
+type WgKey = (String, String, String);
+
+// Generic netlink socket of one namespace, the family id is known once the module is loaded
+struct WgSource {
+    namespace: NamespaceHandle,
+    socket: Arc<Socket>,
+    family: Option<u16>,
+}
+
+pub async fn save_wg_stats_every_interval(namespaces: &[NamespaceHandle], server_config: &ServerConfiguration, client: &Client) {
+    let collector = server_config.get_collector();
+    let Some(wg_interval) = collector.wg_interval else {
+        return;
+    };
+
+    let mut sources: Vec<WgSource> = namespaces.iter().filter_map(|namespace| {
+        open_socket(namespace.path.as_deref()).inspect_err(|e| {
+            error!("Failed to open generic netlink socket in the {} namespace: {e}", namespace.display_name());
+        }).ok().map(|socket| WgSource { namespace: namespace.clone(), socket: Arc::new(socket), family: None })
+    }).collect();
+
+    if sources.is_empty() {
+        error!("No generic netlink socket could be opened, WireGuard peers are not collected");
+        return;
+    }
+
+    let mut wg_timer = interval(wg_interval);
+    wg_timer.set_missed_tick_behavior(MissedTickBehavior::Skip);
+    let mut refresh_timer = interval(collector.refresh_interval);
+    let rules = compile_rules(&server_config.get_config().interface_filter);
+    let mut peers: HashMap<Ipv6Addr, String> = HashMap::new();
+    let mut last_stats = Baselines::default();
+
+    info!("Collecting WireGuard peer statistics every {} ms.", wg_interval.as_millis());
+
+    loop {
+        tokio::select! {
+            _ = wg_timer.tick() => {
+                let mut samples = Vec::new();
+                for source in &mut sources {
+                    samples.extend(get_wg_peer_stats(source, server_config, &rules, &peers).await);
+                }
+
+                let stats = save_wg_stat(&mut last_stats, samples);
+                if !stats.is_empty() {
+                    add_wg_peer_stats(client, stats).await.inspect_err(|e| {
+                        error!("Failed to save WireGuard peer stats: {e}");
+                    }).ok();
+                }
+            },
+            _ = refresh_timer.tick() => {
+                match get_peer_addr(client, server_config.get_config()).await {
+                    Ok(addrs) => peers = peer_addresses(&addrs),
+                    Err(e) => error!("Failed to get peer addresses: {e}, continuing with existing peers"),
+                }
+            }
+        }
+    }
+}
+
+async fn get_wg_peer_stats(source: &mut WgSource, server_config: &ServerConfiguration, rules: &[Option<Regex>],
+    peers: &HashMap<Ipv6Addr, String>) -> Vec<WgPeerStat> {
+    let name = source.namespace.display_name();
+
+    let devices = match get_all_interfaces(&source.namespace.handle).await {
+        Ok(links) => wireguard_interfaces(&links, rules),
+        Err(e) => {
+            error!("Failed to get interfaces in the {name} namespace: {e}");
+            return Vec::new();
+        }
+    };
+    if devices.is_empty() {
+        return Vec::new();
+    }
+
+    // Without a WireGuard interface the module may not be loaded yet
+    let family = match source.family {
+        Some(family) => family,
+        None => {
+            let socket = Arc::clone(&source.socket);
+            match tokio::task::spawn_blocking(move || resolve_family(&socket)).await {
+                Ok(Ok(Some(family))) => *source.family.insert(family),
+                Ok(Ok(None)) => {
+                    warn!("WireGuard interfaces exist in the {name} namespace but the generic netlink family is missing");
+                    return Vec::new();
+                }
+                Ok(Err(e)) => {
+                    error!("Failed to resolve the WireGuard family in the {name} namespace: {e}");
+                    return Vec::new();
+                }
+                Err(e) => {
+                    error!("Generic netlink task failed: {e}");
+                    return Vec::new();
+                }
+            }
+        }
+    };
+
+    let Some(timestamp) = timestamp() else {
+        return Vec::new();
+    };
+    let instant = Instant::now();
+    let mut stats = Vec::new();
+
+    for (index, interface) in devices {
+        let socket = Arc::clone(&source.socket);
+        let wg_peers = match tokio::task::spawn_blocking(move || dump_peers(&socket, family, index)).await {
+            Ok(Ok(wg_peers)) => wg_peers,
+            Ok(Err(e)) => {
+                error!("Failed to dump WireGuard peers of {interface} in the {name} namespace: {e}");
+                // The id changes when the module is reloaded
+                source.family = None;
+                continue;
+            }
+            Err(e) => {
+                error!("Generic netlink task failed: {e}");
+                continue;
+            }
+        };
+
+        let template = WgPeerStat {
+            server_id: server_config.get_config().server_id.clone(),
+            netns: source.namespace.netns.clone(),
+            interface,
+            timestamp: timestamp.as_secs() as u32,
+            timestamp_ms: timestamp.as_millis() as u64,
+            instant: Some(instant),
+            ..Default::default()
+        };
+        stats.extend(wg_peers.iter().map(|peer| wg_peer_stat(peer, &template, peers)));
+    }
+    stats
+}
+
+// Index and name of the filtered WireGuard interfaces
+pub fn wireguard_interfaces(links: &[LinkMessage], rules: &[Option<Regex>]) -> Vec<(u32, String)> {
+    links.iter()
+        .filter(|link| link.attributes.iter().any(|attribute| matches!(attribute,
+            LinkAttribute::LinkInfo(infos) if infos.contains(&LinkInfo::Kind(InfoKind::Wireguard)))))
+        .filter_map(|link| {
+            get_interface_name_from_attribute(link.attributes.clone())
+                .filter(|name| is_interface_matching(name, false, rules))
+                .map(|name| (link.header.index, name))
+        })
+        .collect()
+}
+
+// Counters are totals until save_wg_stat turns them into deltas
+pub fn wg_peer_stat(peer: &WgPeer, template: &WgPeerStat, peers: &HashMap<Ipv6Addr, String>) -> WgPeerStat {
+    let endpoint = peer.endpoint.map(|endpoint| to_ipv6(endpoint.ip()));
+    let now = Duration::from_millis(template.timestamp_ms);
+
+    WgPeerStat {
+        public_key: encode_key(&peer.public_key),
+        endpoint,
+        endpoint_port: peer.endpoint.map(|endpoint| endpoint.port()).unwrap_or(0),
+        allowed_ips: peer.allowed_ips.iter().map(|(ip, cidr)| (to_ipv6(*ip), *cidr)).collect(),
+        peer_server_id: endpoint.and_then(|endpoint| peers.get(&endpoint).cloned()),
+        handshake_age_s: peer.last_handshake.map(|handshake| now.saturating_sub(handshake).as_secs() as u32),
+        rx: peer.rx_bytes,
+        tx: peer.tx_bytes,
+        ..template.clone()
+    }
+}
+
+// Peers are tracked by public key, one removed and added again skips a sample
+pub fn save_wg_stat(last_stats: &mut Baselines<WgKey, WgPeerStat>, stats: Vec<WgPeerStat>) -> Vec<WgPeerStat> {
+    let mut seen = HashSet::new();
+
+    let final_stats = stats.into_iter().filter_map(|curr_stat| {
+        let key = (curr_stat.netns.clone(), curr_stat.interface.clone(), curr_stat.public_key.clone());
+        seen.insert(key.clone());
+
+        last_stats.delta(key, curr_stat, |curr_stat, old_data| {
+            let stat = wg_delta(curr_stat, old_data);
+            if stat.is_none() {
+                warn!("Counters of WireGuard peer {} on {} were reset, skipping sample", curr_stat.public_key, curr_stat.interface);
+            }
+            stat
+        })
+    }).collect();
+
+    // Peers and interfaces that were removed since
+    last_stats.retain(|key| seen.contains(key));
+    final_stats
+}
+
+// WireGuard counters are 64-bit, a decrease means the peer was removed and added again
+pub fn wg_delta(curr_stat: &WgPeerStat, old_data: &WgPeerStat) -> Option<WgPeerStat> {
+    Some(WgPeerStat {
+        interval_ms: interval_ms(curr_stat, old_data),
+        rx: curr_stat.rx.checked_sub(old_data.rx)?,
+        tx: curr_stat.tx.checked_sub(old_data.tx)?,
+        ..curr_stat.clone()
+    })
+}
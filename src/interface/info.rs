@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use std::net::{IpAddr, Ipv6Addr};
 use regex::Regex;
 use log::error;
@@ -17,46 +18,61 @@ pub async fn get_all_interfaces(handle: &Handle) -> Result<Vec<LinkMessage>, rtn
     Ok(response_link)
 }
 
+pub fn compile_rules(rules: &[Option<String>]) -> Vec<Option<Regex>> {
+    rules.iter()
+        .map(|rule_opt| rule_opt.as_ref().and_then(|pattern| Regex::new(pattern).ok()))
+        .collect()
+}
+
+pub fn is_interface_matching(name: &str, is_loopback: bool, compiled_rules: &[Option<Regex>]) -> bool {
+    if is_loopback {
+        return false;
+    }
+    // If rules are empty, include all non-loopback interfaces
+    compiled_rules.is_empty() || compiled_rules.iter().any(|opt_regex| {
+        if let Some(regex) = opt_regex {
+            regex.is_match(name)
+        } else {
+            // If rule is None, allow non-loopback interfaces.
+            true
+        }
+    })
+}
+
 pub async fn get_filtered_interfaces_names(handle: &Handle, rules: &[Option<String>]) -> Result<Vec<String>, rtnetlinkErr> {
-    let compiled_rules: Vec<Option<Regex>> = rules
-            .iter()
-            .map(|rule_opt| rule_opt.as_ref().and_then(|pattern| Regex::new(pattern).ok()))
-            .collect();
+    let compiled_rules = compile_rules(rules);
 
-    let all_interfaces = get_all_interfaces(&handle).await?;
+    let all_interfaces = get_all_interfaces(handle).await?;
     let mut matching_interface_names: Vec<String> = Vec::new();
-    let only_none_or_empty = rules.is_empty() || rules.iter().all(|rule| rule.is_none());
 
     for interface in all_interfaces {
         let int_name = get_interface_name_from_attribute(interface.attributes);
         let is_loopback = get_loopback_from_header(interface.header);
 
         if let Some(name) = int_name {
-            // If rules are empty or [None], include all non-loopback interfaces
-            if only_none_or_empty {
-                if !is_loopback {
-                    matching_interface_names.push(name);
-                }
-            } else {
-                let interface_name_match = compiled_rules.iter().any(|opt_regex| {
-                    if let Some(regex) = opt_regex {
-                        regex.is_match(&name)
-                    } else {
-                        // If rule is None, allow non-loopback interfaces.
-                        !is_loopback
-                    }
-                });
-                if interface_name_match && !is_loopback {
-                    matching_interface_names.push(name);
-                }
+            if is_interface_matching(&name, is_loopback, &compiled_rules) {
+                matching_interface_names.push(name);
             }
         }
     }
     Ok(matching_interface_names)
 }
 
-pub async fn get_interface_status(handle: &Handle, name: &String) -> Result<bool, rtnetlinkErr> {
-    let response_link = get_interface(&handle, name).await?;
+// Names of the filtered interfaces by index
+pub async fn get_filtered_interfaces_by_index(handle: &Handle, compiled_rules: &[Option<Regex>]) -> Result<HashMap<u32, String>, rtnetlinkErr> {
+    Ok(get_all_interfaces(handle).await?.into_iter()
+        .filter_map(|link| {
+            let is_loopback = get_loopback_from_header(link.header.clone());
+            get_interface_name_from_attribute(link.attributes)
+                .filter(|name| is_interface_matching(name, is_loopback, compiled_rules))
+                .map(|name| (link.header.index, name))
+        })
+        .collect())
+}
+
+#[allow(dead_code)]
+pub async fn get_interface_status(handle: &Handle, name: &str) -> Result<bool, rtnetlinkErr> {
+    let response_link = get_interface(handle, name).await?;
     for header in response_link.header.flags.iter() {
         if let LinkFlag::Up = header {
             return Ok(true);
@@ -65,16 +81,16 @@ pub async fn get_interface_status(handle: &Handle, name: &String) -> Result<bool
     Ok(false)
 }
 
-pub async fn get_interface_stats(handle: &Handle, name: &String) -> Result<interface::Stats, rtnetlinkErr> {
-    let response_link = get_interface(&handle, name).await?;
+pub fn get_interface_stats(link: &LinkMessage) -> Option<interface::Stats> {
     let mut int_name = String::from("");
 
-    for attribute in response_link.attributes.iter() {
+    for attribute in link.attributes.iter() {
         if let LinkAttribute::IfName(interface) = attribute { int_name = interface.to_string(); }
 
         if let LinkAttribute::Stats64(status) = attribute {
-            return Ok(interface::Stats {
+            return Some(interface::Stats {
                 int_name,
+                index: link.header.index,
                 tx_packets: status.tx_packets,
                 rx_packets: status.rx_packets,
                 tx_bytes: status.tx_bytes,
@@ -82,24 +98,49 @@ pub async fn get_interface_stats(handle: &Handle, name: &String) -> Result<inter
                 tx_dropped: status.tx_dropped,
                 rx_dropped: status.rx_dropped,
                 tx_error: status.tx_errors,
-                rx_error: status.rx_errors
+                rx_error: status.rx_errors,
+                multicast: status.multicast,
+                collisions: status.collisions,
+                rx_length_errors: status.rx_length_errors,
+                rx_over_errors: status.rx_over_errors,
+                rx_crc_errors: status.rx_crc_errors,
+                rx_frame_errors: status.rx_frame_errors,
+                rx_fifo_errors: status.rx_fifo_errors,
+                rx_missed_errors: status.rx_missed_errors,
+                tx_aborted_errors: status.tx_aborted_errors,
+                tx_carrier_errors: status.tx_carrier_errors,
+                tx_fifo_errors: status.tx_fifo_errors,
+                tx_heartbeat_errors: status.tx_heartbeat_errors,
+                tx_window_errors: status.tx_window_errors,
+                rx_compressed: status.rx_compressed,
+                tx_compressed: status.tx_compressed,
+                rx_nohandler: status.rx_nohandler
             });
         }
     }
+    None
+}
+
+pub async fn get_interface(handle: &Handle, name: &str) -> Result<LinkMessage, rtnetlinkErr> {
+    let link_handle = handle.link().get();
+    let get_link = link_handle.match_name(name.to_string());
+    let response_link = get_link.execute().try_next().await?;
+
+    if let Some(link) = response_link { return Ok(link); }
     Err(rtnetlinkErr::RequestFailed)
 }
 
-pub async fn get_interface(handle: &Handle, name: &String) -> Result<LinkMessage, rtnetlinkErr> {
+pub async fn get_interface_by_index(handle: &Handle, index: u32) -> Result<LinkMessage, rtnetlinkErr> {
     let link_handle = handle.link().get();
-    let get_link = link_handle.match_name(name.clone());
+    let get_link = link_handle.match_index(index);
     let response_link = get_link.execute().try_next().await?;
 
     if let Some(link) = response_link { return Ok(link); }
     Err(rtnetlinkErr::RequestFailed)
 }
 
-pub async fn get_index_by_name(handle: &Handle, name: &String) -> Result<u32, rtnetlinkErr> {
-    let response_link = get_interface(&handle, name).await?;
+pub async fn get_index_by_name(handle: &Handle, name: &str) -> Result<u32, rtnetlinkErr> {
+    let response_link = get_interface(handle, name).await?;
 
     Ok(response_link.header.index)
 }
@@ -112,10 +153,7 @@ pub fn get_interface_name_from_attribute(attr: Vec<LinkAttribute>) -> Option<Str
 }
 
 pub fn get_loopback_from_header(header: LinkHeader) -> bool {
-    return header.flags.into_iter().any(|e| match e {
-        LinkFlag::Loopback => true,
-        _ => false
-    });
+    header.flags.into_iter().any(|e| matches!(e, LinkFlag::Loopback))
 }
 
 pub async fn get_interface_address(handle: &Handle, name: &String) -> Result<Vec<interface::InterfaceAddr>, rtnetlinkErr> {
@@ -139,7 +177,7 @@ pub async fn get_interface_address(handle: &Handle, name: &String) -> Result<Vec
         let mut address_mapped: Option<Ipv6Addr> = None;
         let mut local_mapped: Option<Ipv6Addr> = None;
 
-        address_attributes.into_iter().for_each(|e| {
+        address_attributes.iter().for_each(|e| {
             match e {
                 Address(addr_peer) => address = Some(*addr_peer),
                 Local(addr_local) => local = Some(*addr_local),
@@ -161,7 +199,7 @@ pub async fn get_interface_address(handle: &Handle, name: &String) -> Result<Vec
 
         // Skip link-local addresses with /64 mask
         let is_link_local = address_mapped.as_ref()
-            .map(|addr| is_ipv6_link_local(addr))
+            .map(is_ipv6_link_local)
             .unwrap_or(false);
 
         if is_link_local && address_message.header.prefix_len == 64 {
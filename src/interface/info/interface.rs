@@ -9,6 +9,7 @@ pub struct InterfaceAddr {
 #[derive(Debug)]
 pub struct Stats {
     pub int_name: String,
+    pub index: u32,
     pub tx_packets: u64,
     pub rx_packets: u64,
     pub tx_bytes: u64,
@@ -16,5 +17,21 @@ pub struct Stats {
     pub tx_dropped: u64,
     pub rx_dropped: u64,
     pub tx_error: u64,
-    pub rx_error: u64
+    pub rx_error: u64,
+    pub multicast: u64,
+    pub collisions: u64,
+    pub rx_length_errors: u64,
+    pub rx_over_errors: u64,
+    pub rx_crc_errors: u64,
+    pub rx_frame_errors: u64,
+    pub rx_fifo_errors: u64,
+    pub rx_missed_errors: u64,
+    pub tx_aborted_errors: u64,
+    pub tx_carrier_errors: u64,
+    pub tx_fifo_errors: u64,
+    pub tx_heartbeat_errors: u64,
+    pub tx_window_errors: u64,
+    pub rx_compressed: u64,
+    pub tx_compressed: u64,
+    pub rx_nohandler: u64
 }
@@ -1,3 +1,17 @@
 pub mod info;
 pub mod get_stats;
+pub mod sample;
 pub mod get_address;
+pub mod get_link;
+pub mod diff;
+pub mod events;
+pub mod netns;
+pub mod netlink;
+pub mod remote;
+pub mod sock_diag;
+pub mod get_tcp_stats;
+pub mod get_qdisc;
+pub mod get_route;
+pub mod get_neighbor;
+pub mod wireguard;
+pub mod get_wg_stats;
@@ -0,0 +1,107 @@
+use std::io;
+use std::sync::atomic::{AtomicU32, Ordering};
+use netlink_packet_core::NetlinkBuffer;
+use netlink_sys::{Socket, SocketAddr};
+
+// linux/netlink.h
+const NLM_F_REQUEST: u16 = 0x1;
+const NLM_F_DUMP_INTR: u16 = 0x10;
+const NLM_F_DUMP: u16 = 0x300;
+const NLMSG_ERROR: u16 = 2;
+const NLMSG_DONE: u16 = 3;
+const NLMSG_HDRLEN: usize = 16;
+
+// Every request gets its own sequence number, replies to an earlier request are told apart by it
+static NEXT_SEQUENCE: AtomicU32 = AtomicU32::new(1);
+
+// Sequence number and port id the replies to a request carry
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DumpId {
+    pub sequence: u32,
+    pub port: u32,
+}
+
+// Raw dumps for the netlink families rtnetlink doesn't cover (netfilter, sock_diag)
+pub fn dump_request(message_type: u16, payload: &[u8]) -> Vec<u8> {
+    let length = NLMSG_HDRLEN + payload.len();
+    let mut request = Vec::with_capacity(length);
+    request.extend_from_slice(&(length as u32).to_ne_bytes());
+    request.extend_from_slice(&message_type.to_ne_bytes());
+    request.extend_from_slice(&(NLM_F_REQUEST | NLM_F_DUMP).to_ne_bytes());
+    // Sequence number and port id, the kernel fills in the port
+    request.extend_from_slice(&NEXT_SEQUENCE.fetch_add(1, Ordering::Relaxed).to_ne_bytes());
+    request.extend_from_slice(&0u32.to_ne_bytes());
+    request.extend_from_slice(payload);
+    request
+}
+
+// Sends the request and calls `on_message` with the type and payload of every reply.
+// A failed dump leaves the socket drained, so the next one doesn't start with its leftovers.
+pub fn dump(socket: &Socket, request: &[u8], mut on_message: impl FnMut(u16, &[u8])) -> io::Result<()> {
+    let mut address = SocketAddr::new(0, 0);
+    socket.get_address(&mut address)?;
+    let id = DumpId {
+        sequence: NetlinkBuffer::new_checked(request)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e.to_string()))?
+            .sequence_number(),
+        port: address.port_number(),
+    };
+
+    socket.send(request, 0)?;
+
+    loop {
+        let done = socket.recv_from_full()
+            .and_then(|(buffer, _)| parse_messages(&buffer, id, &mut on_message));
+        match done {
+            Ok(true) => return Ok(()),
+            Ok(false) => (),
+            Err(e) => {
+                drain(socket);
+                return Err(e);
+            }
+        }
+    }
+}
+
+// Reads whatever is left of an aborted dump. The kernel only carries on with a dump
+// while it is being read, so this runs until the dump is over.
+fn drain(socket: &Socket) {
+    if socket.set_non_blocking(true).is_err() {
+        return;
+    }
+    while socket.recv_from_full().is_ok() {}
+    socket.set_non_blocking(false).ok();
+}
+
+// Parses one datagram of a dump, returns true once the dump is done. Messages of
+// another request are skipped, an interrupted dump fails as its tables are partial.
+pub fn parse_messages(buffer: &[u8], id: DumpId, mut on_message: impl FnMut(u16, &[u8])) -> io::Result<bool> {
+    let mut offset = 0;
+    while offset < buffer.len() {
+        let message = NetlinkBuffer::new_checked(&buffer[offset..])
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+        // Messages are aligned to 4 bytes
+        offset += (message.length() as usize + 3) & !3;
+
+        if message.sequence_number() != id.sequence || message.port_number() != id.port {
+            continue;
+        }
+        if message.flags() & NLM_F_DUMP_INTR != 0 {
+            return Err(io::Error::new(io::ErrorKind::Interrupted, "dump interrupted by a concurrent change"));
+        }
+
+        match message.message_type() {
+            NLMSG_DONE => return Ok(true),
+            NLMSG_ERROR => {
+                let code = message.payload().get(..4)
+                    .map(|code| i32::from_ne_bytes([code[0], code[1], code[2], code[3]]))
+                    .unwrap_or(0);
+                if code != 0 {
+                    return Err(io::Error::from_raw_os_error(-code));
+                }
+            }
+            kind => on_message(kind, message.payload())
+        }
+    }
+    Ok(false)
+}
@@ -0,0 +1,104 @@
+use std::fs::File;
+use std::io;
+use std::os::fd::AsRawFd;
+use std::path::{Path, PathBuf};
+use netlink_sys::{AsyncSocket, SocketAddr};
+use rtnetlink::{new_connection, Handle};
+use syscalls::{syscall, Sysno};
+
+use super::events::interface_event_groups;
+use super::get_address::InterfaceEvents;
+
+// linux/sched.h
+const CLONE_NEWNET: usize = 0x4000_0000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Namespace {
+    // Stored in the netns column, empty for the daemon's own namespace
+    pub name: String,
+    // None for the daemon's own namespace
+    pub path: Option<PathBuf>,
+}
+
+// rtnetlink handle whose socket lives in a namespace
+#[derive(Debug, Clone)]
+pub struct NamespaceHandle {
+    pub netns: String,
+    // To open other sockets in the namespace, None for the daemon's own
+    pub path: Option<PathBuf>,
+    pub handle: Handle,
+}
+
+impl Namespace {
+
+    pub fn host() -> Self {
+        Namespace { name: String::new(), path: None }
+    }
+
+    // A name under /var/run/netns (as created by `ip netns add`), a path to a
+    // namespace file or the pid of a process running in the namespace
+    pub fn parse(entry: &str) -> Result<Self, String> {
+        let entry = entry.trim();
+
+        let path = if entry.is_empty() {
+            return Err("namespace must not be empty".to_string());
+        } else if entry.starts_with('/') {
+            PathBuf::from(entry)
+        } else if let Ok(pid) = entry.parse::<u32>() {
+            PathBuf::from(format!("/proc/{pid}/ns/net"))
+        } else if entry.contains('/') || entry == "." || entry == ".." {
+            return Err(format!("invalid namespace name {entry}"));
+        } else {
+            PathBuf::from(format!("/var/run/netns/{entry}"))
+        };
+
+        Ok(Namespace { name: entry.to_string(), path: Some(path) })
+    }
+
+    pub fn is_host(&self) -> bool {
+        self.path.is_none()
+    }
+}
+
+impl NamespaceHandle {
+
+    // Name used in log messages
+    pub fn display_name(&self) -> &str {
+        if self.netns.is_empty() { "default" } else { &self.netns }
+    }
+}
+
+fn setns(file: &File) -> io::Result<()> {
+    // SAFETY: setns only reads the file descriptor, which outlives the call
+    unsafe { syscall!(Sysno::setns, file.as_raw_fd(), CLONE_NEWNET) }
+        .map(|_| ())
+        .map_err(io::Error::from)
+}
+
+// Runs `open` with the calling thread switched to the namespace. A socket stays in the
+// namespace it was created in, so the thread only has to switch while it is being created.
+pub fn in_namespace<T>(path: Option<&Path>, open: impl FnOnce() -> io::Result<T>) -> io::Result<T> {
+    let Some(path) = path else {
+        return open();
+    };
+
+    let target = File::open(path)?;
+    let own = File::open("/proc/thread-self/ns/net")?;
+
+    setns(&target)?;
+    let result = open();
+    // The thread is shared with other tasks, it can't be left in the wrong namespace
+    setns(&own).unwrap_or_else(|e| panic!("Failed to switch back to the daemon's network namespace: {e}"));
+    result
+}
+
+// Opens an rtnetlink connection inside the namespace and subscribes to link, address and route changes
+pub fn connect(namespace: &Namespace) -> io::Result<(NamespaceHandle, InterfaceEvents)> {
+    let (mut connection, handle, messages) = in_namespace(namespace.path.as_deref(), new_connection)?;
+
+    connection.socket_mut().socket_mut().bind(&SocketAddr::new(0, interface_event_groups()))?;
+    // Running in the background (asynchronously)
+    tokio::spawn(connection);
+
+    Ok((NamespaceHandle { netns: namespace.name.clone(), path: namespace.path.clone(), handle }, messages))
+}
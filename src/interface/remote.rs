@@ -0,0 +1,50 @@
+use std::collections::{HashMap, HashSet};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use futures::TryStreamExt;
+use netlink_packet_route::address::AddressAttribute;
+use rtnetlink::Handle;
+
+use crate::db::schema::Addr;
+
+// Helpers shared by the collectors that attribute traffic to remote hosts and networks
+
+pub async fn get_local_addresses(handle: &Handle) -> Result<HashSet<IpAddr>, rtnetlink::Error> {
+    let messages: Vec<_> = handle.address().get().execute().try_collect().await?;
+
+    Ok(messages.iter()
+        .flat_map(|message| message.attributes.iter())
+        .filter_map(|attribute| match attribute {
+            AddressAttribute::Address(addr) | AddressAttribute::Local(addr) => Some(*addr),
+            _ => None
+        })
+        .collect())
+}
+
+// Addresses of other servers as stored in addr, IPv4 is IPv4-mapped
+pub fn peer_addresses(addrs: &[Addr]) -> HashMap<Ipv6Addr, String> {
+    addrs.iter()
+        .flat_map(|addr| addr.ipv6.iter().map(move |(ip, _)| (ip, &addr.server_id)))
+        .filter_map(|(ip, server_id)| ip.map(|ip| (ip, server_id.clone())))
+        .collect()
+}
+
+// Network address and prefix length in the address family's own terms
+pub fn remote_network(addr: IpAddr, prefix_v4: u8, prefix_v6: u8) -> (Ipv6Addr, u8) {
+    match addr {
+        IpAddr::V4(v4) => {
+            let mask = u32::MAX.checked_shl(32 - prefix_v4.min(32) as u32).unwrap_or(0);
+            (Ipv4Addr::from(u32::from(v4) & mask).to_ipv6_mapped(), prefix_v4.min(32))
+        }
+        IpAddr::V6(v6) => {
+            let mask = u128::MAX.checked_shl(128 - prefix_v6.min(128) as u32).unwrap_or(0);
+            (Ipv6Addr::from(u128::from(v6) & mask), prefix_v6.min(128))
+        }
+    }
+}
+
+pub fn to_ipv6(addr: IpAddr) -> Ipv6Addr {
+    match addr {
+        IpAddr::V4(v4) => v4.to_ipv6_mapped(),
+        IpAddr::V6(v6) => v6,
+    }
+}
@@ -0,0 +1,92 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use log::error;
+
+use crate::db::schema::{QdiscStat, Stat, WgPeerStat};
+
+// When a counter sample was taken
+pub trait Sampled {
+    // Monotonic time, only set for samples taken by this process
+    fn instant(&self) -> Option<Instant>;
+    fn timestamp_ms(&self) -> u64;
+}
+
+impl Sampled for Stat {
+    fn instant(&self) -> Option<Instant> {
+        self.instant
+    }
+
+    fn timestamp_ms(&self) -> u64 {
+        self.timestamp_ms
+    }
+}
+
+impl Sampled for QdiscStat {
+    fn instant(&self) -> Option<Instant> {
+        self.instant
+    }
+
+    fn timestamp_ms(&self) -> u64 {
+        self.timestamp_ms
+    }
+}
+
+impl Sampled for WgPeerStat {
+    fn instant(&self) -> Option<Instant> {
+        self.instant
+    }
+
+    fn timestamp_ms(&self) -> u64 {
+        self.timestamp_ms
+    }
+}
+
+// Wall clock time stored with every sample, None when the clock is before the epoch
+pub fn timestamp() -> Option<Duration> {
+    SystemTime::now().duration_since(UNIX_EPOCH).inspect_err(|err| {
+        error!("Failed to get timestamp: {}", err);
+    }).ok()
+}
+
+// Seconds since the epoch, for the DateTime columns
+pub fn unix_timestamp() -> u32 {
+    timestamp().map(|timestamp| timestamp.as_secs() as u32).unwrap_or(0)
+}
+
+// Time between two samples. Prefer monotonic time, wall clock can jump.
+pub fn interval_ms(curr: &impl Sampled, old: &impl Sampled) -> u64 {
+    match (curr.instant(), old.instant()) {
+        (Some(curr), Some(old)) => curr.saturating_duration_since(old).as_millis() as u64,
+        _ => curr.timestamp_ms().saturating_sub(old.timestamp_ms()),
+    }
+}
+
+// Previous sample of every key, for collectors storing deltas of cumulative counters
+#[derive(Debug)]
+pub struct Baselines<K, T> {
+    last: HashMap<K, T>,
+}
+
+impl<K: Eq + Hash, T> Default for Baselines<K, T> {
+    fn default() -> Self {
+        Baselines { last: HashMap::new() }
+    }
+}
+
+impl<K: Eq + Hash, T> Baselines<K, T> {
+
+    // Delta of the current sample to the previous one. The first sample of a key is only a baseline,
+    // and `delta` returns None on a counter reset to skip the sample. Either way the current sample
+    // becomes the new baseline.
+    pub fn delta<D>(&mut self, key: K, curr: T, delta: impl FnOnce(&T, &T) -> Option<D>) -> Option<D> {
+        let result = self.last.get(&key).and_then(|old| delta(&curr, old));
+        self.last.insert(key, curr);
+        result
+    }
+
+    // Forgets the keys that are gone, called after every pass with the keys it saw
+    pub fn retain(&mut self, mut keep: impl FnMut(&K) -> bool) {
+        self.last.retain(|key, _| keep(key));
+    }
+}
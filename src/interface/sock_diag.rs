@@ -0,0 +1,117 @@
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::path::Path;
+use netlink_packet_utils::nla::NlasIterator;
+use netlink_sys::{protocols::NETLINK_SOCK_DIAG, Socket, SocketAddr};
+
+use super::netlink::{dump, dump_request};
+use super::netns::in_namespace;
+
+// linux/sock_diag.h, inet_diag.h
+const SOCK_DIAG_BY_FAMILY: u16 = 20;
+const AF_INET: u8 = 2;
+const AF_INET6: u8 = 10;
+const IPPROTO_TCP: u8 = 6;
+const INET_DIAG_INFO: u16 = 2;
+const INET_DIAG_MSG_LEN: usize = 72;
+const TCP_ESTABLISHED: u32 = 1;
+
+// Offsets in struct tcp_info (linux/tcp.h)
+const TCPI_LOST: usize = 32;
+const TCPI_RETRANS: usize = 36;
+const TCPI_RTT: usize = 68;
+const TCPI_RTTVAR: usize = 72;
+const TCPI_SND_CWND: usize = 80;
+const TCPI_TOTAL_RETRANS: usize = 100;
+
+// The part of tcp_info of an established connection the map needs
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TcpConnection {
+    pub src: Option<IpAddr>,
+    pub dst: Option<IpAddr>,
+    // Smoothed RTT and its variation in microseconds
+    pub rtt_us: u32,
+    pub rttvar_us: u32,
+    // In segments
+    pub cwnd: u32,
+    // Segments currently retransmitted and lost
+    pub retrans: u32,
+    pub lost: u32,
+    // Retransmitted segments over the lifetime of the connection
+    pub total_retrans: u32,
+}
+
+pub fn open_socket(namespace: Option<&Path>) -> io::Result<Socket> {
+    in_namespace(namespace, || {
+        let mut socket = Socket::new(NETLINK_SOCK_DIAG)?;
+        socket.bind_auto()?;
+        socket.connect(&SocketAddr::new(0, 0))?;
+        Ok(socket)
+    })
+}
+
+// Established TCP connections over IPv4 and IPv6
+pub fn dump_tcp_connections(socket: &Socket) -> io::Result<Vec<TcpConnection>> {
+    let mut connections = Vec::new();
+    for family in [AF_INET, AF_INET6] {
+        let request = dump_request(SOCK_DIAG_BY_FAMILY, &inet_diag_request(family));
+        dump(socket, &request, |kind, payload| connections.extend(parse_message(kind, payload)))?;
+    }
+    Ok(connections)
+}
+
+// struct inet_diag_req_v2 asking for tcp_info, the socket id is left empty to match every socket
+pub fn inet_diag_request(family: u8) -> Vec<u8> {
+    let mut request = vec![family, IPPROTO_TCP, 1 << (INET_DIAG_INFO - 1), 0];
+    request.extend_from_slice(&(1u32 << TCP_ESTABLISHED).to_ne_bytes());
+    request.resize(56, 0);
+    request
+}
+
+pub fn parse_message(kind: u16, payload: &[u8]) -> Option<TcpConnection> {
+    if kind != SOCK_DIAG_BY_FAMILY || payload.len() < INET_DIAG_MSG_LEN {
+        return None;
+    }
+
+    // struct inet_diag_msg: family, state, timer, retrans, then the socket id
+    let family = payload[0];
+    let src = parse_address(family, &payload[8..24]);
+    let dst = parse_address(family, &payload[24..40]);
+
+    let mut connection = TcpConnection { src, dst, ..Default::default() };
+    for nla in NlasIterator::new(&payload[INET_DIAG_MSG_LEN..]) {
+        let nla = nla.ok()?;
+        if nla.kind() == INET_DIAG_INFO {
+            let info = nla.value();
+            connection.lost = read_u32(info, TCPI_LOST)?;
+            connection.retrans = read_u32(info, TCPI_RETRANS)?;
+            connection.rtt_us = read_u32(info, TCPI_RTT)?;
+            connection.rttvar_us = read_u32(info, TCPI_RTTVAR)?;
+            connection.cwnd = read_u32(info, TCPI_SND_CWND)?;
+            connection.total_retrans = read_u32(info, TCPI_TOTAL_RETRANS)?;
+            return Some(connection);
+        }
+    }
+    // Without tcp_info there is nothing to report
+    None
+}
+
+// IPv4-mapped addresses of dual-stack sockets are returned as IPv4
+fn parse_address(family: u8, address: &[u8]) -> Option<IpAddr> {
+    match family {
+        AF_INET => {
+            let octets: [u8; 4] = address.get(..4)?.try_into().ok()?;
+            Some(IpAddr::V4(Ipv4Addr::from(octets)))
+        }
+        AF_INET6 => {
+            let octets: [u8; 16] = address.get(..16)?.try_into().ok()?;
+            let v6 = Ipv6Addr::from(octets);
+            Some(v6.to_ipv4_mapped().map(IpAddr::V4).unwrap_or(IpAddr::V6(v6)))
+        }
+        _ => None
+    }
+}
+
+fn read_u32(info: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_ne_bytes(info.get(offset..offset + 4)?.try_into().ok()?))
+}
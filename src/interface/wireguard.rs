@@ -0,0 +1,205 @@
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr as IpSocketAddr};
+use std::path::Path;
+use std::time::Duration;
+use netlink_packet_utils::nla::NlasIterator;
+use netlink_sys::{protocols::NETLINK_GENERIC, Socket, SocketAddr};
+
+use super::netlink::{dump, dump_request};
+use super::netns::in_namespace;
+
+// linux/genetlink.h
+const GENL_ID_CTRL: u16 = 0x10;
+const GENL_HDRLEN: usize = 4;
+const CTRL_CMD_NEWFAMILY: u8 = 1;
+const CTRL_CMD_GETFAMILY: u8 = 3;
+const CTRL_ATTR_FAMILY_ID: u16 = 1;
+const CTRL_ATTR_FAMILY_NAME: u16 = 2;
+
+// linux/wireguard.h
+const WG_GENL_NAME: &[u8] = b"wireguard";
+const WG_GENL_VERSION: u8 = 1;
+const WG_CMD_GET_DEVICE: u8 = 0;
+const WGDEVICE_A_IFINDEX: u16 = 1;
+const WGDEVICE_A_PEERS: u16 = 8;
+const WGPEER_A_PUBLIC_KEY: u16 = 1;
+const WGPEER_A_ENDPOINT: u16 = 4;
+const WGPEER_A_LAST_HANDSHAKE_TIME: u16 = 6;
+const WGPEER_A_RX_BYTES: u16 = 7;
+const WGPEER_A_TX_BYTES: u16 = 8;
+const WGPEER_A_ALLOWEDIPS: u16 = 9;
+const WGALLOWEDIP_A_IPADDR: u16 = 2;
+const WGALLOWEDIP_A_CIDR_MASK: u16 = 3;
+
+const AF_INET: u16 = 2;
+const AF_INET6: u16 = 10;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct WgPeer {
+    pub public_key: Vec<u8>,
+    pub endpoint: Option<IpSocketAddr>,
+    pub allowed_ips: Vec<(IpAddr, u8)>,
+    // Unix time of the latest handshake, None before the first one
+    pub last_handshake: Option<Duration>,
+    pub rx_bytes: u64,
+    pub tx_bytes: u64,
+}
+
+pub fn open_socket(namespace: Option<&Path>) -> io::Result<Socket> {
+    in_namespace(namespace, || {
+        let mut socket = Socket::new(NETLINK_GENERIC)?;
+        socket.bind_auto()?;
+        socket.connect(&SocketAddr::new(0, 0))?;
+        Ok(socket)
+    })
+}
+
+// Generic netlink ids are assigned when the module is loaded, None when it isn't
+pub fn resolve_family(socket: &Socket) -> io::Result<Option<u16>> {
+    let request = dump_request(GENL_ID_CTRL, &[CTRL_CMD_GETFAMILY, 1, 0, 0]);
+
+    let mut family = None;
+    dump(socket, &request, |kind, payload| {
+        if family.is_none() {
+            family = parse_family(kind, payload);
+        }
+    })?;
+    Ok(family)
+}
+
+pub fn parse_family(kind: u16, payload: &[u8]) -> Option<u16> {
+    if kind != GENL_ID_CTRL || *payload.first()? != CTRL_CMD_NEWFAMILY {
+        return None;
+    }
+
+    let mut id = None;
+    let mut is_wireguard = None;
+    for nla in NlasIterator::new(payload.get(GENL_HDRLEN..)?) {
+        let nla = nla.ok()?;
+        match nla.kind() {
+            CTRL_ATTR_FAMILY_ID => id = Some(u16::from_ne_bytes(nla.value().get(..2)?.try_into().ok()?)),
+            // NUL terminated
+            CTRL_ATTR_FAMILY_NAME => is_wireguard = Some(nla.value().split(|byte| *byte == 0).next()? == WG_GENL_NAME),
+            _ => ()
+        }
+    }
+    is_wireguard?.then_some(id?)
+}
+
+// Peers of one device. Devices with many peers are split over several messages.
+pub fn dump_peers(socket: &Socket, family: u16, ifindex: u32) -> io::Result<Vec<WgPeer>> {
+    let mut payload = vec![WG_CMD_GET_DEVICE, WG_GENL_VERSION, 0, 0];
+    payload.extend_from_slice(&8u16.to_ne_bytes());
+    payload.extend_from_slice(&WGDEVICE_A_IFINDEX.to_ne_bytes());
+    payload.extend_from_slice(&ifindex.to_ne_bytes());
+    let request = dump_request(family, &payload);
+
+    let mut peers = Vec::new();
+    dump(socket, &request, |kind, message| {
+        if kind == family {
+            merge_peers(&mut peers, parse_device(message).unwrap_or_default());
+        }
+    })?;
+    Ok(peers)
+}
+
+pub fn parse_device(payload: &[u8]) -> Option<Vec<WgPeer>> {
+    for nla in NlasIterator::new(payload.get(GENL_HDRLEN..)?) {
+        let nla = nla.ok()?;
+        if nla.kind() == WGDEVICE_A_PEERS {
+            // An array of nested attributes, one per peer
+            return NlasIterator::new(nla.value())
+                .map(|peer| parse_peer(peer.ok()?.value()))
+                .collect();
+        }
+    }
+    Some(Vec::new())
+}
+
+fn parse_peer(attributes: &[u8]) -> Option<WgPeer> {
+    let mut peer = WgPeer::default();
+
+    for nla in NlasIterator::new(attributes) {
+        let nla = nla.ok()?;
+        let value = nla.value();
+        match nla.kind() {
+            WGPEER_A_PUBLIC_KEY => peer.public_key = value.to_vec(),
+            WGPEER_A_ENDPOINT => peer.endpoint = parse_sockaddr(value),
+            WGPEER_A_LAST_HANDSHAKE_TIME => {
+                // struct __kernel_timespec, zero before the first handshake
+                let seconds = i64::from_ne_bytes(value.get(..8)?.try_into().ok()?);
+                let nanoseconds = i64::from_ne_bytes(value.get(8..16)?.try_into().ok()?);
+                peer.last_handshake = (seconds > 0).then(|| Duration::new(seconds as u64, nanoseconds as u32));
+            }
+            WGPEER_A_RX_BYTES => peer.rx_bytes = u64::from_ne_bytes(value.get(..8)?.try_into().ok()?),
+            WGPEER_A_TX_BYTES => peer.tx_bytes = u64::from_ne_bytes(value.get(..8)?.try_into().ok()?),
+            WGPEER_A_ALLOWEDIPS => {
+                for allowed_ip in NlasIterator::new(value) {
+                    peer.allowed_ips.extend(parse_allowed_ip(allowed_ip.ok()?.value()));
+                }
+            }
+            _ => ()
+        }
+    }
+    Some(peer)
+}
+
+fn parse_allowed_ip(attributes: &[u8]) -> Option<(IpAddr, u8)> {
+    let mut ip = None;
+    let mut cidr = None;
+    for nla in NlasIterator::new(attributes) {
+        let nla = nla.ok()?;
+        match nla.kind() {
+            WGALLOWEDIP_A_IPADDR => ip = match nla.value().len() {
+                4 => Some(IpAddr::V4(Ipv4Addr::from(<[u8; 4]>::try_from(nla.value()).ok()?))),
+                16 => Some(IpAddr::V6(Ipv6Addr::from(<[u8; 16]>::try_from(nla.value()).ok()?))),
+                _ => None
+            },
+            WGALLOWEDIP_A_CIDR_MASK => cidr = nla.value().first().copied(),
+            _ => ()
+        }
+    }
+    Some((ip?, cidr?))
+}
+
+// struct sockaddr_in or sockaddr_in6, the port is in network order
+fn parse_sockaddr(value: &[u8]) -> Option<IpSocketAddr> {
+    let family = u16::from_ne_bytes(value.get(..2)?.try_into().ok()?);
+    let port = u16::from_be_bytes(value.get(2..4)?.try_into().ok()?);
+    let ip = match family {
+        AF_INET => IpAddr::V4(Ipv4Addr::from(<[u8; 4]>::try_from(value.get(4..8)?).ok()?)),
+        AF_INET6 => IpAddr::V6(Ipv6Addr::from(<[u8; 16]>::try_from(value.get(8..24)?).ok()?)),
+        _ => return None
+    };
+    Some(IpSocketAddr::new(ip, port))
+}
+
+// When the allowed IPs of a peer don't fit in one message, the next message starts with the
+// same peer again, carrying only its public key and the remaining allowed IPs
+pub fn merge_peers(peers: &mut Vec<WgPeer>, more: Vec<WgPeer>) {
+    for peer in more {
+        match peers.last_mut() {
+            Some(last) if last.public_key == peer.public_key => last.allowed_ips.extend(peer.allowed_ips),
+            _ => peers.push(peer),
+        }
+    }
+}
+
+// Keys are shown in base64, like wg(8) does
+pub fn encode_key(key: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut encoded = String::with_capacity(key.len().div_ceil(3) * 4);
+    for chunk in key.chunks(3) {
+        let bytes = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let group = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+        for index in 0..4 {
+            if index <= chunk.len() {
+                encoded.push(ALPHABET[(group >> (18 - 6 * index) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
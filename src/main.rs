@@ -1,19 +1,36 @@
 use interface::get_address::{add_addr_to_database, check_for_interface_updates};
 use interface::get_stats::save_stats_every_second;
+use interface::get_tcp_stats::save_tcp_stats_every_interval;
+use interface::get_qdisc::save_qdisc_stats_every_interval;
+use interface::get_wg_stats::save_wg_stats_every_interval;
+use system::get_proto_stats::save_proto_stats_every_interval;
+use flow::get_flows::save_flows_every_interval;
+use alert::engine::start_alerts;
+use export::LatestStats;
+use export::prometheus::serve_metrics;
 
 use server::server::add_server_to_database;
-use tokio;
-use rtnetlink::{new_connection, Error as rtnetlinkErr, Handle};
-use log::error;
+use server::links::discover_links_every_interval;
+use interface::netns::{connect, Namespace};
+use rtnetlink::Error as rtnetlinkErr;
+use log::{error, info};
+use std::sync::Arc;
+use clickhouse::Client;
 
 mod db;
 mod interface;
+mod flow;
+mod alert;
+mod export;
+mod system;
 mod config;
 mod server;
 mod tests;
 
 use crate::config::config:: { DbConnection, ServerConfiguration };
-use crate::db::queries;
+use crate::db::store::{ClickHouseStore, Store, StoreKind};
+use crate::db::memory_store::MemoryStore;
+use crate::export::otlp::OtlpStore;
 
 use crate::db::schema;
 
@@ -23,42 +40,120 @@ async fn main() -> Result<(), rtnetlinkErr> {
     let con = DbConnection::new().await;
     let server_config = ServerConfiguration::new(con.get_config());
     let get_config = server_config.get_config().clone();
+    // Server, addresses, interface stats and host metrics, the other tables are always written to ClickHouse
+    let store = open_store(&server_config.get_collector().store, &con.get_client());
 
-    add_server_to_database(&con.get_client(), get_config).await;
+    add_server_to_database(store.as_ref(), get_config).await;
 
-    // Connection to a Netlink socket
-    let connect = new_connection();
-    let handle: Handle;
+    // Connection to a Netlink socket in every collected namespace
+    let namespaces = std::iter::once(Namespace::host())
+        .chain(server_config.get_collector().namespaces.iter().cloned());
+    let mut handles = Vec::new();
+    let mut events = Vec::new();
 
-    match connect {
-        Ok((connection, get_handle, _)) => {
-            handle = get_handle;
-            // Running in the background (asynchronously)
-            tokio::spawn(connection);
+    for namespace in namespaces {
+        match connect(&namespace) {
+            Ok((handle, messages)) => {
+                info!("Connected to the {} namespace", handle.display_name());
+                handles.push(handle);
+                events.push(messages);
+            }
+            Err(e) if namespace.is_host() => panic!("RTNetLink Connection failed: {e}"),
+            Err(e) => error!("Failed to connect to namespace {}: {e}, skipping it", namespace.name),
         }
-        Err(_) => panic!("RTNetLink Connection failed"),
     }
 
-   let handle_clone = handle.clone();
+   // Threshold and state alerts on the stats and interface changes collected below
+   let alerts = start_alerts(server_config.get_alerting(), &server_config.get_config().server_id);
+
+   // Latest cumulative counters for Prometheus to scrape
+   let latest_stats = match server_config.get_collector().metrics_listen {
+       Some(listen) => {
+           let latest_stats = LatestStats::enabled();
+           tokio::spawn(serve_metrics(listen, server_config.get_config().clone(), latest_stats.clone()));
+           latest_stats
+       }
+       None => LatestStats::default(),
+   };
+
    let client_clone = con.get_client().clone();
-   let server_conf_clone = server_config.clone();
 
-   add_addr_to_database(&handle, &client_clone, &server_config).await;
+   add_addr_to_database(&handles, store.as_ref(), &server_config).await;
 
-   let updates_task = tokio::spawn(async move {
-       check_for_interface_updates(&handle_clone, &client_clone, &server_conf_clone).await;
-   });
+   let mut updates_tasks = tokio::task::JoinSet::new();
+   for (handle, messages) in handles.iter().cloned().zip(events) {
+       let client_clone = client_clone.clone();
+       let store = Arc::clone(&store);
+       let server_conf_clone = server_config.clone();
+       let alerts = alerts.clone();
+       updates_tasks.spawn(async move {
+           check_for_interface_updates(&handle, &client_clone, store.as_ref(), &server_conf_clone, &alerts, messages).await;
+       });
+   }
+
+   // Optional collectors stop on their own when they can't start, the daemon keeps running without them
+   if server_config.get_collector().flow_interval.is_some() {
+       let (handles, client, server_config) = (handles.clone(), con.get_client(), server_config.clone());
+       tokio::spawn(async move {
+           save_flows_every_interval(&handles, &server_config, &client).await;
+       });
+   }
+
+   if server_config.get_collector().tcp_interval.is_some() {
+       let (handles, client, server_config) = (handles.clone(), con.get_client(), server_config.clone());
+       tokio::spawn(async move {
+           save_tcp_stats_every_interval(&handles, &server_config, &client).await;
+       });
+   }
+
+   if server_config.get_collector().qdisc_interval.is_some() {
+       let (handles, client, server_config) = (handles.clone(), con.get_client(), server_config.clone());
+       tokio::spawn(async move {
+           save_qdisc_stats_every_interval(&handles, &server_config, &client).await;
+       });
+   }
+
+   if server_config.get_collector().wg_interval.is_some() {
+       let (handles, client, server_config) = (handles.clone(), con.get_client(), server_config.clone());
+       tokio::spawn(async move {
+           save_wg_stats_every_interval(&handles, &server_config, &client).await;
+       });
+   }
+
+   if server_config.get_collector().proto_interval.is_some() {
+       let (handles, client, server_config) = (handles.clone(), con.get_client(), server_config.clone());
+       tokio::spawn(async move {
+           save_proto_stats_every_interval(&handles, &server_config, &client).await;
+       });
+   }
+
+   // Links to the other servers, from the addresses they saved
+   {
+       let (client, store, server_config) = (con.get_client(), Arc::clone(&store), server_config.clone());
+       let netns: Vec<String> = handles.iter().map(|handle| handle.netns.clone()).collect();
+       tokio::spawn(async move {
+           discover_links_every_interval(&server_config, &client, store.as_ref(), &netns).await;
+       });
+   }
 
    let stats_task = tokio::spawn(async move {
-       if let Err(e) = save_stats_every_second(&handle, &server_config, &con.get_client()).await {
+       if let Err(e) = save_stats_every_second(&handles, &server_config, store.as_ref(), &alerts, &latest_stats).await {
            error!("Stats task failed: {e}");
        }
    });
 
    tokio::select! {
-       _ = updates_task => error!("Interface update task unexpectedly terminated"),
+       _ = updates_tasks.join_next() => error!("Interface update task unexpectedly terminated"),
        _ = stats_task => error!("Stats task unexpectedly terminated"),
    }
 
    Ok(())
 }
+
+fn open_store(kind: &StoreKind, client: &Client) -> Arc<dyn Store> {
+    match kind {
+        StoreKind::ClickHouse => Arc::new(ClickHouseStore::new(client.clone())),
+        StoreKind::Memory => Arc::new(MemoryStore::default()),
+        StoreKind::Otlp(endpoint) => Arc::new(OtlpStore::new(endpoint.clone())),
+    }
+}
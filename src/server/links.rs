@@ -0,0 +1,131 @@
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv6Addr};
+use clickhouse::Client;
+use log::{error, info};
+use tokio::time::interval;
+
+use crate::config::config::ServerConfiguration;
+use crate::db::queries::{add_links, delete_links, get_all_addr, get_links};
+use crate::db::schema::{Addr, Link};
+use crate::db::store::Store;
+use crate::interface::diff::{apply_diff, diff_by_key, Diff};
+use crate::interface::remote::remote_network;
+
+// One edge per pair of interfaces, without the kind
+type LinkKey = (String, String, String, String, String);
+
+// The addresses of the other servers change when they restart or resync, links are
+// discovered again every link_interval. The own addresses come from the store, which
+// isn't ClickHouse with the memory store.
+pub async fn discover_links_every_interval(server_config: &ServerConfiguration, client: &Client, store: &dyn Store, netns: &[String]) {
+    let server_id = &server_config.get_config().server_id;
+    let link_interval = server_config.get_collector().link_interval;
+    let mut link_timer = interval(link_interval);
+
+    info!("Discovering links to other servers every {} ms.", link_interval.as_millis());
+
+    loop {
+        link_timer.tick().await;
+
+        let mut own = Vec::new();
+        for netns in netns {
+            match store.get_addresses(server_config.get_config(), netns).await {
+                Ok(addrs) => own.extend(addrs),
+                Err(e) => error!("Failed to get own addresses in the {netns} namespace: {e}"),
+            }
+        }
+        let others: Vec<Addr> = match get_all_addr(client).await {
+            Ok(addrs) => addrs.into_iter().filter(|addr| addr.server_id != *server_id).collect(),
+            Err(e) => {
+                error!("Failed to get addresses: {e}, skipping link discovery");
+                continue;
+            }
+        };
+
+        let db_links = match get_links(client, server_config.get_config()).await {
+            Ok(links) => links,
+            Err(e) => {
+                error!("Failed to get links from database: {e}, skipping link discovery");
+                continue;
+            }
+        };
+
+        apply_link_updates(client, compare_discovered_links(&discover_links(&own, &others), &db_links)).await;
+    }
+}
+
+// Links from the interfaces of one server to the interfaces of the others
+pub fn discover_links(own: &[Addr], others: &[Addr]) -> Vec<Link> {
+    let mut links: HashMap<LinkKey, Link> = HashMap::new();
+
+    for local in own {
+        for remote in others {
+            let link = |kind: &str| Link {
+                server_a: local.server_id.clone(),
+                netns_a: local.netns.clone(),
+                iface_a: local.interface.clone(),
+                server_b: remote.server_id.clone(),
+                netns_b: remote.netns.clone(),
+                iface_b: remote.interface.clone(),
+                kind: kind.to_string()
+            };
+
+            let is_peer = local.ipv6_peer.iter()
+                .filter_map(|(peer, _)| *peer)
+                .any(|peer| remote.ipv6.iter().any(|(ip, _)| *ip == Some(peer)));
+
+            if is_peer {
+                let link = link("peer");
+                links.insert(link_key(&link), link);
+            } else if shares_subnet(&local.ipv6, &remote.ipv6) {
+                let link = link("subnet");
+                links.entry(link_key(&link)).or_insert(link);
+            }
+        }
+    }
+    links.into_values().collect()
+}
+
+fn link_key(link: &Link) -> LinkKey {
+    (link.netns_a.clone(), link.iface_a.clone(), link.server_b.clone(), link.netns_b.clone(), link.iface_b.clone())
+}
+
+// Host addresses (/32, /128) have no subnet to share. The same address on both sides is a
+// shared VIP or a default like docker0, not a link.
+fn shares_subnet(local: &[(Option<Ipv6Addr>, Option<u8>)], remote: &[(Option<Ipv6Addr>, Option<u8>)]) -> bool {
+    let subnets = |addrs: &[(Option<Ipv6Addr>, Option<u8>)]| -> Vec<(Ipv6Addr, Ipv6Addr, u8)> {
+        addrs.iter()
+            .filter_map(|(ip, prefix)| Some((ip.as_ref()?, (*prefix)?)))
+            .filter_map(|(ip, prefix)| {
+                let addr = ip.to_ipv4_mapped().map(IpAddr::V4).unwrap_or(IpAddr::V6(*ip));
+                let host_prefix = if addr.is_ipv4() { 32 } else { 128 };
+                (prefix > 0 && prefix < host_prefix && !addr.is_loopback()).then(|| {
+                    let (network, prefix) = remote_network(addr, prefix, prefix);
+                    (*ip, network, prefix)
+                })
+            })
+            .collect()
+    };
+
+    let remote = subnets(remote);
+    subnets(local).iter().any(|(local_ip, network, prefix)| {
+        remote.iter().any(|(remote_ip, remote_network, remote_prefix)| {
+            local_ip != remote_ip && network == remote_network && prefix == remote_prefix
+        })
+    })
+}
+
+async fn apply_link_updates(client: &Client, diff: Diff<Link>) {
+    apply_diff(diff, "link(s) to other servers",
+        |deletes| async move { delete_links(client, &deletes).await },
+        |creates| async move {
+            for link in &creates {
+                info!("{}: {} link to {} {}", link.iface_a, link.kind, link.server_b, link.iface_b);
+            }
+            add_links(client, creates).await
+        }).await;
+}
+
+pub fn compare_discovered_links(fresh: &[Link], db: &[Link]) -> Diff<Link> {
+    diff_by_key(fresh, db, link_key)
+}
@@ -1,23 +1,22 @@
 use std::process;
-use clickhouse::Client;
 use log::error;
 
-use crate::db::queries::{self, server_exists, update_server};
 use crate::db::schema::Server;
+use crate::db::store::Store;
 
-pub async fn add_server_to_database(client: &Client, server: Server) {
+pub async fn add_server_to_database(store: &dyn Store, server: Server) {
     // Check if the server exists
-    match server_exists(client, server.clone()).await {
+    match store.server_exists(&server).await {
         Ok(exists) => {
             // If it exists, update it
             if exists {
-                if let Err(e) = update_server(client, server).await {
+                if let Err(e) = store.update_server(server).await {
                     error!("Failed to update existing server: {e}. Exiting...");
                     process::exit(1);
                 }
             } else {
                 // Add the server
-                if let Err(e) = queries::add_server(client, server).await {
+                if let Err(e) = store.add_server(server).await {
                     error!("Failed to add server to the database: {e}. Exiting...");
                     process::exit(1);
                 }
@@ -0,0 +1,135 @@
+use std::collections::HashSet;
+use std::io;
+use std::time::{Duration, Instant};
+use clickhouse::Client;
+use log::{error, info, warn};
+use tokio::time::{interval, MissedTickBehavior};
+
+use crate::config::config::ServerConfiguration;
+use crate::db::queries::{add_proto_stats, add_stat_reset};
+use crate::db::schema::{ProtoStat, StatReset};
+use crate::interface::netns::NamespaceHandle;
+use crate::interface::sample::{interval_ms, timestamp, Baselines, Sampled};
+use super::procfs::{read_proto_counters, ProtoCounters};
+
+// Counters of one namespace and when they were read
+pub struct ProtoSample {
+    pub instant: Instant,
+    pub timestamp: Duration,
+    pub counters: ProtoCounters,
+}
+
+impl Sampled for ProtoSample {
+    fn instant(&self) -> Option<Instant> {
+        Some(self.instant)
+    }
+
+    fn timestamp_ms(&self) -> u64 {
+        self.timestamp.as_millis() as u64
+    }
+}
+
+pub async fn save_proto_stats_every_interval(namespaces: &[NamespaceHandle], server_config: &ServerConfiguration, client: &Client) {
+    let Some(proto_interval) = server_config.get_collector().proto_interval else {
+        return;
+    };
+
+    let mut proto_timer = interval(proto_interval);
+    proto_timer.set_missed_tick_behavior(MissedTickBehavior::Skip);
+    // Previous counters by namespace
+    let mut last_counters: Baselines<String, ProtoSample> = Baselines::default();
+
+    info!("Collecting protocol counters every {} ms.", proto_interval.as_millis());
+
+    loop {
+        proto_timer.tick().await;
+
+        let mut stats = Vec::new();
+        let mut resets = Vec::new();
+        let mut seen = HashSet::new();
+        for namespace in namespaces {
+            let counters = match read_counters(namespace).await {
+                Ok(counters) => counters,
+                Err(e) => {
+                    error!("Failed to read protocol counters in the {} namespace: {e}", namespace.display_name());
+                    continue;
+                }
+            };
+            let Some(timestamp) = timestamp() else {
+                continue;
+            };
+            let sample = ProtoSample { instant: Instant::now(), timestamp, counters };
+            seen.insert(namespace.netns.clone());
+
+            let deltas = last_counters.delta(namespace.netns.clone(), sample, |curr, old| {
+                let template = ProtoStat {
+                    server_id: server_config.get_config().server_id.clone(),
+                    netns: namespace.netns.clone(),
+                    timestamp: curr.timestamp.as_secs() as u32,
+                    timestamp_ms: curr.timestamp_ms(),
+                    interval_ms: interval_ms(curr, old),
+                    ..Default::default()
+                };
+                Some(proto_deltas(&curr.counters, &old.counters, &template))
+            });
+            if let Some((deltas, namespace_resets)) = deltas {
+                if !namespace_resets.is_empty() {
+                    warn!("{} protocol counter(s) of the {} namespace were reset, skipping them",
+                        namespace_resets.len(), namespace.display_name());
+                }
+                stats.extend(deltas);
+                resets.extend(namespace_resets);
+            }
+        }
+        last_counters.retain(|netns| seen.contains(netns));
+
+        if !stats.is_empty() {
+            add_proto_stats(client, stats).await.inspect_err(|e| {
+                error!("Failed to save protocol stats: {e}");
+            }).ok();
+        }
+        if !resets.is_empty() {
+            add_stat_reset(client, resets).await.inspect_err(|e| {
+                error!("Failed to save protocol counter resets: {e}");
+            }).ok();
+        }
+    }
+}
+
+// Switching namespaces and reading /proc block, keep them off the async workers
+async fn read_counters(namespace: &NamespaceHandle) -> io::Result<ProtoCounters> {
+    let path = namespace.path.clone();
+    tokio::task::spawn_blocking(move || read_proto_counters(path.as_deref())).await.map_err(io::Error::other)?
+}
+
+// The counters are 64-bit, one going back was reset (the namespace was recreated, or a module
+// reloaded). It is skipped and recorded in stat_reset, the other counters are kept.
+// Counters that didn't move are left out, most of them stay at 0.
+pub fn proto_deltas(counters: &ProtoCounters, old_counters: &ProtoCounters, template: &ProtoStat) -> (Vec<ProtoStat>, Vec<StatReset>) {
+    let mut stats = Vec::new();
+    let mut resets = Vec::new();
+
+    for ((protocol, counter), value) in counters {
+        // New counters (a module was loaded) start from the current value
+        let Some(old_value) = old_counters.get(&(protocol.clone(), counter.clone())) else {
+            continue;
+        };
+        match value.checked_sub(*old_value) {
+            Some(0) => (),
+            Some(value) => stats.push(ProtoStat {
+                protocol: protocol.clone(),
+                counter: counter.clone(),
+                value,
+                ..template.clone()
+            }),
+            None => resets.push(StatReset {
+                server_id: template.server_id.clone(),
+                netns: template.netns.clone(),
+                interface: String::new(),
+                timestamp: template.timestamp,
+                reason: format!("{protocol} {counter}")
+            }),
+        }
+    }
+    (stats, resets)
+}
@@ -0,0 +1,126 @@
+use std::io;
+use std::time::{Duration, Instant};
+use log::{error, warn};
+
+use crate::db::schema::SystemStat;
+use crate::interface::get_stats::counter_delta32;
+use crate::interface::sample::timestamp;
+use super::procfs::{read_host_sample, HostSample};
+
+// The stats tick can be a few ms, host metrics don't need more than one sample a second
+const MIN_SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+
+// Host counters of the previous sample
+pub struct HostStats {
+    previous: (Instant, HostSample),
+}
+
+impl HostStats {
+    // None when /proc can't be read, host metrics are then left out
+    pub async fn new() -> Option<Self> {
+        match read_sample().await {
+            Ok(sample) => Some(HostStats { previous: (Instant::now(), sample) }),
+            Err(e) => {
+                warn!("Failed to read host metrics: {e}, they are not collected");
+                None
+            }
+        }
+    }
+
+    // None until a second has passed since the previous sample
+    pub async fn sample(&mut self, server_id: &str) -> Option<SystemStat> {
+        if self.previous.0.elapsed() < MIN_SAMPLE_INTERVAL {
+            return None;
+        }
+
+        let sample = read_sample().await.inspect_err(|e| {
+            error!("Failed to read host metrics: {e}");
+        }).ok()?;
+        let instant = Instant::now();
+        let timestamp = timestamp()?;
+
+        // CPU times move in jiffies (10 ms at 100 Hz), a sample without any is merged into the next one
+        let (old_instant, old) = &self.previous;
+        if sample.cpu.total() == old.cpu.total() {
+            return None;
+        }
+
+        let template = SystemStat {
+            server_id: server_id.to_string(),
+            timestamp: timestamp.as_secs() as u32,
+            timestamp_ms: timestamp.as_millis() as u64,
+            interval_ms: instant.saturating_duration_since(*old_instant).as_millis() as u64,
+            ..Default::default()
+        };
+        let stat = system_delta(&sample, old, template);
+        if stat.is_none() {
+            warn!("CPUs went online or offline, skipping host metrics sample");
+        }
+        self.previous = (instant, sample);
+        stat
+    }
+}
+
+// Reading /proc blocks, keep it off the async workers
+async fn read_sample() -> io::Result<HostSample> {
+    tokio::task::spawn_blocking(read_host_sample).await.map_err(io::Error::other)?
+}
+
+pub fn system_delta(curr: &HostSample, old: &HostSample, template: SystemStat) -> Option<SystemStat> {
+    // Per CPU values can't be matched after a CPU went online or offline
+    if curr.cpus.len() != old.cpus.len() || curr.softnet.len() != old.softnet.len() {
+        return None;
+    }
+
+    let share = |curr: u64, old: u64, total: u64| {
+        if total == 0 {
+            return 0.0;
+        }
+        curr.saturating_sub(old) as f32 * 100.0 / total as f32
+    };
+    let total = curr.cpu.total().checked_sub(old.cpu.total())?;
+
+    let (mut cpu_busy_max, mut cpu_softirq_max) = (0.0f32, 0.0f32);
+    for (curr_cpu, old_cpu) in curr.cpus.iter().zip(&old.cpus) {
+        let cpu_total = curr_cpu.total().saturating_sub(old_cpu.total());
+        cpu_busy_max = cpu_busy_max.max(share(curr_cpu.busy(), old_cpu.busy(), cpu_total));
+        cpu_softirq_max = cpu_softirq_max.max(share(curr_cpu.softirq, old_cpu.softirq, cpu_total));
+    }
+
+    let softnet: Vec<(u64, u64, u64)> = curr.softnet.iter().zip(&old.softnet)
+        .map(|(curr_cpu, old_cpu)| (
+            // 32-bit counters in softnet_stat
+            counter_delta32(curr_cpu.processed, old_cpu.processed).unwrap_or(0),
+            counter_delta32(curr_cpu.dropped, old_cpu.dropped).unwrap_or(0),
+            counter_delta32(curr_cpu.time_squeeze, old_cpu.time_squeeze).unwrap_or(0),
+        ))
+        .collect();
+
+    Some(SystemStat {
+        cpu_user: share(curr.cpu.user + curr.cpu.nice, old.cpu.user + old.cpu.nice, total),
+        cpu_system: share(curr.cpu.system, old.cpu.system, total),
+        cpu_iowait: share(curr.cpu.iowait, old.cpu.iowait, total),
+        cpu_irq: share(curr.cpu.irq, old.cpu.irq, total),
+        cpu_softirq: share(curr.cpu.softirq, old.cpu.softirq, total),
+        cpu_steal: share(curr.cpu.steal, old.cpu.steal, total),
+        cpu_idle: share(curr.cpu.idle, old.cpu.idle, total),
+        cpu_busy_max,
+        cpu_softirq_max,
+        load1: curr.load.load1,
+        load5: curr.load.load5,
+        load15: curr.load.load15,
+        mem_total: curr.memory.total,
+        mem_available: curr.memory.available,
+        mem_free: curr.memory.free,
+        mem_buffers: curr.memory.buffers,
+        mem_cached: curr.memory.cached,
+        swap_total: curr.memory.swap_total,
+        swap_free: curr.memory.swap_free,
+        softnet_processed: softnet.iter().map(|cpu| cpu.0).sum(),
+        softnet_dropped: softnet.iter().map(|cpu| cpu.1).sum(),
+        softnet_time_squeeze: softnet.iter().map(|cpu| cpu.2).sum(),
+        softnet_dropped_per_cpu: softnet.iter().map(|cpu| cpu.1).collect(),
+        softnet_time_squeeze_per_cpu: softnet.iter().map(|cpu| cpu.2).collect(),
+        ..template
+    })
+}
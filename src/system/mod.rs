@@ -0,0 +1,3 @@
+pub mod procfs;
+pub mod get_system_stats;
+pub mod get_proto_stats;
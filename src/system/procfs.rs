@@ -0,0 +1,217 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::interface::netns::in_namespace;
+
+// /proc/meminfo is in kB
+const KB: u64 = 1024;
+
+// Jiffies spent in each state since boot, from a cpu line of /proc/stat. Guest time is
+// already counted in user and nice.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CpuTimes {
+    pub user: u64,
+    pub nice: u64,
+    pub system: u64,
+    pub idle: u64,
+    pub iowait: u64,
+    pub irq: u64,
+    pub softirq: u64,
+    pub steal: u64,
+}
+
+impl CpuTimes {
+    pub fn total(&self) -> u64 {
+        self.user + self.nice + self.system + self.idle + self.iowait + self.irq + self.softirq + self.steal
+    }
+
+    pub fn busy(&self) -> u64 {
+        self.total() - self.idle - self.iowait
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LoadAvg {
+    pub load1: f32,
+    pub load5: f32,
+    pub load15: f32,
+}
+
+// In bytes
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MemInfo {
+    pub total: u64,
+    pub free: u64,
+    pub available: u64,
+    pub buffers: u64,
+    pub cached: u64,
+    pub swap_total: u64,
+    pub swap_free: u64,
+}
+
+// One line of /proc/net/softnet_stat, 32-bit counters that wrap
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Softnet {
+    pub processed: u64,
+    // The backlog queue was full (netdev_max_backlog)
+    pub dropped: u64,
+    // The NAPI budget or time ran out with work left (netdev_budget)
+    pub time_squeeze: u64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HostSample {
+    pub cpu: CpuTimes,
+    pub cpus: Vec<CpuTimes>,
+    pub load: LoadAvg,
+    pub memory: MemInfo,
+    pub softnet: Vec<Softnet>,
+}
+
+pub fn read_host_sample() -> io::Result<HostSample> {
+    let invalid = |file: &str| io::Error::new(io::ErrorKind::InvalidData, format!("Failed to parse {file}"));
+
+    let (cpu, cpus) = parse_cpu_times(&fs::read_to_string("/proc/stat")?).ok_or_else(|| invalid("/proc/stat"))?;
+    Ok(HostSample {
+        cpu,
+        cpus,
+        load: parse_loadavg(&fs::read_to_string("/proc/loadavg")?).ok_or_else(|| invalid("/proc/loadavg"))?,
+        memory: parse_meminfo(&fs::read_to_string("/proc/meminfo")?).ok_or_else(|| invalid("/proc/meminfo"))?,
+        softnet: parse_softnet_stat(&fs::read_to_string("/proc/net/softnet_stat")?),
+    })
+}
+
+// The aggregated cpu line and one line per online CPU
+pub fn parse_cpu_times(stat: &str) -> Option<(CpuTimes, Vec<CpuTimes>)> {
+    let mut total = None;
+    let mut cpus = Vec::new();
+
+    for line in stat.lines() {
+        let mut fields = line.split_whitespace();
+        let Some(name) = fields.next().filter(|name| name.starts_with("cpu")) else {
+            continue;
+        };
+        let values: Vec<u64> = fields.map(|value| value.parse().unwrap_or(0)).collect();
+        let value = |index: usize| values.get(index).copied().unwrap_or(0);
+        let times = CpuTimes {
+            user: value(0),
+            nice: value(1),
+            system: value(2),
+            idle: value(3),
+            iowait: value(4),
+            irq: value(5),
+            softirq: value(6),
+            steal: value(7),
+        };
+
+        if name == "cpu" {
+            total = Some(times);
+        } else {
+            cpus.push(times);
+        }
+    }
+    Some((total?, cpus))
+}
+
+pub fn parse_loadavg(loadavg: &str) -> Option<LoadAvg> {
+    let mut fields = loadavg.split_whitespace().map(|field| field.parse::<f32>().ok());
+    Some(LoadAvg { load1: fields.next()??, load5: fields.next()??, load15: fields.next()?? })
+}
+
+pub fn parse_meminfo(meminfo: &str) -> Option<MemInfo> {
+    let mut memory = MemInfo::default();
+
+    for line in meminfo.lines() {
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let Some(value) = value.split_whitespace().next().and_then(|value| value.parse::<u64>().ok()) else {
+            continue;
+        };
+        match key {
+            "MemTotal" => memory.total = value * KB,
+            "MemFree" => memory.free = value * KB,
+            "MemAvailable" => memory.available = value * KB,
+            "Buffers" => memory.buffers = value * KB,
+            "Cached" => memory.cached = value * KB,
+            "SwapTotal" => memory.swap_total = value * KB,
+            "SwapFree" => memory.swap_free = value * KB,
+            _ => ()
+        }
+    }
+    (memory.total > 0).then_some(memory)
+}
+
+// One line per online CPU, in hexadecimal
+pub fn parse_softnet_stat(softnet_stat: &str) -> Vec<Softnet> {
+    softnet_stat.lines()
+        .map(|line| {
+            let values: Vec<u64> = line.split_whitespace()
+                .map(|value| u64::from_str_radix(value, 16).unwrap_or(0))
+                .collect();
+            let value = |index: usize| values.get(index).copied().unwrap_or(0);
+            Softnet { processed: value(0), dropped: value(1), time_squeeze: value(2) }
+        })
+        .collect()
+}
+
+// Protocol counters by protocol and name, e.g. ("Tcp", "RetransSegs")
+pub type ProtoCounters = HashMap<(String, String), u64>;
+
+// Settings and gauges listed among the counters of /proc/net/snmp
+const PROTO_GAUGES: [(&str, &str); 7] = [
+    ("Ip", "Forwarding"), ("Ip", "DefaultTTL"), ("Tcp", "RtoAlgorithm"), ("Tcp", "RtoMin"),
+    ("Tcp", "RtoMax"), ("Tcp", "MaxConn"), ("Tcp", "CurrEstab"),
+];
+
+// Counters of the namespace the thread runs in. /proc/self/net follows the main thread,
+// thread-self follows the namespace in_namespace switched to.
+pub fn read_proto_counters(namespace: Option<&Path>) -> io::Result<ProtoCounters> {
+    in_namespace(namespace, || {
+        let mut counters = parse_snmp(&fs::read_to_string("/proc/thread-self/net/snmp")?);
+        counters.extend(parse_snmp(&fs::read_to_string("/proc/thread-self/net/netstat")?));
+        // Missing when IPv6 is disabled
+        match fs::read_to_string("/proc/thread-self/net/snmp6") {
+            Ok(snmp6) => counters.extend(parse_snmp6(&snmp6)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => (),
+            Err(e) => return Err(e),
+        }
+        Ok(counters)
+    })
+}
+
+// Pairs of lines, the names then the values, both prefixed with the protocol
+pub fn parse_snmp(snmp: &str) -> ProtoCounters {
+    let mut counters = ProtoCounters::new();
+    let mut lines = snmp.lines();
+
+    while let (Some(names), Some(values)) = (lines.next(), lines.next()) {
+        let (Some((protocol, names)), Some((_, values))) = (names.split_once(':'), values.split_once(':')) else {
+            continue;
+        };
+        for (name, value) in names.split_whitespace().zip(values.split_whitespace()) {
+            if PROTO_GAUGES.contains(&(protocol, name)) {
+                continue;
+            }
+            // MaxConn is -1, anything negative isn't a counter
+            if let Ok(value) = value.parse::<u64>() {
+                counters.insert((protocol.to_string(), name.to_string()), value);
+            }
+        }
+    }
+    counters
+}
+
+// One counter per line, the protocol is the name up to the 6: Ip6InReceives, UdpLite6InErrors
+pub fn parse_snmp6(snmp6: &str) -> ProtoCounters {
+    snmp6.lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let (name, value) = (fields.next()?, fields.next()?.parse::<u64>().ok()?);
+            let split = name.find('6')? + 1;
+            Some(((name[..split].to_string(), name[split..].to_string()), value))
+        })
+        .collect()
+}
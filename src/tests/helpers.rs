@@ -0,0 +1,27 @@
+// Builders for raw netlink messages, shared by the tests of the netlink parsers
+use crate::interface::netlink::DumpId;
+
+pub fn nla(kind: u16, value: &[u8]) -> Vec<u8> {
+    let mut buffer = Vec::new();
+    buffer.extend_from_slice(&((4 + value.len()) as u16).to_ne_bytes());
+    buffer.extend_from_slice(&kind.to_ne_bytes());
+    buffer.extend_from_slice(value);
+    buffer.resize((buffer.len() + 3) & !3, 0);
+    buffer
+}
+
+pub fn nested(kind: u16, nlas: &[Vec<u8>]) -> Vec<u8> {
+    nla(kind | 0x8000, &nlas.concat())
+}
+
+// Netlink header followed by the payload
+pub fn message(kind: u16, flags: u16, id: DumpId, payload: &[u8]) -> Vec<u8> {
+    let mut buffer = Vec::new();
+    buffer.extend_from_slice(&((16 + payload.len()) as u32).to_ne_bytes());
+    buffer.extend_from_slice(&kind.to_ne_bytes());
+    buffer.extend_from_slice(&flags.to_ne_bytes());
+    buffer.extend_from_slice(&id.sequence.to_ne_bytes());
+    buffer.extend_from_slice(&id.port.to_ne_bytes());
+    buffer.extend_from_slice(payload);
+    buffer
+}
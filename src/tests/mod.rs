@@ -1,2 +1,18 @@
+#[cfg(test)]
+pub mod helpers;
 pub mod unit_test_interface;
 pub mod unit_test_functions;
+pub mod unit_test_config;
+pub mod unit_test_spool;
+pub mod unit_test_flow;
+pub mod unit_test_tcp;
+pub mod unit_test_qdisc;
+pub mod unit_test_route;
+pub mod unit_test_neighbor;
+pub mod unit_test_links;
+pub mod unit_test_wireguard;
+pub mod unit_test_system;
+pub mod unit_test_proto;
+pub mod unit_test_alert;
+pub mod unit_test_store;
+pub mod unit_test_export;
@@ -0,0 +1,174 @@
+#[cfg(test)]
+mod alert_tests {
+    use std::net::Ipv6Addr;
+    use std::time::{Duration, Instant};
+    use crate::alert::engine::{AlertEngine, AlertInput, Notification};
+    use crate::alert::rules::{parse_condition, AlertConfiguration, Condition};
+    use crate::config::parse_config::{AlertRule, Alerting};
+    use crate::db::schema::{Addr, Interface, Stat};
+
+    fn rule(name: &str, interface: &str, condition: &str, for_s: u64) -> AlertRule {
+        AlertRule {
+            name: name.to_string(),
+            interface: Some(interface.to_string()),
+            condition: condition.to_string(),
+            for_s: Some(for_s)
+        }
+    }
+
+    fn alerting(rules: Vec<AlertRule>) -> Alerting {
+        Alerting {
+            webhooks: Some(vec!["http://127.0.0.1:9000/hooks".to_string()]),
+            commands: None,
+            rules: Some(rules)
+        }
+    }
+
+    fn engine(rules: Vec<AlertRule>) -> AlertEngine {
+        let config = AlertConfiguration::new(alerting(rules)).unwrap().unwrap();
+        AlertEngine::new("test", config.rules)
+    }
+
+    fn stat(interface: &str, rx_e: u64) -> Stat {
+        Stat {
+            server_id: "test".to_string(),
+            interface: interface.to_string(),
+            interval_ms: 1000,
+            rx_e,
+            ..Default::default()
+        }
+    }
+
+    fn link(name: &str, oper_state: &str) -> Interface {
+        Interface {
+            server_id: "test".to_string(),
+            netns: String::new(),
+            interface: name.to_string(),
+            ifindex: 2,
+            master_ifindex: None,
+            kind: None,
+            parent_ifindex: None,
+            bond_mode: None,
+            slave_state: None,
+            vlan_id: None,
+            vxlan_vni: None,
+            vxlan_remote: None,
+            mac: None,
+            oper_state: oper_state.to_string(),
+            admin_up: true,
+            carrier: oper_state == "up",
+            carrier_changes: 0,
+            mtu: 1500,
+            txqlen: 1000,
+            speed: None,
+            duplex: None
+        }
+    }
+
+    fn addr(name: &str, ip: Option<Ipv6Addr>) -> Addr {
+        Addr {
+            server_id: "test".to_string(),
+            netns: String::new(),
+            interface: name.to_string(),
+            ipv6: vec![(ip, ip.map(|_| 64))],
+            ipv6_peer: Vec::new()
+        }
+    }
+
+    fn statuses(notifications: &[Notification]) -> Vec<(&str, &str, &str)> {
+        notifications.iter()
+            .map(|notification| (notification.status.as_str(), notification.rule.as_str(), notification.interface.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_condition() {
+        assert_eq!(parse_condition("rx_e > 100/s"), Ok(Condition::Rate { metric: "rx_e".to_string(), above: true, threshold: 100.0 }));
+        assert_eq!(parse_condition(" tx<1e6 "), Ok(Condition::Rate { metric: "tx".to_string(), above: false, threshold: 1e6 }));
+        assert_eq!(parse_condition("down"), Ok(Condition::Down));
+        assert_eq!(parse_condition("no_address"), Ok(Condition::NoAddress));
+        assert!(parse_condition("rx_bytes > 10/s").is_err());
+        assert!(parse_condition("rx_e > many").is_err());
+        assert!(parse_condition("up").is_err());
+    }
+
+    #[test]
+    fn test_alert_configuration() {
+        assert!(AlertConfiguration::new(Alerting::default()).unwrap().is_none());
+
+        let config = AlertConfiguration::new(alerting(vec![rule("errors", "^eth", "rx_e > 100/s", 30)])).unwrap().unwrap();
+        assert_eq!(config.rules[0].hold, Duration::from_secs(30));
+        assert_eq!(config.rules[0].expression, "rx_e > 100/s");
+        assert!(config.rules[0].matches("eth1"));
+        assert!(!config.rules[0].matches("wg0"));
+
+        let duplicate = alerting(vec![rule("errors", "^eth", "down", 0), rule("errors", "^wg", "down", 0)]);
+        assert!(AlertConfiguration::new(duplicate).is_err());
+        assert!(AlertConfiguration::new(alerting(vec![rule("errors", "eth(", "down", 0)])).is_err());
+
+        let https = Alerting { webhooks: Some(vec!["https://example.com/hook".to_string()]), ..alerting(vec![rule("down", "^eth0$", "down", 0)]) };
+        assert!(AlertConfiguration::new(https).is_err());
+        let nowhere = Alerting { webhooks: None, ..alerting(vec![rule("down", "^eth0$", "down", 0)]) };
+        assert!(AlertConfiguration::new(nowhere).is_err());
+    }
+
+    #[test]
+    fn test_rate_alert() {
+        let mut engine = engine(vec![rule("errors", "^eth", "rx_e > 100/s", 30)]);
+        let start = Instant::now();
+        let at = |seconds: u64| start + Duration::from_secs(seconds);
+
+        // Has to hold for 30 seconds
+        assert!(engine.handle(AlertInput::Stats(vec![stat("eth0", 150), stat("wg0", 500)]), at(0), 1000).is_empty());
+        assert!(engine.handle(AlertInput::Stats(vec![stat("eth0", 150)]), at(10), 1010).is_empty());
+        assert!(engine.tick(at(20), 1020).is_empty());
+
+        let firing = engine.handle(AlertInput::Stats(vec![stat("eth0", 200)]), at(30), 1030);
+        assert_eq!(statuses(&firing), vec![("firing", "errors", "eth0")]);
+        assert_eq!(firing[0].value, Some(200.0));
+        assert_eq!((firing[0].since, firing[0].timestamp), (1000, 1030));
+        assert_eq!(firing[0].condition, "rx_e > 100/s");
+
+        // Notified once while it keeps firing
+        assert!(engine.handle(AlertInput::Stats(vec![stat("eth0", 300)]), at(31), 1031).is_empty());
+        assert!(engine.tick(at(32), 1032).is_empty());
+
+        let resolved = engine.handle(AlertInput::Stats(vec![stat("eth0", 10)]), at(33), 1033);
+        assert_eq!(statuses(&resolved), vec![("resolved", "errors", "eth0")]);
+        assert_eq!(resolved[0].value, Some(10.0));
+
+        // A dip below the threshold restarts the hold time
+        engine.handle(AlertInput::Stats(vec![stat("eth0", 150)]), at(40), 1040);
+        engine.handle(AlertInput::Stats(vec![stat("eth0", 50)]), at(60), 1060);
+        assert!(engine.handle(AlertInput::Stats(vec![stat("eth0", 150)]), at(75), 1075).is_empty());
+    }
+
+    #[test]
+    fn test_state_alerts() {
+        let mut engine = engine(vec![rule("down", "^eth", "down", 0), rule("address", "^wg0$", "no_address", 10)]);
+        let start = Instant::now();
+        let at = |seconds: u64| start + Duration::from_secs(seconds);
+        let snapshot = |links: Vec<Interface>, addrs: Vec<Addr>| AlertInput::Interfaces { netns: String::new(), links, addrs };
+        let wg0 = || link("wg0", "unknown");
+
+        let ip = Some(Ipv6Addr::new(0xfd00, 0, 0, 0, 0, 0, 0, 1));
+        let healthy = || snapshot(vec![link("eth0", "up"), wg0()], vec![addr("eth0", ip), addr("wg0", ip)]);
+        assert!(engine.handle(healthy(), at(0), 1000).is_empty());
+
+        // Fires right away without a hold time
+        let down = engine.handle(snapshot(vec![link("eth0", "lowerlayerdown"), wg0()], vec![addr("eth0", ip), addr("wg0", ip)]), at(1), 1001);
+        assert_eq!(statuses(&down), vec![("firing", "down", "eth0")]);
+        assert_eq!(down[0].value, None);
+
+        // The address is gone, the alert fires on the tick once held for 10 seconds
+        assert!(engine.handle(snapshot(vec![link("eth0", "lowerlayerdown"), wg0()], vec![addr("wg0", None)]), at(2), 1002).is_empty());
+        assert!(engine.tick(at(5), 1005).is_empty());
+        assert_eq!(statuses(&engine.tick(at(12), 1012)), vec![("firing", "address", "wg0")]);
+
+        // Deleted interfaces resolve their alerts
+        let mut resolved = engine.handle(snapshot(Vec::new(), Vec::new()), at(13), 1013);
+        resolved.sort_by(|a, b| a.rule.cmp(&b.rule));
+        assert_eq!(statuses(&resolved), vec![("resolved", "address", "wg0"), ("resolved", "down", "eth0")]);
+        assert!(engine.handle(healthy(), at(14), 1014).is_empty());
+    }
+}
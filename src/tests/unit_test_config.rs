@@ -0,0 +1,135 @@
+#[cfg(test)]
+mod config_tests {
+    use std::path::PathBuf;
+    use std::time::Duration;
+    use crate::config::config::CollectorConfiguration;
+    use crate::config::parse_config::Collector;
+    use crate::interface::netns::Namespace;
+    use crate::db::store::StoreKind;
+
+    #[test]
+    fn test_collector_defaults() {
+        let collector = CollectorConfiguration::new(Collector::default(), Collector::default()).unwrap();
+
+        assert_eq!(collector.stats_interval, Duration::from_secs(1));
+        assert_eq!(collector.refresh_interval, Duration::from_secs(60));
+        assert_eq!(collector.resync_interval, Duration::from_secs(60));
+        assert_eq!(collector.link_interval, Duration::from_secs(600));
+    }
+
+    #[test]
+    fn test_collector_cli_overrides_config() {
+        let cli = Collector {
+            stats_interval_ms: Some(100),
+            refresh_interval_ms: None,
+            resync_interval_ms: None,
+            ..Default::default()
+        };
+        let config = Collector {
+            stats_interval_ms: Some(500),
+            refresh_interval_ms: Some(30_000),
+            resync_interval_ms: None,
+            ..Default::default()
+        };
+
+        let collector = CollectorConfiguration::new(cli, config).unwrap();
+        assert_eq!(collector.stats_interval, Duration::from_millis(100));
+        assert_eq!(collector.refresh_interval, Duration::from_secs(30));
+        assert_eq!(collector.resync_interval, Duration::from_secs(60));
+    }
+
+    #[test]
+    fn test_collector_invalid_intervals() {
+        let stats = Collector { stats_interval_ms: Some(0), ..Default::default() };
+        assert!(CollectorConfiguration::new(stats, Collector::default()).is_err());
+
+        let refresh = Collector { refresh_interval_ms: Some(500), ..Default::default() };
+        assert!(CollectorConfiguration::new(Collector::default(), refresh).is_err());
+
+        let resync = Collector { resync_interval_ms: Some(0), ..Default::default() };
+        assert!(CollectorConfiguration::new(Collector::default(), resync).is_err());
+
+        let link = Collector { link_interval_ms: Some(500), ..Default::default() };
+        assert!(CollectorConfiguration::new(link, Collector::default()).is_err());
+    }
+
+    #[test]
+    fn test_namespace_parse() {
+        let named = Namespace::parse("blue").unwrap();
+        assert_eq!(named.name, "blue");
+        assert_eq!(named.path, Some(PathBuf::from("/var/run/netns/blue")));
+
+        let pid = Namespace::parse("1234").unwrap();
+        assert_eq!(pid.path, Some(PathBuf::from("/proc/1234/ns/net")));
+
+        let path = Namespace::parse("/run/docker/netns/abc").unwrap();
+        assert_eq!(path.name, "/run/docker/netns/abc");
+        assert_eq!(path.path, Some(PathBuf::from("/run/docker/netns/abc")));
+
+        assert!(!named.is_host());
+        assert!(Namespace::host().is_host());
+
+        assert!(Namespace::parse("").is_err());
+        assert!(Namespace::parse("..").is_err());
+        assert!(Namespace::parse("blue/red").is_err());
+    }
+
+    #[test]
+    fn test_collector_namespaces() {
+        let config = Collector { namespaces: Some(vec!["blue".to_string(), "42".to_string()]), ..Default::default() };
+        let collector = CollectorConfiguration::new(Collector::default(), config).unwrap();
+        assert_eq!(collector.namespaces.len(), 2);
+        assert_eq!(collector.namespaces[1].path, Some(PathBuf::from("/proc/42/ns/net")));
+
+        // The same namespace listed by name and by path
+        let duplicate = Collector {
+            namespaces: Some(vec!["blue".to_string(), "/var/run/netns/blue".to_string()]),
+            ..Default::default()
+        };
+        assert!(CollectorConfiguration::new(duplicate, Collector::default()).is_err());
+    }
+
+    #[test]
+    fn test_collector_flows() {
+        let collector = CollectorConfiguration::new(Collector::default(), Collector::default()).unwrap();
+        assert_eq!(collector.flow_interval, None);
+        assert_eq!((collector.flow_prefix_v4, collector.flow_prefix_v6), (24, 64));
+
+        let flows = Collector { flow_interval_ms: Some(5000), flow_prefix_v4: Some(32), ..Default::default() };
+        let collector = CollectorConfiguration::new(flows, Collector::default()).unwrap();
+        assert_eq!(collector.flow_interval, Some(Duration::from_secs(5)));
+        assert_eq!(collector.flow_prefix_v4, 32);
+
+        let too_often = Collector { flow_interval_ms: Some(100), ..Default::default() };
+        assert!(CollectorConfiguration::new(too_often, Collector::default()).is_err());
+        let prefix = Collector { flow_prefix_v6: Some(129), ..Default::default() };
+        assert!(CollectorConfiguration::new(Collector::default(), prefix).is_err());
+
+        let tcp = Collector { tcp_interval_ms: Some(15_000), ..Default::default() };
+        assert_eq!(CollectorConfiguration::new(Collector::default(), tcp).unwrap().tcp_interval, Some(Duration::from_secs(15)));
+        let tcp_too_often = Collector { tcp_interval_ms: Some(10), ..Default::default() };
+        assert!(CollectorConfiguration::new(tcp_too_often, Collector::default()).is_err());
+        let wg = Collector { wg_interval_ms: Some(30_000), ..Default::default() };
+        assert_eq!(CollectorConfiguration::new(wg, Collector::default()).unwrap().wg_interval, Some(Duration::from_secs(30)));
+        let proto = Collector { proto_interval_ms: Some(60_000), ..Default::default() };
+        assert_eq!(CollectorConfiguration::new(Collector::default(), proto).unwrap().proto_interval, Some(Duration::from_secs(60)));
+        let burst = Collector { burst_interval_ms: Some(100), ..Default::default() };
+        assert_eq!(CollectorConfiguration::new(burst, Collector::default()).unwrap().burst_interval, Some(Duration::from_millis(100)));
+        let burst_too_slow = Collector { stats_interval_ms: Some(1000), burst_interval_ms: Some(1000), ..Default::default() };
+        assert!(CollectorConfiguration::new(burst_too_slow, Collector::default()).is_err());
+        let memory = Collector { store: Some("memory".to_string()), ..Default::default() };
+        assert_eq!(CollectorConfiguration::new(Collector::default(), memory).unwrap().store, StoreKind::Memory);
+        let unknown_store = Collector { store: Some("sqlite".to_string()), ..Default::default() };
+        assert!(CollectorConfiguration::new(unknown_store, Collector::default()).is_err());
+        let otlp = Collector { store: Some("otlp".to_string()), otlp_endpoint: Some("http://127.0.0.1:4318".to_string()), ..Default::default() };
+        assert!(matches!(CollectorConfiguration::new(Collector::default(), otlp).unwrap().store, StoreKind::Otlp(_)));
+        let otlp_unused = Collector { otlp_endpoint: Some("http://127.0.0.1:4318".to_string()), ..Default::default() };
+        assert!(CollectorConfiguration::new(Collector::default(), otlp_unused).is_err());
+        let metrics = Collector { metrics_listen: Some("127.0.0.1:9469".to_string()), ..Default::default() };
+        assert_eq!(CollectorConfiguration::new(metrics, Collector::default()).unwrap().metrics_listen, Some("127.0.0.1:9469".parse().unwrap()));
+        let metrics_no_port = Collector { metrics_listen: Some("0.0.0.0".to_string()), ..Default::default() };
+        assert!(CollectorConfiguration::new(Collector::default(), metrics_no_port).is_err());
+        let qdisc_too_often = Collector { qdisc_interval_ms: Some(10), ..Default::default() };
+        assert!(CollectorConfiguration::new(Collector::default(), qdisc_too_often).is_err());
+    }
+}
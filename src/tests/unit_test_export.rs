@@ -0,0 +1,208 @@
+#[cfg(test)]
+mod export_tests {
+    use serde_json::Value;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+    use tokio::runtime::Runtime;
+    use crate::db::schema::{Addr, Server, Stat};
+    use crate::db::store::Store;
+    use crate::export::LatestStats;
+    use crate::export::otlp::{export_request, parse_endpoint, stat_metrics, OtlpStore};
+    use crate::export::prometheus::{escape, render_metrics};
+
+    fn server() -> Server {
+        Server {
+            server_id: "abc".to_string(),
+            hostname: "prg-1".to_string(),
+            label: "PRG".to_string(),
+            lat: 50.0833,
+            lng: 14.4667,
+            interface_filter: Vec::new(),
+            city: Some("Prague".to_string()),
+            country: None,
+            priority: Some(1),
+            center: Some(true)
+        }
+    }
+
+    fn stat(netns: &str, interface: &str, rx: u64, tx: u64) -> Stat {
+        Stat {
+            server_id: "abc".to_string(),
+            netns: netns.to_string(),
+            interface: interface.to_string(),
+            rx,
+            tx,
+            rx_crc_e: 3,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_render_metrics() {
+        let output = render_metrics(&server(), &[stat("", "eth0", 1000, 2000), stat("blue", "veth0", 5, 6)]);
+        let lines: Vec<&str> = output.lines().collect();
+
+        assert!(lines.contains(&"# TYPE network_map_server_info gauge"));
+        assert!(lines.contains(&"network_map_server_info{server_id=\"abc\",hostname=\"prg-1\",label=\"PRG\",city=\"Prague\",country=\"\",lat=\"50.0833\",lng=\"14.4667\",priority=\"1\",center=\"true\"} 1"));
+
+        assert!(lines.contains(&"# TYPE network_map_receive_bytes_total counter"));
+        assert!(lines.contains(&"network_map_receive_bytes_total{server_id=\"abc\",label=\"PRG\",netns=\"\",interface=\"eth0\"} 1000"));
+        assert!(lines.contains(&"network_map_transmit_bytes_total{server_id=\"abc\",label=\"PRG\",netns=\"blue\",interface=\"veth0\"} 6"));
+        assert!(lines.contains(&"network_map_receive_crc_errors_total{server_id=\"abc\",label=\"PRG\",netns=\"\",interface=\"eth0\"} 3"));
+
+        // Every family is declared once, before its samples
+        let families = lines.iter().filter(|line| line.starts_with("# TYPE")).count();
+        assert_eq!(families, 25);
+        assert_eq!(lines.iter().filter(|line| !line.starts_with('#')).count(), 1 + 24 * 2);
+    }
+
+    #[test]
+    fn test_escape_label() {
+        assert_eq!(escape("eth0"), "eth0");
+        assert_eq!(escape("a\"b\\c\nd"), "a\\\"b\\\\c\\nd");
+    }
+
+    #[test]
+    fn test_latest_stats() {
+        let disabled = LatestStats::default();
+        disabled.update(&[stat("", "eth0", 1, 1)]);
+        assert!(disabled.snapshot().is_empty());
+
+        let latest = LatestStats::enabled();
+        latest.update(&[stat("blue", "eth0", 1, 1), stat("", "eth1", 1, 1), stat("", "eth0", 1, 1)]);
+        let order: Vec<(String, String)> = latest.snapshot().into_iter().map(|stat| (stat.netns, stat.interface)).collect();
+        assert_eq!(order, vec![
+            (String::new(), "eth0".to_string()),
+            (String::new(), "eth1".to_string()),
+            ("blue".to_string(), "eth0".to_string()),
+        ]);
+
+        // The next tick replaces the previous one
+        latest.update(&[stat("", "eth0", 2, 2)]);
+        assert_eq!(latest.snapshot().len(), 1);
+        assert_eq!(latest.snapshot()[0].rx, 2);
+    }
+
+    // Stand-in for an OTLP collector: answers one request with the status and returns its path and body
+    async fn collector(status: &'static str) -> (String, tokio::task::JoinHandle<(String, Value)>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = format!("http://{}/v1/metrics", listener.local_addr().unwrap());
+
+        let handle = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            let mut buffer = [0; 4096];
+            let (head, length) = loop {
+                let read = socket.read(&mut buffer).await.unwrap();
+                request.extend_from_slice(&buffer[..read]);
+                let text = String::from_utf8_lossy(&request);
+                if let Some(end) = text.find("\r\n\r\n") {
+                    let length = text[..end].lines()
+                        .find_map(|line| line.to_lowercase().strip_prefix("content-length: ").map(|length| length.parse::<usize>().unwrap()))
+                        .unwrap();
+                    break (end + 4, length);
+                }
+            };
+            while request.len() < head + length {
+                let read = socket.read(&mut buffer).await.unwrap();
+                request.extend_from_slice(&buffer[..read]);
+            }
+            socket.write_all(format!("HTTP/1.1 {status}\r\ncontent-length: 0\r\n\r\n").as_bytes()).await.unwrap();
+
+            let path = String::from_utf8_lossy(&request).split_whitespace().nth(1).unwrap().to_string();
+            (path, serde_json::from_slice(&request[head..]).unwrap())
+        });
+        (endpoint, handle)
+    }
+
+    fn attribute<'a>(attributes: &'a Value, key: &str) -> &'a Value {
+        &attributes.as_array().unwrap().iter().find(|attribute| attribute["key"] == key).unwrap()["value"]
+    }
+
+    #[test]
+    fn test_otlp_request() {
+        let stats = vec![Stat { timestamp_ms: 10_000, interval_ms: 1000, rx_p: 7, ..stat("blue", "eth0", 1000, 2000) }];
+        let request = export_request(Some(&server()), stat_metrics(&stats));
+
+        let resource = &request["resourceMetrics"][0]["resource"]["attributes"];
+        assert_eq!(attribute(resource, "host.id")["stringValue"], "abc");
+        assert_eq!(attribute(resource, "host.name")["stringValue"], "prg-1");
+        assert_eq!(attribute(resource, "network_map.city")["stringValue"], "Prague");
+        assert!((attribute(resource, "network_map.lat")["doubleValue"].as_f64().unwrap() - 50.0833).abs() < 1e-4);
+        // No country configured
+        assert!(!resource.as_array().unwrap().iter().any(|attribute| attribute["key"] == "network_map.country"));
+
+        let metrics = request["resourceMetrics"][0]["scopeMetrics"][0]["metrics"].as_array().unwrap();
+        assert_eq!(metrics.len(), 4);
+        let io = &metrics[0];
+        assert_eq!(io["name"], "system.network.io");
+        assert_eq!(io["sum"]["aggregationTemporality"], 1);
+        assert_eq!(io["sum"]["isMonotonic"], true);
+
+        let points = io["sum"]["dataPoints"].as_array().unwrap();
+        assert_eq!(points.len(), 2);
+        assert_eq!(points[0]["asInt"], "1000");
+        assert_eq!(points[1]["asInt"], "2000");
+        assert_eq!(attribute(&points[1]["attributes"], "network.io.direction")["stringValue"], "transmit");
+        assert_eq!(attribute(&points[0]["attributes"], "network_map.netns")["stringValue"], "blue");
+        assert_eq!(points[0]["startTimeUnixNano"], "9000000000");
+        assert_eq!(points[0]["timeUnixNano"], "10000000000");
+        assert_eq!(metrics[1]["sum"]["dataPoints"][0]["asInt"], "7");
+    }
+
+    #[test]
+    fn test_otlp_store_exports_to_collector() {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async {
+            let (endpoint, received) = collector("200 OK").await;
+            let store = OtlpStore::new(endpoint.parse().unwrap());
+
+            assert!(!store.server_exists(&server()).await.unwrap());
+            store.add_server(server()).await.unwrap();
+            store.add_addresses(vec![Addr {
+                server_id: "abc".to_string(),
+                netns: String::new(),
+                interface: "eth0".to_string(),
+                ipv6: vec![(Some("192.0.2.1".parse::<std::net::Ipv4Addr>().unwrap().to_ipv6_mapped()), Some(24))],
+                ipv6_peer: Vec::new()
+            }]).await.unwrap();
+            assert_eq!(store.get_addresses(&server(), "").await.unwrap().len(), 1);
+
+            store.add_stats(vec![Stat { timestamp_ms: 5000, interval_ms: 1000, ..stat("", "eth0", 10, 20) }]).await.unwrap();
+
+            let (path, request) = received.await.unwrap();
+            assert_eq!(path, "/v1/metrics");
+            let resource = &request["resourceMetrics"][0]["resource"]["attributes"];
+            assert_eq!(attribute(resource, "network_map.server_id")["stringValue"], "abc");
+
+            let metrics = request["resourceMetrics"][0]["scopeMetrics"][0]["metrics"].as_array().unwrap();
+            let address = metrics.iter().find(|metric| metric["name"] == "network_map.interface.address").unwrap();
+            let point = &address["gauge"]["dataPoints"][0];
+            assert_eq!(attribute(&point["attributes"], "network_map.address")["stringValue"], "192.0.2.1");
+            assert_eq!(attribute(&point["attributes"], "network_map.prefix")["intValue"], "24");
+            assert_eq!(point["timeUnixNano"], "5000000000");
+        });
+    }
+
+    #[test]
+    fn test_otlp_store_reports_collector_errors() {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async {
+            let (endpoint, received) = collector("503 Service Unavailable").await;
+            let store = OtlpStore::new(endpoint.parse().unwrap());
+
+            // The error lets the stats loop spool the batch
+            assert!(store.add_stats(vec![stat("", "eth0", 1, 1)]).await.is_err());
+            received.await.unwrap();
+        });
+    }
+
+    #[test]
+    fn test_parse_endpoint() {
+        // The metrics path is added when the endpoint has none
+        assert_eq!(parse_endpoint("http://127.0.0.1:4318"), Ok("http://127.0.0.1:4318/v1/metrics".parse().unwrap()));
+        assert_eq!(parse_endpoint("http://collector/otlp/v1/metrics"), Ok("http://collector/otlp/v1/metrics".parse().unwrap()));
+        assert!(parse_endpoint("https://collector:4318").is_err());
+        assert!(parse_endpoint("collector:4318").is_err());
+    }
+}
@@ -0,0 +1,166 @@
+#[cfg(test)]
+mod flow_tests {
+    use std::collections::{HashMap, HashSet};
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+    use crate::db::schema::FlowStat;
+    use crate::flow::conntrack::{parse_message, Flow};
+    use crate::interface::netlink::{parse_messages, DumpId};
+    use crate::tests::helpers::{message, nested, nla};
+    use crate::flow::get_flows::{aggregate_flows, flow_deltas};
+    use crate::interface::remote::remote_network;
+
+    fn template() -> FlowStat {
+        FlowStat {
+            server_id: "test".to_string(),
+            netns: String::new(),
+            timestamp: 1000,
+            timestamp_ms: 1_000_000,
+            interval_ms: 10_000,
+            remote: Ipv6Addr::UNSPECIFIED,
+            remote_prefix: 0,
+            peer_server_id: None,
+            flows: 0,
+            rx: 0,
+            tx: 0,
+            rx_p: 0,
+            tx_p: 0
+        }
+    }
+
+    fn flow(id: u32, src: [u8; 4], dst: [u8; 4], orig_bytes: u64, reply_bytes: u64) -> Flow {
+        Flow {
+            id,
+            protocol: 6,
+            src: Some(IpAddr::V4(Ipv4Addr::from(src))),
+            dst: Some(IpAddr::V4(Ipv4Addr::from(dst))),
+            orig_bytes,
+            orig_packets: orig_bytes / 100,
+            reply_bytes,
+            reply_packets: reply_bytes / 100,
+            has_counters: true
+        }
+    }
+
+    #[test]
+    fn test_parse_conntrack_dump() {
+        let tuple = nested(1, &[
+            nested(1, &[nla(1, &[10, 0, 0, 1]), nla(2, &[192, 0, 2, 7])]),
+            nested(2, &[nla(1, &[6])]),
+        ]);
+        let counters = |packets: u64, bytes: u64| vec![nla(1, &packets.to_be_bytes()), nla(2, &bytes.to_be_bytes())];
+        let attributes = [
+            tuple,
+            nested(9, &counters(3, 300)),
+            nested(10, &counters(2, 1200)),
+            nla(12, &42u32.to_be_bytes()),
+        ].concat();
+
+        // nfgenmsg followed by the attributes
+        let id = DumpId { sequence: 7, port: 1234 };
+        let entry = message(0x100, 2, id, &[&[2u8, 0, 0, 0][..], &attributes].concat());
+        let done = message(3, 2, id, &0i32.to_ne_bytes());
+
+        let mut flows = Vec::new();
+        assert!(!parse_messages(&entry, id, |kind, payload| flows.extend(parse_message(kind, payload))).unwrap());
+        assert!(parse_messages(&done, id, |kind, payload| flows.extend(parse_message(kind, payload))).unwrap());
+
+        assert_eq!(flows, vec![Flow {
+            id: 42,
+            protocol: 6,
+            src: Some(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1))),
+            dst: Some(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 7))),
+            orig_bytes: 300,
+            orig_packets: 3,
+            reply_bytes: 1200,
+            reply_packets: 2,
+            has_counters: true
+        }]);
+
+        // EPERM without CAP_NET_ADMIN
+        let error = message(2, 0, id, &(-1i32).to_ne_bytes());
+        assert!(parse_messages(&error, id, |_, _| ()).is_err());
+
+        // Leftovers of an earlier dump are skipped, its end doesn't end this one
+        let earlier = DumpId { sequence: 6, port: 1234 };
+        let mut flows = Vec::new();
+        let leftovers = [message(0x100, 2, earlier, &[&[2u8, 0, 0, 0][..], &attributes].concat()),
+            message(3, 2, earlier, &0i32.to_ne_bytes())].concat();
+        assert!(!parse_messages(&leftovers, id, |kind, payload| flows.extend(parse_message(kind, payload))).unwrap());
+        assert!(flows.is_empty());
+
+        // NLM_F_DUMP_INTR, the table changed during the dump
+        let interrupted = message(0x100, 2 | 0x10, id, &[&[2u8, 0, 0, 0][..], &attributes].concat());
+        assert!(parse_messages(&interrupted, id, |_, _| ()).is_err());
+    }
+
+    #[test]
+    fn test_flow_deltas() {
+        let previous = HashMap::from([
+            (1, flow(1, [10, 0, 0, 1], [192, 0, 2, 7], 1000, 5000)),
+            (2, flow(2, [10, 0, 0, 1], [192, 0, 2, 8], 500, 500)),
+            (3, flow(3, [10, 0, 0, 1], [192, 0, 2, 9], 9000, 9000)),
+        ]);
+        let current = HashMap::from([
+            (1, flow(1, [10, 0, 0, 1], [192, 0, 2, 7], 1500, 8000)),
+            // Idle
+            (2, flow(2, [10, 0, 0, 1], [192, 0, 2, 8], 500, 500)),
+            // Id reused by a new connection
+            (3, flow(3, [10, 0, 0, 1], [198, 51, 100, 1], 100, 200)),
+            (4, flow(4, [10, 0, 0, 1], [198, 51, 100, 2], 300, 400)),
+        ]);
+
+        let mut deltas = flow_deltas(&previous, &current);
+        deltas.sort_by_key(|flow| flow.id);
+
+        assert_eq!(deltas.len(), 3);
+        assert_eq!((deltas[0].orig_bytes, deltas[0].reply_bytes), (500, 3000));
+        assert_eq!((deltas[1].orig_bytes, deltas[1].reply_bytes), (100, 200));
+        assert_eq!((deltas[2].orig_bytes, deltas[2].reply_bytes), (300, 400));
+    }
+
+    #[test]
+    fn test_aggregate_flows() {
+        let local = HashSet::from([IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)), IpAddr::V4(Ipv4Addr::LOCALHOST)]);
+        let peers = HashMap::from([(Ipv4Addr::new(203, 0, 113, 5).to_ipv6_mapped(), "peer-1".to_string())]);
+
+        let deltas = vec![
+            // Outbound to the same /24
+            flow(1, [10, 0, 0, 1], [192, 0, 2, 7], 100, 1000),
+            flow(2, [10, 0, 0, 1], [192, 0, 2, 8], 200, 2000),
+            // Inbound from a peer server
+            flow(3, [203, 0, 113, 5], [10, 0, 0, 1], 300, 3000),
+            // Loopback
+            flow(4, [127, 0, 0, 1], [127, 0, 0, 1], 400, 400),
+        ];
+
+        let mut totals = aggregate_flows(&deltas, &local, &peers, 24, 64, &template());
+        totals.sort_by_key(|total| total.remote);
+        assert_eq!(totals.len(), 2);
+
+        let network = &totals[0];
+        assert_eq!(network.remote, Ipv4Addr::new(192, 0, 2, 0).to_ipv6_mapped());
+        assert_eq!(network.remote_prefix, 24);
+        assert_eq!(network.peer_server_id, None);
+        assert_eq!(network.flows, 2);
+        assert_eq!((network.tx, network.rx), (300, 3000));
+        assert_eq!(network.timestamp, 1000);
+
+        let peer = &totals[1];
+        assert_eq!(peer.remote, Ipv4Addr::new(203, 0, 113, 5).to_ipv6_mapped());
+        assert_eq!(peer.remote_prefix, 32);
+        assert_eq!(peer.peer_server_id.as_deref(), Some("peer-1"));
+        assert_eq!((peer.rx, peer.tx), (300, 3000));
+        assert_eq!((peer.rx_p, peer.tx_p), (3, 30));
+    }
+
+    #[test]
+    fn test_remote_network() {
+        let v6: Ipv6Addr = "2001:db8:1:2:3:4:5:6".parse().unwrap();
+        assert_eq!(remote_network(IpAddr::V6(v6), 24, 48), ("2001:db8:1::".parse().unwrap(), 48));
+        assert_eq!(remote_network(IpAddr::V6(v6), 24, 0), (Ipv6Addr::UNSPECIFIED, 0));
+        assert_eq!(remote_network(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 200)), 32, 64),
+            (Ipv4Addr::new(192, 0, 2, 200).to_ipv6_mapped(), 32));
+        assert_eq!(remote_network(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 200)), 16, 64),
+            (Ipv4Addr::new(192, 0, 0, 0).to_ipv6_mapped(), 16));
+    }
+}
@@ -1,11 +1,14 @@
 #[cfg(test)]
 mod stats_tests {
     use crate::db::schema::Stat;
-    use crate::interface::get_stats::save_stat;
+    use crate::interface::get_stats::{counter_delta, counter_delta32, save_stat};
     use std::collections::HashMap;
     use std::sync::Arc;
     use tokio::runtime::Runtime;
     use tokio::sync::Mutex;
+    use std::time::Duration;
+
+    const INTERVAL: Duration = Duration::from_secs(1);
 
 
     #[test]
@@ -13,7 +16,7 @@ mod stats_tests {
         let rt = Runtime::new().unwrap();
         rt.block_on(async {
             // Setup
-            let last_stats = Arc::new(Mutex::new(HashMap::<String, Option<Stat>>::new()));
+            let last_stats = Arc::new(Mutex::new(HashMap::<(String, String), Option<Stat>>::new()));
 
             // Create current stats
             let current_stats = vec![
@@ -21,6 +24,7 @@ mod stats_tests {
                     server_id: "test-server".to_string(),
                     interface: "eth0".to_string(),
                     timestamp: 1000,
+                    ifindex: 1,
                     rx: 2000,
                     tx: 3000,
                     rx_p: 200,
@@ -28,13 +32,14 @@ mod stats_tests {
                     rx_d: 20,
                     tx_d: 30,
                     rx_e: 2,
-                    tx_e: 3
+                    tx_e: 3,
+                    ..Default::default()
                 }
             ];
 
-            let result1 = save_stat(Arc::clone(&last_stats), current_stats.clone()).await;
+            let result1 = save_stat(Arc::clone(&last_stats), current_stats.clone(), INTERVAL).await;
             assert!(result1.is_some());
-            let final_stats1 = result1.unwrap();
+            let final_stats1 = result1.unwrap().stats;
             assert_eq!(final_stats1.len(), 0);
 
             let updated_stats = vec![
@@ -42,6 +47,7 @@ mod stats_tests {
                     server_id: "test-server".to_string(),
                     interface: "eth0".to_string(),
                     timestamp: 1001,
+                    ifindex: 1,
                     rx: 2500,    // +500
                     tx: 3800,    // +800
                     rx_p: 250,   // +50
@@ -49,14 +55,15 @@ mod stats_tests {
                     rx_d: 25,    // +5
                     tx_d: 38,    // +8
                     rx_e: 4,     // +2
-                    tx_e: 6      // +3
+                    tx_e: 6,     // +3
+                    ..Default::default()
                 }
             ];
 
             // Second call should produce stats with differences
-            let result2 = save_stat(Arc::clone(&last_stats), updated_stats).await;
+            let result2 = save_stat(Arc::clone(&last_stats), updated_stats, INTERVAL).await;
             assert!(result2.is_some());
-            let final_stats2 = result2.unwrap();
+            let final_stats2 = result2.unwrap().stats;
             assert_eq!(final_stats2.len(), 1);
 
             // Check that the difference calculations are correct
@@ -79,13 +86,14 @@ mod stats_tests {
     fn test_save_stat_multiple_interfaces() {
         let rt = Runtime::new().unwrap();
         rt.block_on(async {
-            let last_stats = Arc::new(Mutex::new(HashMap::<String, Option<Stat>>::new()));
+            let last_stats = Arc::new(Mutex::new(HashMap::<(String, String), Option<Stat>>::new()));
 
             let initial_stats = vec![
                 Stat {
                     server_id: "test-server".to_string(),
                     interface: "eth0".to_string(),
                     timestamp: 1000,
+                    ifindex: 1,
                     rx: 1000,
                     tx: 2000,
                     rx_p: 100,
@@ -93,12 +101,14 @@ mod stats_tests {
                     rx_d: 10,
                     tx_d: 20,
                     rx_e: 1,
-                    tx_e: 2
+                    tx_e: 2,
+                    ..Default::default()
                 },
                 Stat {
                     server_id: "test-server".to_string(),
                     interface: "eth1".to_string(),
                     timestamp: 1000,
+                    ifindex: 1,
                     rx: 3000,
                     tx: 4000,
                     rx_p: 300,
@@ -106,16 +116,18 @@ mod stats_tests {
                     rx_d: 30,
                     tx_d: 40,
                     rx_e: 3,
-                    tx_e: 4
+                    tx_e: 4,
+                    ..Default::default()
                 }
             ];
 
-            let _ = save_stat(Arc::clone(&last_stats), initial_stats).await;
+            let _ = save_stat(Arc::clone(&last_stats), initial_stats, INTERVAL).await;
             let updated_stats = vec![
                 Stat {
                     server_id: "test-server".to_string(),
                     interface: "eth0".to_string(),
                     timestamp: 1001,
+                    ifindex: 1,
                     rx: 1500,    // +500
                     tx: 2800,    // +800
                     rx_p: 150,   // +50
@@ -123,12 +135,14 @@ mod stats_tests {
                     rx_d: 15,    // +5
                     tx_d: 28,    // +8
                     rx_e: 3,     // +2
-                    tx_e: 5      // +3
+                    tx_e: 5,     // +3
+                    ..Default::default()
                 },
                 Stat {
                     server_id: "test-server".to_string(),
                     interface: "eth1".to_string(),
                     timestamp: 1001,
+                    ifindex: 1,
                     rx: 3100,    // +100
                     tx: 4200,    // +200
                     rx_p: 310,   // +10
@@ -136,13 +150,14 @@ mod stats_tests {
                     rx_d: 31,    // +1
                     tx_d: 42,    // +2
                     rx_e: 4,     // +1
-                    tx_e: 6      // +2
+                    tx_e: 6,     // +2
+                    ..Default::default()
                 }
             ];
 
-            let result = save_stat(Arc::clone(&last_stats), updated_stats).await;
+            let result = save_stat(Arc::clone(&last_stats), updated_stats, INTERVAL).await;
             assert!(result.is_some());
-            let final_stats = result.unwrap();
+            let final_stats = result.unwrap().stats;
             assert_eq!(final_stats.len(), 2);
 
             let mut sorted_stats = final_stats;
@@ -159,4 +174,285 @@ mod stats_tests {
             assert_eq!(eth1_diff.tx, 200);
         });
     }
+
+    fn stat(timestamp: u32, ifindex: u32, rx: u64, tx: u64) -> Stat {
+        Stat {
+            server_id: "test-server".to_string(),
+            interface: "eth0".to_string(),
+            timestamp,
+            ifindex,
+            rx,
+            tx,
+            rx_p: 10,
+            tx_p: 10,
+            rx_d: 0,
+            tx_d: 0,
+            rx_e: 0,
+            tx_e: 0,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_counter_delta() {
+        assert_eq!(counter_delta(150, 100), Some(50));
+        assert_eq!(counter_delta(100, 100), Some(0));
+
+        // A reset of a 64-bit byte counter sitting near the top of the 32-bit range
+        let max = u32::MAX as u64;
+        assert_eq!(counter_delta(9, max - 10), None);
+        assert_eq!(counter_delta(5, 1_000), None);
+        assert_eq!(counter_delta(5, max + 1_000), None);
+    }
+
+    #[test]
+    fn test_counter_delta32() {
+        assert_eq!(counter_delta32(150, 100), Some(50));
+
+        // Wraparound
+        let max = u32::MAX as u64;
+        assert_eq!(counter_delta32(9, max - 10), Some(20));
+
+        // Reset
+        assert_eq!(counter_delta32(5, 1_000), None);
+        assert_eq!(counter_delta32(5, max + 1_000), None);
+    }
+
+    #[test]
+    fn test_save_stat_counter_reset() {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async {
+            let last_stats = Arc::new(Mutex::new(HashMap::<(String, String), Option<Stat>>::new()));
+
+            save_stat(Arc::clone(&last_stats), vec![stat(1000, 1, 5000, 6000)], INTERVAL).await;
+
+            // Counter went down: no stat, one reset event
+            let samples = save_stat(Arc::clone(&last_stats), vec![stat(1001, 1, 100, 6100)], INTERVAL).await.unwrap();
+            assert_eq!(samples.stats.len(), 0);
+            assert_eq!(samples.resets.len(), 1);
+            assert_eq!(samples.resets[0].interface, "eth0");
+            assert_eq!(samples.resets[0].timestamp, 1001);
+            assert_eq!(samples.resets[0].reason, "counter");
+
+            // The reset sample becomes the new baseline
+            let samples = save_stat(Arc::clone(&last_stats), vec![stat(1002, 1, 300, 6300)], INTERVAL).await.unwrap();
+            assert_eq!(samples.resets.len(), 0);
+            assert_eq!(samples.stats.len(), 1);
+            assert_eq!(samples.stats[0].rx, 200);
+            assert_eq!(samples.stats[0].tx, 200);
+        });
+    }
+
+    #[test]
+    fn test_save_stat_ifindex_changed() {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async {
+            let last_stats = Arc::new(Mutex::new(HashMap::<(String, String), Option<Stat>>::new()));
+
+            save_stat(Arc::clone(&last_stats), vec![stat(1000, 1, 100, 100)], INTERVAL).await;
+
+            // Interface recreated with the same name, counters happen to be higher
+            let samples = save_stat(Arc::clone(&last_stats), vec![stat(1001, 7, 500, 500)], INTERVAL).await.unwrap();
+            assert_eq!(samples.stats.len(), 0);
+            assert_eq!(samples.resets.len(), 1);
+            assert_eq!(samples.resets[0].reason, "ifindex");
+
+            let samples = save_stat(Arc::clone(&last_stats), vec![stat(1002, 7, 600, 650)], INTERVAL).await.unwrap();
+            assert_eq!(samples.stats.len(), 1);
+            assert_eq!(samples.stats[0].rx, 100);
+            assert_eq!(samples.stats[0].tx, 150);
+        });
+    }
+
+    #[test]
+    fn test_save_stat_detailed_counters() {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async {
+            let last_stats = Arc::new(Mutex::new(HashMap::<(String, String), Option<Stat>>::new()));
+
+            let mut initial = stat(1000, 1, 100, 100);
+            initial.multicast = 10;
+            initial.rx_crc_e = 3;
+            initial.rx_over_e = 7;
+            initial.tx_carrier_e = 1;
+            initial.rx_nohandler = 4;
+            save_stat(Arc::clone(&last_stats), vec![initial], INTERVAL).await;
+
+            let mut updated = stat(1001, 1, 200, 200);
+            updated.multicast = 15;
+            updated.rx_crc_e = 9;
+            updated.rx_over_e = 7;
+            updated.tx_carrier_e = 2;
+            updated.rx_nohandler = 6;
+            let samples = save_stat(Arc::clone(&last_stats), vec![updated], INTERVAL).await.unwrap();
+
+            assert_eq!(samples.stats.len(), 1);
+            let diff_stat = &samples.stats[0];
+            assert_eq!(diff_stat.multicast, 5);
+            assert_eq!(diff_stat.rx_crc_e, 6);
+            assert_eq!(diff_stat.rx_over_e, 0);
+            assert_eq!(diff_stat.tx_carrier_e, 1);
+            assert_eq!(diff_stat.rx_nohandler, 2);
+        });
+    }
+
+    #[test]
+    fn test_get_stats_from_link() {
+        use crate::interface::get_stats::get_stats;
+        use netlink_packet_route::link::{LinkAttribute, LinkMessage, Stats64, Stats64Buffer};
+        use netlink_packet_utils::traits::Parseable;
+        use std::time::Duration;
+
+        let mut bytes = [0u8; 200];
+        let mut buffer = Stats64Buffer::new(&mut bytes[..]);
+        buffer.set_rx_bytes(2000);
+        buffer.set_tx_bytes(3000);
+        buffer.set_rx_crc_errors(4);
+        let stats64 = Stats64::parse(&Stats64Buffer::new(&bytes[..])).unwrap();
+
+        let mut link = LinkMessage::default();
+        link.header.index = 5;
+        link.attributes.push(LinkAttribute::IfName("eth0".to_string()));
+        link.attributes.push(LinkAttribute::Stats64(stats64));
+
+        let timestamp = Duration::from_millis(1_000_250);
+        let stat = get_stats(&link, "test-server", "", timestamp).unwrap();
+        assert_eq!(stat.server_id, "test-server");
+        assert_eq!(stat.interface, "eth0");
+        assert_eq!(stat.ifindex, 5);
+        assert_eq!(stat.timestamp, 1000);
+        assert_eq!(stat.timestamp_ms, 1_000_250);
+        assert_eq!(stat.rx, 2000);
+        assert_eq!(stat.tx, 3000);
+        assert_eq!(stat.rx_crc_e, 4);
+
+        // Links without Stats64 are skipped
+        let mut link = LinkMessage::default();
+        link.attributes.push(LinkAttribute::IfName("eth1".to_string()));
+        assert!(get_stats(&link, "test-server", "", timestamp).is_none());
+    }
+
+    #[test]
+    fn test_stat_delta_rates() {
+        use crate::interface::get_stats::stat_delta;
+        use std::time::Instant;
+
+        let start = Instant::now();
+        let mut old = stat(1000, 1, 1000, 1000);
+        old.instant = Some(start);
+
+        // On schedule: 500 bytes in 500ms
+        let mut curr = stat(1000, 1, 1500, 2000);
+        curr.instant = Some(start + Duration::from_millis(500));
+        let diff_stat = stat_delta(&curr, &old, Duration::from_millis(500)).unwrap();
+        assert_eq!(diff_stat.interval_ms, 500);
+        assert_eq!(diff_stat.missed_ticks, 0);
+        assert_eq!(diff_stat.rx_rate, 1000.0);
+        assert_eq!(diff_stat.tx_rate, 2000.0);
+
+        // Two ticks missed: 3 seconds passed with a 1 second interval
+        curr.instant = Some(start + Duration::from_millis(3000));
+        let diff_stat = stat_delta(&curr, &old, INTERVAL).unwrap();
+        assert_eq!(diff_stat.interval_ms, 3000);
+        assert_eq!(diff_stat.missed_ticks, 2);
+        assert!((diff_stat.rx_rate - 500.0 / 3.0).abs() < 1e-9);
+
+        // Falls back to wall clock without monotonic time
+        old.instant = None;
+        old.timestamp_ms = 1_000_000;
+        curr.instant = None;
+        curr.timestamp_ms = 1_002_000;
+        let diff_stat = stat_delta(&curr, &old, INTERVAL).unwrap();
+        assert_eq!(diff_stat.interval_ms, 2000);
+        assert_eq!(diff_stat.missed_ticks, 1);
+        assert_eq!(diff_stat.rx_rate, 250.0);
+    }
+
+    #[test]
+    fn test_baselines() {
+        use crate::interface::sample::Baselines;
+
+        let mut baselines = Baselines::default();
+        let delta = |curr: &u64, old: &u64| curr.checked_sub(*old);
+
+        // Only a baseline, per key
+        assert_eq!(baselines.delta("a", 10, delta), None);
+        assert_eq!(baselines.delta("b", 100, delta), None);
+        assert_eq!(baselines.delta("a", 15, delta), Some(5));
+        // A reset is skipped and becomes the new baseline
+        assert_eq!(baselines.delta("b", 3, delta), None);
+        assert_eq!(baselines.delta("b", 7, delta), Some(4));
+
+        // A key that wasn't seen is forgotten and starts over
+        baselines.retain(|key| *key == "b");
+        assert_eq!(baselines.delta("a", 20, delta), None);
+        assert_eq!(baselines.delta("b", 8, delta), Some(1));
+    }
+
+    #[test]
+    fn test_save_stat_namespaces() {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async {
+            let last_stats = Arc::new(Mutex::new(HashMap::<(String, String), Option<Stat>>::new()));
+            let in_netns = |timestamp, ifindex, rx, tx| Stat { netns: "blue".to_string(), ..stat(timestamp, ifindex, rx, tx) };
+
+            // eth0 exists in both namespaces with unrelated counters and indexes
+            save_stat(Arc::clone(&last_stats), vec![stat(1000, 1, 100, 100), in_netns(1000, 2, 5000, 5000)], INTERVAL).await;
+            let samples = save_stat(Arc::clone(&last_stats), vec![stat(1001, 1, 150, 200), in_netns(1001, 2, 5100, 5300)], INTERVAL).await.unwrap();
+
+            assert!(samples.resets.is_empty());
+            assert_eq!(samples.stats.len(), 2);
+            let host = samples.stats.iter().find(|stat| stat.netns.is_empty()).unwrap();
+            assert_eq!((host.rx, host.tx), (50, 100));
+            let blue = samples.stats.iter().find(|stat| stat.netns == "blue").unwrap();
+            assert_eq!((blue.rx, blue.tx), (100, 300));
+        });
+    }
+
+    #[test]
+    fn test_burst_windows() {
+        use crate::interface::get_stats::{add_burst_samples, apply_burst_windows, BurstWindows};
+        use std::time::Instant;
+
+        let burst = Duration::from_millis(100);
+        let start = Instant::now();
+        let at = |ms: u64, ifindex, rx, tx| Stat { instant: Some(start + Duration::from_millis(ms)), ..stat(1000, ifindex, rx, tx) };
+        let mut windows = BurstWindows::new();
+
+        // A 10 KB burst in the second window, then the counters were reset
+        add_burst_samples(&mut windows, &[at(0, 1, 0, 0)], burst);
+        add_burst_samples(&mut windows, &[at(100, 1, 100, 0)], burst);
+        add_burst_samples(&mut windows, &[at(200, 1, 10_100, 50)], burst);
+        // Lands with the previous tick, too close to give a rate
+        add_burst_samples(&mut windows, &[at(210, 1, 10_200, 50)], burst);
+        add_burst_samples(&mut windows, &[at(300, 1, 10_300, 50)], burst);
+        add_burst_samples(&mut windows, &[at(400, 1, 5, 0)], burst);
+        add_burst_samples(&mut windows, &[at(500, 1, 105, 10)], burst);
+        // Recreated interface
+        add_burst_samples(&mut windows, &[at(600, 7, 1_000_000, 0)], burst);
+
+        let window = &windows[&(String::new(), "eth0".to_string())];
+        assert_eq!(window.rx_rates, vec![1000.0, 100_000.0, 2000.0, 1000.0]);
+        assert_eq!(window.tx_rates, vec![0.0, 500.0, 0.0, 100.0]);
+
+        let mut stats = vec![stat(1001, 7, 100, 100), Stat { interface: "eth1".to_string(), ..stat(1001, 3, 100, 100) }];
+        apply_burst_windows(&mut windows, &mut stats);
+        assert_eq!((stats[0].rx_rate_min, stats[0].rx_rate_max, stats[0].rx_rate_p99), (1000.0, 100_000.0, 100_000.0));
+        assert_eq!((stats[0].tx_rate_min, stats[0].tx_rate_max), (0.0, 500.0));
+        // No sub-samples
+        assert_eq!((stats[1].rx_rate_min, stats[1].rx_rate_max, stats[1].rx_rate_p99), (0.0, 0.0, 0.0));
+
+        // The next window starts empty
+        assert!(windows[&(String::new(), "eth0".to_string())].rx_rates.is_empty());
+    }
+
+    #[test]
+    fn test_rate_summary() {
+        use crate::interface::get_stats::rate_summary;
+
+        assert_eq!(rate_summary(&mut []), (0.0, 0.0, 0.0));
+        assert_eq!(rate_summary(&mut [5.0]), (5.0, 5.0, 5.0));
+        let mut rates: Vec<f64> = (1..=200).rev().map(f64::from).collect();
+        assert_eq!(rate_summary(&mut rates), (1.0, 200.0, 198.0));
+    }
 }
@@ -28,8 +28,7 @@ mod tests {
     #[test]
     fn test_get_loopback_from_header() {
         // Test loopback interface
-        let mut flags = Vec::new();
-        flags.push(LinkFlag::Loopback);
+        let flags = vec![LinkFlag::Loopback];
 
         let header = LinkHeader {
             index: 1,
@@ -61,6 +60,7 @@ mod tests {
         // Create test addresses
         let addr1 = Addr {
             server_id: "test".to_string(),
+            netns: String::new(),
             interface: "eth0".to_string(),
             ipv6: vec![(Some(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 1)), Some(128))],
             ipv6_peer: vec![]
@@ -68,6 +68,7 @@ mod tests {
 
         let addr2 = Addr {
             server_id: "test".to_string(),
+            netns: String::new(),
             interface: "eth1".to_string(),
             ipv6: vec![(Some(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 2)), Some(128))],
             ipv6_peer: vec![]
@@ -76,6 +77,7 @@ mod tests {
         // Same as addr1 but different IP
         let addr3 = Addr {
             server_id: "test".to_string(),
+            netns: String::new(),
             interface: "eth0".to_string(),
             ipv6: vec![(Some(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 3)), Some(128))],
             ipv6_peer: vec![]
@@ -123,6 +125,7 @@ mod tests {
             // Address with empty IP arrays
         let empty_addr = Addr {
             server_id: "test".to_string(),
+            netns: String::new(),
             interface: "eth0".to_string(),
             ipv6: vec![],
             ipv6_peer: vec![]
@@ -131,6 +134,7 @@ mod tests {
         // Address with valid IP
         let valid_addr = Addr {
             server_id: "test".to_string(),
+            netns: String::new(),
             interface: "eth0".to_string(),
             ipv6: vec![(Some(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 1)), Some(128))],
             ipv6_peer: vec![]
@@ -160,6 +164,7 @@ mod tests {
 
         let wlp1s0_empty = Addr {
             server_id: "2420549211b547559bef4ab3e5e25571".to_string(),
+            netns: String::new(),
             interface: "wlp1s0".to_string(),
             ipv6: vec![],
             ipv6_peer: vec![]
@@ -167,6 +172,7 @@ mod tests {
 
         let wlp1s0_with_ip = Addr {
             server_id: "2420549211b547559bef4ab3e5e25571".to_string(),
+            netns: String::new(),
             interface: "wlp1s0".to_string(),
             ipv6: vec![(Some(Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 1)), Some(64))],
             ipv6_peer: vec![]
@@ -186,4 +192,306 @@ mod tests {
 
         assert_eq!(valid_updates.len(), 0);
     }
+
+    #[test]
+    fn test_is_interface_matching() {
+        use crate::interface::info::{compile_rules, is_interface_matching};
+
+        let rules = compile_rules(&[Some("^eth".to_string()), Some("wg0".to_string())]);
+        assert!(is_interface_matching("eth0", false, &rules));
+        assert!(is_interface_matching("wg0", false, &rules));
+        assert!(!is_interface_matching("docker0", false, &rules));
+        assert!(!is_interface_matching("eth0", true, &rules));
+
+        // Empty or [None] rules include every non-loopback interface
+        let empty = compile_rules(&[]);
+        assert!(is_interface_matching("docker0", false, &empty));
+        assert!(!is_interface_matching("lo", true, &empty));
+
+        let none = compile_rules(&[None]);
+        assert!(is_interface_matching("docker0", false, &none));
+        assert!(!is_interface_matching("lo", true, &none));
+    }
+
+    #[test]
+    fn test_parse_interface_event() {
+        use crate::interface::events::{parse_event, InterfaceEvent};
+        use netlink_packet_core::NetlinkMessage;
+        use netlink_packet_route::RouteNetlinkMessage;
+        use netlink_packet_route::address::AddressMessage;
+        use netlink_packet_route::link::LinkMessage;
+
+        let mut addr = AddressMessage::default();
+        addr.header.index = 3;
+        let message = NetlinkMessage::from(RouteNetlinkMessage::DelAddress(addr));
+        assert!(matches!(parse_event(message), Some(InterfaceEvent::Address { index: 3 })));
+
+        let mut link = LinkMessage::default();
+        link.header.index = 4;
+        link.attributes.push(LinkAttribute::IfName("eth1".to_string()));
+        let message = NetlinkMessage::from(RouteNetlinkMessage::DelLink(link));
+        match parse_event(message) {
+            Some(InterfaceEvent::Link { link, removed }) => {
+                assert!(removed);
+                assert_eq!(link.header.index, 4);
+                assert_eq!(get_interface_name_from_attribute(link.attributes), Some("eth1".to_string()));
+            }
+            other => panic!("Unexpected event: {other:?}"),
+        }
+
+        let message = NetlinkMessage::from(RouteNetlinkMessage::NewLink(LinkMessage::default()));
+        assert!(matches!(parse_event(message), Some(InterfaceEvent::Link { removed: false, .. })));
+
+        let message = NetlinkMessage::from(RouteNetlinkMessage::GetLink(LinkMessage::default()));
+        assert!(parse_event(message).is_none());
+    }
+
+    #[test]
+    fn test_parse_link() {
+        use crate::interface::get_link::parse_link;
+        use netlink_packet_route::link::{InfoKind, LinkInfo, LinkMessage, State};
+
+        let mut link = LinkMessage::default();
+        link.header.index = 7;
+        link.header.flags = vec![LinkFlag::Up];
+        link.attributes = vec![
+            LinkAttribute::IfName("bond0.100".to_string()),
+            LinkAttribute::OperState(State::LowerLayerDown),
+            LinkAttribute::Carrier(0),
+            LinkAttribute::CarrierChanges(4),
+            LinkAttribute::Mtu(9000),
+            LinkAttribute::TxQueueLen(1000),
+            LinkAttribute::Controller(3),
+            LinkAttribute::Address(vec![0x02, 0x42, 0xac, 0x11, 0x00, 0x02]),
+            LinkAttribute::LinkInfo(vec![LinkInfo::Kind(InfoKind::Vlan)]),
+        ];
+
+        let interface = parse_link(&link, "test", "").unwrap();
+        assert_eq!(interface.interface, "bond0.100");
+        assert_eq!(interface.ifindex, 7);
+        assert_eq!(interface.master_ifindex, Some(3));
+        assert_eq!(interface.kind.as_deref(), Some("vlan"));
+        assert_eq!(interface.mac.as_deref(), Some("02:42:ac:11:00:02"));
+        assert_eq!(interface.oper_state, "lowerlayerdown");
+        assert!(interface.admin_up);
+        assert!(!interface.carrier);
+        assert_eq!(interface.carrier_changes, 4);
+        assert_eq!(interface.mtu, 9000);
+        assert_eq!(interface.txqlen, 1000);
+
+        // Links without a name are skipped
+        assert!(parse_link(&LinkMessage::default(), "test", "").is_none());
+    }
+
+    #[test]
+    fn test_parse_link_hierarchy() {
+        use crate::interface::get_link::parse_link;
+        use netlink_packet_route::link::{BondPortState, InfoBond, InfoBondPort, InfoData, InfoKind, InfoPortData,
+            InfoPortKind, InfoVlan, InfoVxlan, LinkInfo, LinkMessage};
+
+        let link = |index: u32, name: &str, attributes: Vec<LinkAttribute>| {
+            let mut link = LinkMessage::default();
+            link.header.index = index;
+            link.attributes = [vec![LinkAttribute::IfName(name.to_string())], attributes].concat();
+            link
+        };
+
+        let bond = link(3, "bond0", vec![LinkAttribute::LinkInfo(vec![
+            LinkInfo::Kind(InfoKind::Bond),
+            LinkInfo::Data(InfoData::Bond(vec![InfoBond::Mode(4)])),
+        ])]);
+        let bond = parse_link(&bond, "test", "").unwrap();
+        assert_eq!(bond.bond_mode.as_deref(), Some("802.3ad"));
+
+        let slave = link(4, "eth0", vec![LinkAttribute::Controller(3), LinkAttribute::LinkInfo(vec![
+            LinkInfo::PortKind(InfoPortKind::Bond),
+            LinkInfo::PortData(InfoPortData::BondPort(vec![InfoBondPort::BondPortState(BondPortState::Backup)])),
+        ])]);
+        let slave = parse_link(&slave, "test", "").unwrap();
+        assert_eq!((slave.master_ifindex, slave.slave_state.as_deref()), (Some(3), Some("backup")));
+        assert_eq!(slave.kind, None);
+
+        let vlan = link(7, "bond0.100", vec![LinkAttribute::Link(3), LinkAttribute::LinkInfo(vec![
+            LinkInfo::Kind(InfoKind::Vlan),
+            LinkInfo::Data(InfoData::Vlan(vec![InfoVlan::Id(100)])),
+        ])]);
+        let vlan = parse_link(&vlan, "test", "").unwrap();
+        assert_eq!((vlan.parent_ifindex, vlan.vlan_id), (Some(3), Some(100)));
+
+        let vxlan = link(8, "vxlan100", vec![LinkAttribute::Link(7), LinkAttribute::LinkInfo(vec![
+            LinkInfo::Kind(InfoKind::Vxlan),
+            LinkInfo::Data(InfoData::Vxlan(vec![InfoVxlan::Id(10100), InfoVxlan::Group(vec![192, 0, 2, 50])])),
+        ])]);
+        let vxlan = parse_link(&vxlan, "test", "").unwrap();
+        assert_eq!(vxlan.parent_ifindex, Some(7));
+        assert_eq!(vxlan.vxlan_vni, Some(10100));
+        assert_eq!(vxlan.vxlan_remote, Some(std::net::Ipv4Addr::new(192, 0, 2, 50).to_ipv6_mapped()));
+
+        // The peer of a veth is not its parent, neither is a device in another namespace
+        let veth = link(9, "veth0", vec![LinkAttribute::Link(10), LinkAttribute::LinkInfo(vec![LinkInfo::Kind(InfoKind::Veth)])]);
+        assert_eq!(parse_link(&veth, "test", "").unwrap().parent_ifindex, None);
+        let tunnel = link(11, "ipip0", vec![LinkAttribute::Link(2), LinkAttribute::NetnsId(1)]);
+        assert_eq!(parse_link(&tunnel, "test", "").unwrap().parent_ifindex, None);
+    }
+
+    #[test]
+    fn test_compare_links() {
+        use crate::db::schema::Interface;
+        use crate::interface::get_link::compare_links;
+
+        let link = |name: &str, oper_state: &str| Interface {
+            server_id: "test".to_string(),
+            netns: String::new(),
+            interface: name.to_string(),
+            ifindex: 2,
+            master_ifindex: None,
+            kind: None,
+            parent_ifindex: None,
+            bond_mode: None,
+            slave_state: None,
+            vlan_id: None,
+            vxlan_vni: None,
+            vxlan_remote: None,
+            mac: None,
+            oper_state: oper_state.to_string(),
+            admin_up: true,
+            carrier: oper_state == "up",
+            carrier_changes: 0,
+            mtu: 1500,
+            txqlen: 1000,
+            speed: Some(1000),
+            duplex: Some("full".to_string())
+        };
+
+        let fresh = vec![link("eth0", "down"), link("eth1", "up")];
+        let db = vec![link("eth0", "up"), link("eth2", "up")];
+
+        let diff = compare_links(&fresh, &db);
+        assert_eq!(diff.updates.len(), 1);
+        assert_eq!(diff.updates[0].interface, "eth0");
+        assert_eq!(diff.updates[0].oper_state, "down");
+        assert_eq!(diff.creates.len(), 1);
+        assert_eq!(diff.creates[0].interface, "eth1");
+        assert_eq!(diff.deletes.len(), 1);
+        assert_eq!(diff.deletes[0].interface, "eth2");
+
+        let diff = compare_links(&fresh, &fresh);
+        assert!(diff.updates.is_empty() && diff.creates.is_empty() && diff.deletes.is_empty());
+    }
+
+    #[test]
+    fn test_link_events() {
+        use crate::db::schema::Interface;
+        use crate::interface::get_link::{collect_link_events, link_events};
+
+        let eth0 = Interface {
+            server_id: "test".to_string(),
+            netns: String::new(),
+            interface: "eth0".to_string(),
+            ifindex: 2,
+            master_ifindex: None,
+            kind: None,
+            parent_ifindex: None,
+            bond_mode: None,
+            slave_state: None,
+            vlan_id: None,
+            vxlan_vni: None,
+            vxlan_remote: None,
+            mac: None,
+            oper_state: "up".to_string(),
+            admin_up: true,
+            carrier: true,
+            carrier_changes: 2,
+            mtu: 1500,
+            txqlen: 1000,
+            speed: Some(1000),
+            duplex: Some("full".to_string())
+        };
+
+        // Carrier lost
+        let down = Interface { oper_state: "down".to_string(), carrier: false, carrier_changes: 3, ..eth0.clone() };
+        let events = link_events(Some(&eth0), Some(&down), 1000);
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].event, "oper_state");
+        assert_eq!((events[0].old_value.as_str(), events[0].new_value.as_str()), ("up", "down"));
+        assert_eq!(events[1].event, "carrier");
+        assert_eq!(events[1].timestamp, 1000);
+
+        // Flapped between two observations
+        let flapped = Interface { carrier_changes: 4, ..eth0.clone() };
+        let events = link_events(Some(&eth0), Some(&flapped), 1000);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].event, "carrier_flap");
+        assert_eq!((events[0].old_value.as_str(), events[0].new_value.as_str()), ("2", "4"));
+
+        // Administratively down and renamed
+        let renamed = Interface { interface: "uplink0".to_string(), admin_up: false, ..eth0.clone() };
+        let events = link_events(Some(&eth0), Some(&renamed), 1000);
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].event, "renamed");
+        assert_eq!(events[0].interface, "uplink0");
+        assert_eq!((events[0].old_value.as_str(), events[0].new_value.as_str()), ("eth0", "uplink0"));
+        assert_eq!(events[1].event, "admin_state");
+        assert_eq!(events[1].new_value, "down");
+
+        assert!(link_events(Some(&eth0), Some(&eth0), 1000).is_empty());
+
+        // Enslaved to a bond, then failed over
+        let enslaved = Interface { master_ifindex: Some(3), slave_state: Some("active".to_string()), ..eth0.clone() };
+        let events = link_events(Some(&eth0), Some(&enslaved), 1000);
+        assert_eq!(events.len(), 1);
+        assert_eq!((events[0].event.as_str(), events[0].old_value.as_str(), events[0].new_value.as_str()), ("master", "", "3"));
+        let backup = Interface { slave_state: Some("backup".to_string()), ..enslaved.clone() };
+        let events = link_events(Some(&enslaved), Some(&backup), 1000);
+        assert_eq!(events.len(), 1);
+        assert_eq!((events[0].event.as_str(), events[0].new_value.as_str()), ("slave_state", "backup"));
+
+        // Creation and deletion matched by name
+        let eth1 = Interface { interface: "eth1".to_string(), ..eth0.clone() };
+        let events = collect_link_events(std::slice::from_ref(&eth0), &[eth1], 1000);
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].event, "created");
+        assert_eq!(events[0].interface, "eth1");
+        assert_eq!(events[1].event, "deleted");
+        assert_eq!(events[1].interface, "eth0");
+    }
+
+    #[test]
+    fn test_parse_link_settings() {
+        use crate::interface::get_link::parse_link_settings;
+
+        // 10G full duplex, link mode masks left out
+        let mut settings = vec![0u32; 12];
+        settings[1] = 10000;
+        settings[2] = u32::from_ne_bytes([1, 0, 0, 0]);
+        assert_eq!(parse_link_settings(&settings), (Some(10000), Some("full".to_string())));
+
+        // No link, SPEED_UNKNOWN and DUPLEX_UNKNOWN
+        settings[1] = u32::MAX;
+        settings[2] = u32::from_ne_bytes([0xff, 0, 0, 0]);
+        assert_eq!(parse_link_settings(&settings), (None, None));
+    }
+
+    #[test]
+    fn test_apply_diff_keeps_updates_when_delete_fails() {
+        use std::sync::Mutex;
+        use tokio::runtime::Runtime;
+        use crate::interface::diff::{apply_diff, Diff};
+
+        let diff = || Diff { updates: vec!["changed"], deletes: vec!["gone"], creates: vec!["new"] };
+        let added = Mutex::new(Vec::new());
+
+        let rt = Runtime::new().unwrap();
+        rt.block_on(apply_diff(diff(), "rows",
+            |_| async { Err("unreachable") },
+            |creates| async { added.lock().unwrap().extend(creates); Ok(()) }));
+        // The old row of "changed" is still in the table, only the new row is inserted
+        assert_eq!(*added.lock().unwrap(), vec!["new"]);
+
+        added.lock().unwrap().clear();
+        rt.block_on(apply_diff(diff(), "rows",
+            |_| async { Ok::<(), &str>(()) },
+            |creates| async { added.lock().unwrap().extend(creates); Ok(()) }));
+        assert_eq!(*added.lock().unwrap(), vec!["new", "changed"]);
+    }
 }
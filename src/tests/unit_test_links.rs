@@ -0,0 +1,79 @@
+#[cfg(test)]
+mod links_tests {
+    use std::net::{Ipv4Addr, Ipv6Addr};
+    use crate::db::schema::{Addr, Link};
+    use crate::server::links::{compare_discovered_links, discover_links};
+
+    fn v4(a: u8, b: u8, c: u8, d: u8, prefix: u8) -> (Option<Ipv6Addr>, Option<u8>) {
+        (Some(Ipv4Addr::new(a, b, c, d).to_ipv6_mapped()), Some(prefix))
+    }
+
+    fn addr(server_id: &str, interface: &str, ipv6: Vec<(Option<Ipv6Addr>, Option<u8>)>, ipv6_peer: Vec<(Option<Ipv6Addr>, Option<u8>)>) -> Addr {
+        Addr {
+            server_id: server_id.to_string(),
+            netns: String::new(),
+            interface: interface.to_string(),
+            ipv6,
+            ipv6_peer
+        }
+    }
+
+    fn link(iface_a: &str, server_b: &str, iface_b: &str, kind: &str) -> Link {
+        Link {
+            server_a: "a".to_string(),
+            netns_a: String::new(),
+            iface_a: iface_a.to_string(),
+            server_b: server_b.to_string(),
+            netns_b: String::new(),
+            iface_b: iface_b.to_string(),
+            kind: kind.to_string()
+        }
+    }
+
+    #[test]
+    fn test_discover_links() {
+        let own = vec![
+            // Point-to-point tunnel, also in the same /30 as the other end
+            addr("a", "tun0", vec![v4(10, 9, 0, 1, 30)], vec![v4(10, 9, 0, 2, 30)]),
+            addr("a", "eth0", vec![v4(192, 0, 2, 10, 24), (Some("2001:db8::10".parse().unwrap()), Some(64))], vec![]),
+            addr("a", "docker0", vec![v4(172, 17, 0, 1, 16)], vec![]),
+            addr("a", "lo0", vec![v4(203, 0, 113, 1, 32)], vec![]),
+        ];
+        let others = vec![
+            addr("b", "tun0", vec![v4(10, 9, 0, 2, 30)], vec![v4(10, 9, 0, 1, 30)]),
+            addr("b", "eth1", vec![(Some("2001:db8::20".parse().unwrap()), Some(64))], vec![]),
+            // Same default bridge address everywhere
+            addr("b", "docker0", vec![v4(172, 17, 0, 1, 16)], vec![]),
+            // Anycast address, host prefix
+            addr("c", "lo0", vec![v4(203, 0, 113, 2, 32)], vec![]),
+            addr("c", "eth0", vec![v4(192, 0, 2, 11, 24)], vec![]),
+            // Same network, different prefix
+            addr("c", "eth1", vec![v4(192, 0, 2, 12, 25)], vec![]),
+        ];
+
+        let mut links = discover_links(&own, &others);
+        links.sort_by(|a, b| (&a.iface_a, &a.server_b).cmp(&(&b.iface_a, &b.server_b)));
+        assert_eq!(links, vec![
+            link("eth0", "b", "eth1", "subnet"),
+            link("eth0", "c", "eth0", "subnet"),
+            link("tun0", "b", "tun0", "peer"),
+        ]);
+    }
+
+    #[test]
+    fn test_compare_discovered_links() {
+        let db = vec![
+            link("eth0", "b", "eth1", "subnet"),
+            link("eth0", "c", "eth0", "subnet"),
+        ];
+        let fresh = vec![
+            link("eth0", "b", "eth1", "peer"),
+            link("tun0", "b", "tun0", "peer"),
+        ];
+
+        let diff = compare_discovered_links(&fresh, &db);
+        assert_eq!(diff.updates, vec![link("eth0", "b", "eth1", "peer")]);
+        assert_eq!(diff.creates, vec![link("tun0", "b", "tun0", "peer")]);
+        assert_eq!(diff.deletes, vec![link("eth0", "c", "eth0", "subnet")]);
+    }
+}
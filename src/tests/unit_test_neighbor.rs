@@ -0,0 +1,79 @@
+#[cfg(test)]
+mod neighbor_tests {
+    use std::collections::HashMap;
+    use std::net::{Ipv4Addr, Ipv6Addr};
+    use netlink_packet_route::neighbour::{NeighbourAddress, NeighbourAttribute, NeighbourMessage, NeighbourState};
+    use crate::db::schema::Neighbor;
+    use crate::interface::get_neighbor::{compare_neighbors, parse_neighbor};
+
+    fn message(ifindex: u32, state: NeighbourState, attributes: Vec<NeighbourAttribute>) -> NeighbourMessage {
+        let mut message = NeighbourMessage::default();
+        message.header.ifindex = ifindex;
+        message.header.state = state;
+        message.attributes = attributes;
+        message
+    }
+
+    fn neighbor(interface: &str, ip: Ipv6Addr, mac: Option<&str>, state: &str) -> Neighbor {
+        Neighbor {
+            server_id: "test".to_string(),
+            netns: String::new(),
+            interface: interface.to_string(),
+            ip,
+            mac: mac.map(str::to_string),
+            state: state.to_string()
+        }
+    }
+
+    #[test]
+    fn test_parse_neighbor() {
+        let names = HashMap::from([(2, "eth0".to_string())]);
+        let gateway = Ipv4Addr::new(192, 0, 2, 1);
+
+        let reachable = message(2, NeighbourState::Reachable, vec![
+            NeighbourAttribute::Destination(NeighbourAddress::Inet(gateway)),
+            NeighbourAttribute::LinkLocalAddress(vec![0x02, 0xfc, 0, 0, 0, 0x05]),
+        ]);
+        assert_eq!(parse_neighbor(&reachable, "test", "", &names),
+            Some(neighbor("eth0", gateway.to_ipv6_mapped(), Some("02:fc:00:00:00:05"), "REACHABLE")));
+
+        // Resolution failed, no link-layer address
+        let failed = message(2, NeighbourState::Failed, vec![
+            NeighbourAttribute::Destination(NeighbourAddress::Inet6("fe80::1".parse().unwrap())),
+        ]);
+        assert_eq!(parse_neighbor(&failed, "test", "", &names),
+            Some(neighbor("eth0", "fe80::1".parse().unwrap(), None, "FAILED")));
+
+        // Filtered interface and multicast entries
+        let filtered = message(3, NeighbourState::Stale, vec![NeighbourAttribute::Destination(NeighbourAddress::Inet(gateway))]);
+        assert_eq!(parse_neighbor(&filtered, "test", "", &names), None);
+        let multicast = message(2, NeighbourState::Noarp, vec![
+            NeighbourAttribute::Destination(NeighbourAddress::Inet6("ff02::2".parse().unwrap())),
+        ]);
+        assert_eq!(parse_neighbor(&multicast, "test", "", &names), None);
+    }
+
+    #[test]
+    fn test_compare_neighbors() {
+        let gateway = Ipv4Addr::new(192, 0, 2, 1).to_ipv6_mapped();
+        let peer = Ipv4Addr::new(192, 0, 2, 7).to_ipv6_mapped();
+        let old = Ipv4Addr::new(192, 0, 2, 9).to_ipv6_mapped();
+
+        let db = vec![
+            neighbor("eth0", gateway, Some("02:00:00:00:00:01"), "REACHABLE"),
+            neighbor("eth0", old, Some("02:00:00:00:00:09"), "STALE"),
+            neighbor("eth1", peer, Some("02:00:00:00:00:07"), "STALE"),
+        ];
+        let fresh = vec![
+            neighbor("eth0", gateway, None, "FAILED"),
+            // Same address on another interface
+            neighbor("eth0", peer, Some("02:00:00:00:00:07"), "REACHABLE"),
+            neighbor("eth1", peer, Some("02:00:00:00:00:07"), "STALE"),
+        ];
+
+        let diff = compare_neighbors(&fresh, &db);
+        assert_eq!(diff.updates, vec![neighbor("eth0", gateway, None, "FAILED")]);
+        assert_eq!(diff.creates, vec![neighbor("eth0", peer, Some("02:00:00:00:00:07"), "REACHABLE")]);
+        assert_eq!(diff.deletes, vec![neighbor("eth0", old, Some("02:00:00:00:00:09"), "STALE")]);
+    }
+}
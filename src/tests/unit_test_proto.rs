@@ -0,0 +1,84 @@
+#[cfg(test)]
+mod proto_tests {
+    use crate::db::schema::ProtoStat;
+    use crate::system::get_proto_stats::proto_deltas;
+    use crate::system::procfs::{parse_snmp, parse_snmp6, ProtoCounters};
+
+    fn key(protocol: &str, counter: &str) -> (String, String) {
+        (protocol.to_string(), counter.to_string())
+    }
+
+    #[test]
+    fn test_parse_snmp() {
+        let snmp = "Ip: Forwarding DefaultTTL InReceives InDiscards\n\
+            Ip: 2 64 9077 3\n\
+            Tcp: RtoAlgorithm RtoMin RtoMax MaxConn ActiveOpens CurrEstab RetransSegs\n\
+            Tcp: 1 200 120000 -1 55 6 12\n\
+            Udp: InDatagrams RcvbufErrors\n\
+            Udp: 26 4\n";
+        let counters = parse_snmp(snmp);
+        assert_eq!(counters.len(), 6);
+        assert_eq!(counters[&key("Ip", "InDiscards")], 3);
+        assert_eq!(counters[&key("Tcp", "RetransSegs")], 12);
+        assert_eq!(counters[&key("Udp", "RcvbufErrors")], 4);
+        // Settings and gauges are left out
+        assert!(!counters.contains_key(&key("Tcp", "MaxConn")));
+        assert!(!counters.contains_key(&key("Tcp", "CurrEstab")));
+
+        let netstat = "TcpExt: SyncookiesSent ListenOverflows ListenDrops\n\
+            TcpExt: 0 7 9\n\
+            IpExt: InNoRoutes InOctets\n\
+            IpExt: 0 123456789\n";
+        let counters = parse_snmp(netstat);
+        assert_eq!(counters[&key("TcpExt", "ListenDrops")], 9);
+        assert_eq!(counters[&key("IpExt", "InOctets")], 123456789);
+
+        let snmp6 = "Ip6InReceives                   \t3\n\
+            Ip6InDiscards                   \t1\n\
+            Icmp6InType135                  \t2\n\
+            UdpLite6InErrors                \t0\n";
+        let counters = parse_snmp6(snmp6);
+        assert_eq!(counters.len(), 4);
+        assert_eq!(counters[&key("Ip6", "InDiscards")], 1);
+        assert_eq!(counters[&key("Icmp6", "InType135")], 2);
+        assert_eq!(counters[&key("UdpLite6", "InErrors")], 0);
+    }
+
+    #[test]
+    fn test_proto_deltas() {
+        let template = ProtoStat { server_id: "test".to_string(), interval_ms: 10_000, ..Default::default() };
+        let old = ProtoCounters::from([
+            (key("Tcp", "RetransSegs"), 100),
+            (key("Udp", "RcvbufErrors"), 4),
+            (key("Ip", "InDiscards"), 3_500_000_000),
+        ]);
+        let current = ProtoCounters::from([
+            (key("Tcp", "RetransSegs"), 150),
+            (key("Udp", "RcvbufErrors"), 4),
+            (key("Ip", "InDiscards"), 3_500_000_004),
+            // Not in the baseline
+            (key("TcpExt", "ListenDrops"), 7),
+        ]);
+
+        let (mut stats, resets) = proto_deltas(&current, &old, &template);
+        stats.sort_by(|a, b| a.protocol.cmp(&b.protocol));
+        assert_eq!(stats.len(), 2);
+        assert_eq!((stats[0].protocol.as_str(), stats[0].counter.as_str(), stats[0].value), ("Ip", "InDiscards", 4));
+        assert_eq!((stats[1].protocol.as_str(), stats[1].counter.as_str(), stats[1].value), ("Tcp", "RetransSegs", 50));
+        assert_eq!(stats[1].server_id, "test");
+        assert_eq!(stats[1].interval_ms, 10_000);
+        assert!(resets.is_empty());
+
+        // Only the counter that went back is skipped and recorded, also near the top of the
+        // 32-bit range as it isn't a wraparound
+        let partly_reset = ProtoCounters::from([
+            (key("Tcp", "RetransSegs"), 160),
+            (key("Ip", "InDiscards"), 2),
+        ]);
+        let (stats, resets) = proto_deltas(&partly_reset, &old, &template);
+        assert_eq!(stats.len(), 1);
+        assert_eq!((stats[0].counter.as_str(), stats[0].value), ("RetransSegs", 60));
+        assert_eq!(resets.len(), 1);
+        assert_eq!((resets[0].server_id.as_str(), resets[0].reason.as_str()), ("test", "Ip InDiscards"));
+    }
+}
@@ -0,0 +1,100 @@
+#[cfg(test)]
+mod qdisc_tests {
+    use std::time::Duration;
+    use netlink_packet_route::tc::{TcAttribute, TcHandle, TcMessage, TcStats2, TcStatsBasic, TcStatsQueue};
+    use crate::db::schema::QdiscStat;
+    use crate::interface::get_qdisc::{format_tc_handle, get_tc_stats, save_qdisc_stat};
+    use crate::interface::sample::Baselines;
+
+    fn qdisc(parent: &str, kind: &str, bytes: u64, drops: u64) -> QdiscStat {
+        QdiscStat {
+            server_id: "test".to_string(),
+            interface: "eth0".to_string(),
+            timestamp: 1000,
+            timestamp_ms: 1_000_000,
+            kind: kind.to_string(),
+            handle: "none".to_string(),
+            parent: parent.to_string(),
+            bytes,
+            packets: bytes / 100,
+            drops,
+            backlog: 300,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_get_tc_stats() {
+        let mut basic = TcStatsBasic::default();
+        basic.bytes = 5000;
+        basic.packets = 50;
+        let mut queue = TcStatsQueue::default();
+        queue.drops = 3;
+        queue.overlimits = 7;
+        queue.requeues = 1;
+        queue.backlog = 1500;
+        queue.qlen = 1;
+
+        let mut message = TcMessage::default();
+        message.header.handle = TcHandle { major: 1, minor: 0 };
+        message.header.parent = TcHandle::ROOT;
+        message.attributes.push(TcAttribute::Kind("htb".to_string()));
+        message.attributes.push(TcAttribute::Stats2(vec![TcStats2::Basic(basic), TcStats2::Queue(queue)]));
+
+        let stat = get_tc_stats(&message, "test", "blue", "eth0", false, Duration::from_millis(1_000_500)).unwrap();
+        assert_eq!(stat.kind, "htb");
+        assert_eq!((stat.handle.as_str(), stat.parent.as_str()), ("1:", "root"));
+        assert_eq!((stat.bytes, stat.packets), (5000, 50));
+        assert_eq!((stat.drops, stat.overlimits, stat.requeues), (3, 7, 1));
+        assert_eq!((stat.backlog, stat.qlen), (1500, 1));
+        assert_eq!((stat.timestamp, stat.timestamp_ms), (1000, 1_000_500));
+        assert_eq!(stat.netns, "blue");
+
+        // Nothing to report without statistics
+        let mut message = TcMessage::default();
+        message.attributes.push(TcAttribute::Kind("noqueue".to_string()));
+        assert!(get_tc_stats(&message, "test", "", "eth0", false, Duration::ZERO).is_none());
+    }
+
+    #[test]
+    fn test_format_tc_handle() {
+        assert_eq!(format_tc_handle(TcHandle { major: 1, minor: 0x10 }), "1:10");
+        assert_eq!(format_tc_handle(TcHandle { major: 0x8001, minor: 0 }), "8001:");
+        assert_eq!(format_tc_handle(TcHandle::INGRESS), "ingress");
+        assert_eq!(format_tc_handle(TcHandle::UNSPEC), "none");
+    }
+
+    #[test]
+    fn test_save_qdisc_stat() {
+        let mut last_stats = Baselines::default();
+
+        // Two children of mq, both without a handle
+        let (baseline, _) = save_qdisc_stat(&mut last_stats, vec![qdisc("1:1", "fq_codel", 1000, 1), qdisc("1:2", "fq_codel", 5000, 0)]);
+        assert!(baseline.is_empty());
+
+        let (mut stats, _) = save_qdisc_stat(&mut last_stats, vec![
+            QdiscStat { timestamp_ms: 1_001_000, ..qdisc("1:1", "fq_codel", 1500, 4) },
+            QdiscStat { timestamp_ms: 1_001_000, ..qdisc("1:2", "fq_codel", 5200, 0) },
+        ]);
+        stats.sort_by(|a, b| a.parent.cmp(&b.parent));
+        assert_eq!(stats.len(), 2);
+        assert_eq!((stats[0].bytes, stats[0].drops, stats[0].interval_ms), (500, 3, 1000));
+        assert_eq!((stats[1].bytes, stats[1].drops), (200, 0));
+        // Gauges are kept as they are
+        assert_eq!(stats[0].backlog, 300);
+
+        // Replaced by another qdisc, or counters reset
+        let (stats, mut resets) = save_qdisc_stat(&mut last_stats, vec![qdisc("1:1", "cake", 10, 0), qdisc("1:2", "fq_codel", 100, 0)]);
+        assert!(stats.is_empty());
+        resets.sort_by(|a, b| a.reason.cmp(&b.reason));
+        let reasons: Vec<&str> = resets.iter().map(|reset| reset.reason.as_str()).collect();
+        assert_eq!(reasons, vec!["qdisc none parent 1:1", "qdisc none parent 1:2"]);
+
+        // 1:2 is gone, it starts over from a baseline when it comes back
+        let (stats, resets) = save_qdisc_stat(&mut last_stats, vec![qdisc("1:1", "cake", 60, 0)]);
+        assert_eq!(stats[0].bytes, 50);
+        assert!(resets.is_empty());
+        let (stats, _) = save_qdisc_stat(&mut last_stats, vec![qdisc("1:1", "cake", 70, 0), qdisc("1:2", "fq_codel", 200, 0)]);
+        assert_eq!(stats.len(), 1);
+    }
+}
@@ -0,0 +1,125 @@
+#[cfg(test)]
+mod route_tests {
+    use std::collections::HashMap;
+    use std::net::{Ipv4Addr, Ipv6Addr};
+    use netlink_packet_route::AddressFamily;
+    use netlink_packet_route::route::{RouteAddress, RouteAttribute, RouteMessage, RouteNextHop, RouteProtocol, RouteType};
+    use crate::db::schema::Route;
+    use crate::interface::get_route::{compare_routes, parse_route};
+
+    fn names() -> HashMap<u32, String> {
+        HashMap::from([(2, "eth0".to_string()), (3, "eth1".to_string())])
+    }
+
+    fn message(family: AddressFamily, table: u8, kind: RouteType, attributes: Vec<RouteAttribute>) -> RouteMessage {
+        let mut message = RouteMessage::default();
+        message.header.address_family = family;
+        message.header.table = table;
+        message.header.kind = kind;
+        message.header.protocol = RouteProtocol::Static;
+        message.attributes = attributes;
+        message
+    }
+
+    fn route(destination: Ipv6Addr, prefix: u8, gateway: Ipv6Addr, interface: &str) -> Route {
+        Route {
+            server_id: "test".to_string(),
+            netns: String::new(),
+            table_id: 254,
+            destination,
+            prefix,
+            metric: 0,
+            gateway,
+            interface: interface.to_string(),
+            protocol: "static".to_string(),
+            scope: "universe".to_string(),
+            kind: "unicast".to_string()
+        }
+    }
+
+    #[test]
+    fn test_parse_default_route() {
+        let gateway = Ipv4Addr::new(192, 0, 2, 1);
+        let message = message(AddressFamily::Inet, 254, RouteType::Unicast, vec![
+            RouteAttribute::Table(254),
+            RouteAttribute::Gateway(RouteAddress::Inet(gateway)),
+            RouteAttribute::Oif(2),
+            RouteAttribute::Priority(100),
+        ]);
+
+        let routes = parse_route(&message, "test", "", &names());
+        assert_eq!(routes, vec![Route {
+            metric: 100,
+            ..route(Ipv4Addr::UNSPECIFIED.to_ipv6_mapped(), 0, gateway.to_ipv6_mapped(), "eth0")
+        }]);
+    }
+
+    #[test]
+    fn test_parse_multipath_route() {
+        let hop = |index: u32, gateway: &str| {
+            let mut next_hop = RouteNextHop::default();
+            next_hop.interface_index = index;
+            next_hop.attributes = vec![RouteAttribute::Gateway(RouteAddress::Inet6(gateway.parse().unwrap()))];
+            next_hop
+        };
+        let mut message = message(AddressFamily::Inet6, 254, RouteType::Unicast, vec![
+            RouteAttribute::Destination(RouteAddress::Inet6("2001:db8::".parse().unwrap())),
+            // The second uplink is not collected
+            RouteAttribute::MultiPath(vec![hop(2, "fe80::1"), hop(4, "fe80::2")]),
+        ]);
+        message.header.destination_prefix_length = 32;
+
+        let routes = parse_route(&message, "test", "", &names());
+        assert_eq!(routes, vec![route("2001:db8::".parse().unwrap(), 32, "fe80::1".parse().unwrap(), "eth0")]);
+    }
+
+    #[test]
+    fn test_parse_skipped_routes() {
+        // Addresses of the host, in the local table
+        let local = message(AddressFamily::Inet, 255, RouteType::Local, vec![
+            RouteAttribute::Destination(RouteAddress::Inet(Ipv4Addr::new(192, 0, 2, 2))),
+            RouteAttribute::Oif(2),
+        ]);
+        assert!(parse_route(&local, "test", "", &names()).is_empty());
+
+        // No interface, kept
+        let blackhole = message(AddressFamily::Inet, 254, RouteType::BlackHole, vec![
+            RouteAttribute::Destination(RouteAddress::Inet(Ipv4Addr::new(10, 0, 0, 0))),
+        ]);
+        let routes = parse_route(&blackhole, "test", "", &names());
+        assert_eq!(routes.len(), 1);
+        assert_eq!((routes[0].kind.as_str(), routes[0].interface.as_str()), ("blackhole", ""));
+
+        // Tables above 255 are only in the attribute
+        let vrf = message(AddressFamily::Inet, 252, RouteType::Unicast, vec![RouteAttribute::Table(1000), RouteAttribute::Oif(3)]);
+        assert_eq!(parse_route(&vrf, "test", "", &names())[0].table_id, 1000);
+    }
+
+    #[test]
+    fn test_compare_routes() {
+        let default_v4 = Ipv4Addr::UNSPECIFIED.to_ipv6_mapped();
+        let uplink_1 = Ipv4Addr::new(192, 0, 2, 1).to_ipv6_mapped();
+        let uplink_2 = Ipv4Addr::new(198, 51, 100, 1).to_ipv6_mapped();
+        let lan = Ipv4Addr::new(10, 0, 0, 0).to_ipv6_mapped();
+
+        let db = vec![
+            route(default_v4, 0, uplink_1, "eth0"),
+            route(lan, 8, Ipv6Addr::UNSPECIFIED, "eth1"),
+        ];
+        let fresh = vec![
+            // The default route moved to the other uplink
+            route(default_v4, 0, uplink_2, "eth1"),
+            // Now announced by BGP
+            Route { protocol: "bgp".to_string(), ..route(lan, 8, Ipv6Addr::UNSPECIFIED, "eth1") },
+        ];
+
+        let diff = compare_routes(&fresh, &db);
+        assert_eq!(diff.creates, vec![route(default_v4, 0, uplink_2, "eth1")]);
+        assert_eq!(diff.deletes, vec![route(default_v4, 0, uplink_1, "eth0")]);
+        assert_eq!(diff.updates.len(), 1);
+        assert_eq!(diff.updates[0].protocol, "bgp");
+
+        let diff = compare_routes(&fresh, &fresh);
+        assert!(diff.creates.is_empty() && diff.updates.is_empty() && diff.deletes.is_empty());
+    }
+}
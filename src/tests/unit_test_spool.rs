@@ -0,0 +1,108 @@
+#[cfg(test)]
+mod spool_tests {
+    use std::fs;
+    use std::path::PathBuf;
+    use tokio::runtime::Runtime;
+    use crate::db::memory_store::MemoryStore;
+    use crate::db::schema::Stat;
+    use crate::db::spool::Spool;
+
+    fn spool_dir(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("network_map_spool_{name}_{}", std::process::id()));
+        fs::remove_dir_all(&path).ok();
+        path
+    }
+
+    fn stat(interface: &str, rx: u64) -> Stat {
+        Stat {
+            server_id: "test-server".to_string(),
+            interface: interface.to_string(),
+            timestamp: 1000,
+            rx,
+            rx_rate: 1.5,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_spool_keeps_order() {
+        let rt = Runtime::new().unwrap();
+        let path = spool_dir("order");
+        let mut spool = Spool::new(&path, 1024 * 1024).unwrap();
+        assert!(spool.is_empty());
+
+        rt.block_on(spool.push(&[stat("eth0", 1)])).unwrap();
+        rt.block_on(spool.push(&[stat("eth0", 2), stat("eth1", 3)])).unwrap();
+
+        let batches = spool.batches();
+        assert_eq!(batches.len(), 2);
+
+        let first = Spool::read(&batches[0].1).unwrap();
+        assert_eq!(first.len(), 1);
+        assert_eq!(first[0].rx, 1);
+        assert_eq!(first[0].rx_rate, 1.5);
+
+        let second = Spool::read(&batches[1].1).unwrap();
+        assert_eq!(second.len(), 2);
+        assert_eq!(second[1].interface, "eth1");
+
+        // A new spool on the same directory continues the sequence
+        let mut reopened = Spool::new(&path, 1024 * 1024).unwrap();
+        rt.block_on(reopened.push(&[stat("eth0", 4)])).unwrap();
+        let batches = reopened.batches();
+        assert_eq!(batches.len(), 3);
+        assert_eq!(Spool::read(&batches[2].1).unwrap()[0].rx, 4);
+
+        fs::remove_dir_all(&path).ok();
+    }
+
+    #[test]
+    fn test_spool_drops_oldest() {
+        let rt = Runtime::new().unwrap();
+        let path = spool_dir("limit");
+        let batch_size = serde_json::to_vec(&[stat("eth0", 10)]).unwrap().len() as u64;
+
+        // Room for two batches
+        let mut spool = Spool::new(&path, batch_size * 2).unwrap();
+        for rx in 10..13 {
+            rt.block_on(spool.push(&[stat("eth0", rx)])).unwrap();
+        }
+
+        let batches = spool.batches();
+        assert_eq!(batches.len(), 2);
+        assert_eq!(Spool::read(&batches[0].1).unwrap()[0].rx, 11);
+        assert_eq!(Spool::read(&batches[1].1).unwrap()[0].rx, 12);
+        // The dropped file is gone from the disk too
+        assert_eq!(fs::read_dir(&path).unwrap().count(), 2);
+
+        // The size of the batches left over is picked up again on startup
+        let mut reopened = Spool::new(&path, batch_size * 2).unwrap();
+        rt.block_on(reopened.push(&[stat("eth0", 13)])).unwrap();
+        let rx: Vec<u64> = reopened.batches().iter().map(|(_, file)| Spool::read(file).unwrap()[0].rx).collect();
+        assert_eq!(rx, vec![12, 13]);
+
+        fs::remove_dir_all(&path).ok();
+    }
+
+    #[test]
+    fn test_spool_replay_is_bounded() {
+        let rt = Runtime::new().unwrap();
+        let path = spool_dir("bounded");
+        let mut spool = Spool::new(&path, 1024 * 1024).unwrap();
+        for rx in 0..5 {
+            rt.block_on(spool.push(&[stat("eth0", rx)])).unwrap();
+        }
+
+        let store = MemoryStore::default();
+        assert_eq!(rt.block_on(spool.replay(&store, 2)).unwrap(), 2);
+        assert_eq!(spool.batches().len(), 3);
+        assert_eq!(rt.block_on(spool.replay(&store, 10)).unwrap(), 3);
+        assert!(spool.is_empty());
+
+        let replayed: Vec<u64> = store.stats().iter().map(|stat| stat.rx).collect();
+        assert_eq!(replayed, vec![0, 1, 2, 3, 4]);
+        assert_eq!(fs::read_dir(&path).unwrap().count(), 0);
+
+        fs::remove_dir_all(&path).ok();
+    }
+}
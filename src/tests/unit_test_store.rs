@@ -0,0 +1,97 @@
+#[cfg(test)]
+mod store_tests {
+    use std::fs;
+    use std::net::Ipv6Addr;
+    use hyper::Uri;
+    use tokio::runtime::Runtime;
+    use crate::db::memory_store::MemoryStore;
+    use crate::db::schema::{Addr, Server, Stat};
+    use crate::db::spool::Spool;
+    use crate::db::store::{Store, StoreKind};
+    use crate::server::server::add_server_to_database;
+
+    fn server(label: &str) -> Server {
+        Server {
+            server_id: "test".to_string(),
+            hostname: "host".to_string(),
+            label: label.to_string(),
+            lat: 50.0,
+            lng: 14.0,
+            interface_filter: Vec::new(),
+            city: None,
+            country: None,
+            priority: None,
+            center: None
+        }
+    }
+
+    fn addr(netns: &str, interface: &str, last: u16) -> Addr {
+        Addr {
+            server_id: "test".to_string(),
+            netns: netns.to_string(),
+            interface: interface.to_string(),
+            ipv6: vec![(Some(Ipv6Addr::new(0xfd00, 0, 0, 0, 0, 0, 0, last)), Some(64))],
+            ipv6_peer: Vec::new()
+        }
+    }
+
+    #[test]
+    fn test_store_kind() {
+        assert_eq!(StoreKind::parse("clickhouse", None), Ok(StoreKind::ClickHouse));
+        assert_eq!(StoreKind::parse("memory", None), Ok(StoreKind::Memory));
+        assert!(StoreKind::parse("postgres", None).is_err());
+        let endpoint: Uri = "http://127.0.0.1:4318/v1/metrics".parse().unwrap();
+        assert_eq!(StoreKind::parse("otlp", Some(endpoint.clone())), Ok(StoreKind::Otlp(endpoint)));
+        assert!(StoreKind::parse("otlp", None).is_err());
+        assert_eq!(StoreKind::default(), StoreKind::ClickHouse);
+    }
+
+    #[test]
+    fn test_memory_store_server_and_addresses() {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async {
+            let store = MemoryStore::default();
+
+            // Added once, then updated in place
+            add_server_to_database(&store, server("PRG")).await;
+            add_server_to_database(&store, server("VIE")).await;
+            let servers = store.servers();
+            assert_eq!(servers.len(), 1);
+            assert_eq!(servers[0].label, "VIE");
+
+            store.add_addresses(vec![addr("", "eth0", 1), addr("", "eth1", 2), addr("blue", "eth0", 3)]).await.unwrap();
+            store.update_addresses(vec![addr("", "eth0", 9)]).await.unwrap();
+            store.delete_addresses(vec![addr("", "eth1", 2)]).await.unwrap();
+
+            let host = store.get_addresses(&server("VIE"), "").await.unwrap();
+            assert_eq!(host.len(), 1);
+            assert_eq!(host[0].ipv6, addr("", "eth0", 9).ipv6);
+            assert_eq!(store.get_addresses(&server("VIE"), "blue").await.unwrap().len(), 1);
+
+            store.clear_addresses("test").await.unwrap();
+            assert!(store.addresses().is_empty());
+        });
+    }
+
+    #[test]
+    fn test_spool_replays_into_store() {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async {
+            let path = std::env::temp_dir().join(format!("network_map_store_replay_{}", std::process::id()));
+            fs::remove_dir_all(&path).ok();
+
+            let stat = |rx| Stat { server_id: "test".to_string(), interface: "eth0".to_string(), rx, ..Default::default() };
+            let mut spool = Spool::new(&path, 1024 * 1024).unwrap();
+            spool.push(&[stat(1)]).await.unwrap();
+            spool.push(&[stat(2), stat(3)]).await.unwrap();
+
+            let store = MemoryStore::default();
+            assert_eq!(spool.replay(&store, 10).await.unwrap(), 2);
+            assert!(spool.is_empty());
+            let replayed: Vec<u64> = store.stats().iter().map(|stat| stat.rx).collect();
+            assert_eq!(replayed, vec![1, 2, 3]);
+
+            fs::remove_dir_all(&path).ok();
+        });
+    }
+}
@@ -0,0 +1,106 @@
+#[cfg(test)]
+mod system_tests {
+    use crate::db::schema::SystemStat;
+    use crate::system::get_system_stats::system_delta;
+    use crate::system::procfs::{parse_cpu_times, parse_loadavg, parse_meminfo, parse_softnet_stat,
+        CpuTimes, HostSample, LoadAvg, MemInfo, Softnet};
+
+    fn cpu(user: u64, system: u64, idle: u64, softirq: u64) -> CpuTimes {
+        CpuTimes { user, system, idle, softirq, ..Default::default() }
+    }
+
+    fn sample(cpus: Vec<CpuTimes>, softnet: Vec<Softnet>) -> HostSample {
+        let total = cpus.iter().fold(CpuTimes::default(), |total, cpu| CpuTimes {
+            user: total.user + cpu.user,
+            system: total.system + cpu.system,
+            idle: total.idle + cpu.idle,
+            softirq: total.softirq + cpu.softirq,
+            ..Default::default()
+        });
+        HostSample {
+            cpu: total,
+            cpus,
+            load: LoadAvg { load1: 0.5, load5: 0.25, load15: 0.1 },
+            memory: MemInfo { total: 8 << 30, available: 4 << 30, ..Default::default() },
+            softnet,
+        }
+    }
+
+    fn softnet(processed: u64, dropped: u64, time_squeeze: u64) -> Softnet {
+        Softnet { processed, dropped, time_squeeze }
+    }
+
+    #[test]
+    fn test_parse_proc_files() {
+        let stat = "cpu  100 5 50 1000 10 1 4 0 0 0\n\
+            cpu0 60 5 30 400 5 1 4 0 0 0\n\
+            cpu1 40 0 20 600 5 0 0 0 0 0\n\
+            intr 12345 0 0\n\
+            ctxt 67890\n";
+        let (total, cpus) = parse_cpu_times(stat).unwrap();
+        assert_eq!(total, CpuTimes { user: 100, nice: 5, system: 50, idle: 1000, iowait: 10, irq: 1, softirq: 4, steal: 0 });
+        assert_eq!(total.total(), 1170);
+        assert_eq!(total.busy(), 160);
+        assert_eq!(cpus.len(), 2);
+        assert_eq!(cpus[1].idle, 600);
+        assert_eq!(parse_cpu_times("intr 1 2 3\n"), None);
+
+        assert_eq!(parse_loadavg("0.52 0.58 0.59 1/467 12345\n"), Some(LoadAvg { load1: 0.52, load5: 0.58, load15: 0.59 }));
+        assert_eq!(parse_loadavg(""), None);
+
+        let meminfo = "MemTotal:       16318332 kB\n\
+            MemFree:         1034780 kB\n\
+            MemAvailable:    9502564 kB\n\
+            Buffers:          512000 kB\n\
+            Cached:          7800000 kB\n\
+            SwapCached:            0 kB\n\
+            SwapTotal:       2097148 kB\n\
+            SwapFree:        2097148 kB\n\
+            HugePages_Total:       0\n";
+        let memory = parse_meminfo(meminfo).unwrap();
+        assert_eq!(memory.total, 16318332 * 1024);
+        assert_eq!(memory.available, 9502564 * 1024);
+        assert_eq!(memory.cached, 7800000 * 1024);
+        assert_eq!(memory.swap_free, 2097148 * 1024);
+        assert_eq!(parse_meminfo("HugePages_Total:       0\n"), None);
+
+        let softnet_stat = "0001e240 00000000 0000000a 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000\n\
+            000003e8 00000002 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000001\n";
+        assert_eq!(parse_softnet_stat(softnet_stat), vec![softnet(123456, 0, 10), softnet(1000, 2, 0)]);
+    }
+
+    #[test]
+    fn test_system_delta() {
+        let old = sample(vec![cpu(100, 50, 850, 0), cpu(100, 50, 850, 0)],
+            vec![softnet(1000, 0, 0), softnet(u32::MAX as u64 - 10, 5, 1)]);
+        // CPU 1 spends half of the interval in softirq, its softnet counters wrap
+        let curr = sample(vec![cpu(110, 10, 80, 0), cpu(100, 0, 0, 100)]
+            .into_iter().zip(&old.cpus)
+            .map(|(delta, old)| cpu(old.user + delta.user, old.system + delta.system, old.idle + delta.idle, old.softirq + delta.softirq))
+            .collect(),
+            vec![softnet(1500, 0, 0), softnet(20, 7, 4)]);
+
+        let template = SystemStat { server_id: "test".to_string(), interval_ms: 1000, ..Default::default() };
+        let stat = system_delta(&curr, &old, template).unwrap();
+
+        assert_eq!(stat.server_id, "test");
+        assert_eq!(stat.interval_ms, 1000);
+        assert_eq!(stat.cpu_user, 52.5);
+        assert_eq!(stat.cpu_system, 2.5);
+        assert_eq!(stat.cpu_softirq, 25.0);
+        assert_eq!(stat.cpu_idle, 20.0);
+        assert_eq!(stat.cpu_busy_max, 100.0);
+        assert_eq!(stat.cpu_softirq_max, 50.0);
+        assert_eq!(stat.load1, 0.5);
+        assert_eq!(stat.mem_available, 4 << 30);
+        assert_eq!(stat.softnet_processed, 531);
+        assert_eq!(stat.softnet_dropped, 2);
+        assert_eq!(stat.softnet_time_squeeze, 3);
+        assert_eq!(stat.softnet_dropped_per_cpu, vec![0, 2]);
+        assert_eq!(stat.softnet_time_squeeze_per_cpu, vec![0, 3]);
+
+        // A CPU went offline, per CPU values can't be matched
+        let offline = sample(vec![curr.cpus[0].clone()], vec![softnet(1500, 0, 0)]);
+        assert!(system_delta(&offline, &old, SystemStat::default()).is_none());
+    }
+}
@@ -0,0 +1,121 @@
+#[cfg(test)]
+mod tcp_tests {
+    use std::collections::{HashMap, HashSet};
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+    use crate::db::schema::TcpStat;
+    use crate::interface::get_tcp_stats::summarize_connections;
+    use crate::interface::sock_diag::{inet_diag_request, parse_message, TcpConnection};
+
+    fn template() -> TcpStat {
+        TcpStat {
+            server_id: "test".to_string(),
+            netns: String::new(),
+            timestamp: 1000,
+            timestamp_ms: 1_000_000,
+            remote: Ipv6Addr::UNSPECIFIED,
+            remote_prefix: 0,
+            peer_server_id: None,
+            connections: 0,
+            rtt_avg_us: 0.0,
+            rtt_min_us: 0,
+            rtt_max_us: 0,
+            rttvar_avg_us: 0.0,
+            cwnd_avg: 0.0,
+            cwnd_min: 0,
+            retrans: 0,
+            lost: 0,
+            total_retrans: 0
+        }
+    }
+
+    fn connection(dst: [u8; 4], rtt_us: u32, cwnd: u32, total_retrans: u32) -> TcpConnection {
+        TcpConnection {
+            src: Some(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1))),
+            dst: Some(IpAddr::V4(Ipv4Addr::from(dst))),
+            rtt_us,
+            rttvar_us: rtt_us / 2,
+            cwnd,
+            retrans: 0,
+            lost: 0,
+            total_retrans
+        }
+    }
+
+    #[test]
+    fn test_inet_diag_request() {
+        let request = inet_diag_request(10);
+        assert_eq!(request.len(), 56);
+        // AF_INET6, IPPROTO_TCP, INET_DIAG_INFO
+        assert_eq!(&request[..4], &[10, 6, 2, 0]);
+        // TCP_ESTABLISHED only
+        assert_eq!(u32::from_ne_bytes(request[4..8].try_into().unwrap()), 1 << 1);
+    }
+
+    #[test]
+    fn test_parse_inet_diag_message() {
+        let mut info = vec![0u8; 104];
+        info[32..36].copy_from_slice(&1u32.to_ne_bytes());
+        info[36..40].copy_from_slice(&2u32.to_ne_bytes());
+        info[68..72].copy_from_slice(&15_000u32.to_ne_bytes());
+        info[72..76].copy_from_slice(&3_000u32.to_ne_bytes());
+        info[80..84].copy_from_slice(&42u32.to_ne_bytes());
+        info[100..104].copy_from_slice(&7u32.to_ne_bytes());
+
+        // inet_diag_msg of an IPv6 socket connected to an IPv4-mapped address
+        let mut payload = vec![0u8; 72];
+        payload[0] = 10;
+        payload[8..24].copy_from_slice(&Ipv4Addr::new(10, 0, 0, 1).to_ipv6_mapped().octets());
+        payload[24..40].copy_from_slice(&Ipv4Addr::new(192, 0, 2, 7).to_ipv6_mapped().octets());
+        payload.extend_from_slice(&((4 + info.len()) as u16).to_ne_bytes());
+        payload.extend_from_slice(&2u16.to_ne_bytes());
+        payload.extend_from_slice(&info);
+
+        let connection = parse_message(20, &payload).unwrap();
+        assert_eq!(connection.src, Some(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1))));
+        assert_eq!(connection.dst, Some(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 7))));
+        assert_eq!((connection.lost, connection.retrans), (1, 2));
+        assert_eq!((connection.rtt_us, connection.rttvar_us), (15_000, 3_000));
+        assert_eq!(connection.cwnd, 42);
+        assert_eq!(connection.total_retrans, 7);
+
+        // Without tcp_info
+        assert!(parse_message(20, &payload[..72]).is_none());
+        assert!(parse_message(3, &payload).is_none());
+    }
+
+    #[test]
+    fn test_summarize_connections() {
+        let local = HashSet::from([IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1))]);
+        let peers = HashMap::from([(Ipv4Addr::new(203, 0, 113, 5).to_ipv6_mapped(), "peer-1".to_string())]);
+
+        let connections = vec![
+            connection([192, 0, 2, 7], 10_000, 10, 1),
+            connection([192, 0, 2, 8], 30_000, 40, 2),
+            connection([203, 0, 113, 5], 5_000, 20, 0),
+            // Local
+            connection([10, 0, 0, 1], 50, 10, 0),
+            connection([127, 0, 0, 1], 50, 10, 0),
+        ];
+
+        let mut summaries = summarize_connections(&connections, &local, &peers, 24, 64, &template());
+        summaries.sort_by_key(|summary| summary.remote);
+        assert_eq!(summaries.len(), 2);
+
+        let network = &summaries[0];
+        assert_eq!(network.remote, Ipv4Addr::new(192, 0, 2, 0).to_ipv6_mapped());
+        assert_eq!(network.remote_prefix, 24);
+        assert_eq!(network.connections, 2);
+        assert_eq!(network.rtt_avg_us, 20_000.0);
+        assert_eq!((network.rtt_min_us, network.rtt_max_us), (10_000, 30_000));
+        assert_eq!(network.rttvar_avg_us, 10_000.0);
+        assert_eq!((network.cwnd_avg, network.cwnd_min), (25.0, 10));
+        assert_eq!(network.total_retrans, 3);
+        assert_eq!(network.server_id, "test");
+
+        let peer = &summaries[1];
+        assert_eq!(peer.peer_server_id.as_deref(), Some("peer-1"));
+        assert_eq!(peer.remote_prefix, 32);
+        assert_eq!(peer.connections, 1);
+        assert_eq!((peer.rtt_min_us, peer.rtt_max_us), (5_000, 5_000));
+    }
+}
@@ -0,0 +1,119 @@
+#[cfg(test)]
+mod wireguard_tests {
+    use std::collections::HashMap;
+    use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+    use std::time::Duration;
+    use crate::db::schema::WgPeerStat;
+    use crate::interface::get_wg_stats::{save_wg_stat, wg_peer_stat};
+    use crate::interface::sample::Baselines;
+    use crate::interface::wireguard::{encode_key, merge_peers, parse_device, parse_family, WgPeer};
+    use crate::tests::helpers::{nested, nla};
+
+    fn peer(key: u8, rx_bytes: u64, allowed_ips: Vec<(IpAddr, u8)>) -> WgPeer {
+        WgPeer {
+            public_key: vec![key; 32],
+            endpoint: Some(SocketAddr::new(IpAddr::V4(Ipv4Addr::new(203, 0, 113, 5)), 51820)),
+            allowed_ips,
+            last_handshake: Some(Duration::from_secs(990)),
+            rx_bytes,
+            tx_bytes: rx_bytes * 2
+        }
+    }
+
+    fn template() -> WgPeerStat {
+        WgPeerStat {
+            server_id: "test".to_string(),
+            interface: "wg0".to_string(),
+            timestamp: 1000,
+            timestamp_ms: 1_000_000,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_parse_family() {
+        let attributes = [nla(1, &0x15u16.to_ne_bytes()), nla(2, b"wireguard\0")].concat();
+        let message = [&[1u8, 2, 0, 0][..], &attributes].concat();
+        assert_eq!(parse_family(0x10, &message), Some(0x15));
+
+        let other = [&[1u8, 2, 0, 0][..], &nla(1, &0x16u16.to_ne_bytes()), &nla(2, b"nl80211\0")].concat();
+        assert_eq!(parse_family(0x10, &other), None);
+    }
+
+    #[test]
+    fn test_parse_device() {
+        let mut endpoint = vec![0u8; 16];
+        endpoint[..2].copy_from_slice(&2u16.to_ne_bytes());
+        endpoint[2..4].copy_from_slice(&51820u16.to_be_bytes());
+        endpoint[4..8].copy_from_slice(&[203, 0, 113, 5]);
+
+        let handshake = [990i64.to_ne_bytes(), 0i64.to_ne_bytes()].concat();
+        let allowed_ip = nested(0, &[nla(1, &2u16.to_ne_bytes()), nla(2, &[10, 9, 0, 2]), nla(3, &[32])]);
+        let first = nested(0, &[
+            nla(1, &[7; 32]),
+            nla(4, &endpoint),
+            nla(6, &handshake),
+            nla(7, &5000u64.to_ne_bytes()),
+            nla(8, &10000u64.to_ne_bytes()),
+            nested(9, &[allowed_ip]),
+        ]);
+        // Never connected
+        let second = nested(0, &[nla(1, &[8; 32]), nla(6, &[0; 16])]);
+
+        let message = [&[0u8, 1, 0, 0][..], &nla(1, &5u32.to_ne_bytes()), &nested(8, &[first, second])].concat();
+        let peers = parse_device(&message).unwrap();
+
+        assert_eq!(peers, vec![
+            peer(7, 5000, vec![(IpAddr::V4(Ipv4Addr::new(10, 9, 0, 2)), 32)]),
+            WgPeer { public_key: vec![8; 32], ..Default::default() },
+        ]);
+    }
+
+    #[test]
+    fn test_merge_peers() {
+        let tunnel = |last: u8| (IpAddr::V4(Ipv4Addr::new(10, 9, 0, last)), 32);
+
+        let mut peers = vec![peer(1, 100, vec![]), peer(2, 200, vec![tunnel(2)])];
+        // The next message carries the rest of the allowed IPs of the last peer
+        merge_peers(&mut peers, vec![
+            WgPeer { public_key: vec![2; 32], allowed_ips: vec![tunnel(3)], ..Default::default() },
+            peer(3, 300, vec![]),
+        ]);
+
+        assert_eq!(peers.len(), 3);
+        assert_eq!(peers[1].allowed_ips, vec![tunnel(2), tunnel(3)]);
+        assert_eq!(peers[1].rx_bytes, 200);
+    }
+
+    #[test]
+    fn test_encode_key() {
+        assert_eq!(encode_key(b"foob"), "Zm9vYg==");
+        assert_eq!(encode_key(b"fooba"), "Zm9vYmE=");
+        assert_eq!(encode_key(b"foobar"), "Zm9vYmFy");
+        assert_eq!(encode_key(&[0; 32]), format!("{}=", "A".repeat(43)));
+    }
+
+    #[test]
+    fn test_save_wg_stat() {
+        let peers = HashMap::from([(Ipv4Addr::new(203, 0, 113, 5).to_ipv6_mapped(), "peer-1".to_string())]);
+        let mut last_stats = Baselines::default();
+
+        let stat = wg_peer_stat(&peer(7, 1000, vec![]), &template(), &peers);
+        assert_eq!(stat.peer_server_id.as_deref(), Some("peer-1"));
+        assert_eq!(stat.endpoint_port, 51820);
+        assert_eq!(stat.handshake_age_s, Some(10));
+        assert!(save_wg_stat(&mut last_stats, vec![stat]).is_empty());
+
+        let later = WgPeerStat { timestamp_ms: 1_010_000, ..template() };
+        let stats = save_wg_stat(&mut last_stats, vec![wg_peer_stat(&peer(7, 4000, vec![]), &later, &peers)]);
+        assert_eq!((stats[0].rx, stats[0].tx, stats[0].interval_ms), (3000, 6000, 10_000));
+
+        // Removed and added again
+        let stats = save_wg_stat(&mut last_stats, vec![wg_peer_stat(&peer(7, 10, vec![]), &later, &peers)]);
+        assert!(stats.is_empty());
+
+        // Gone for a pass, the peer starts over from a baseline
+        assert!(save_wg_stat(&mut last_stats, vec![wg_peer_stat(&peer(8, 10, vec![]), &later, &peers)]).is_empty());
+        assert!(save_wg_stat(&mut last_stats, vec![wg_peer_stat(&peer(7, 500, vec![]), &later, &peers)]).is_empty());
+    }
+}